@@ -7,6 +7,7 @@
 
 use core::hint::unreachable_unchecked;
 use core::time::Duration;
+use crate::key;
 use crate::types::*;
 
 #[macro_use]
@@ -25,6 +26,8 @@ generate_enums! {
     ////////////
 
     Agree: 1
+    ChangeKeyLocation: 27
+    CombineKey: 28
     // CreateObject: 2
     // TODO: why do Decrypt and DeriveKey both have discriminant 3?!
     Decrypt: 3
@@ -33,21 +36,24 @@ generate_enums! {
     Encrypt: 6
     Delete: 7
     DeleteAllKeys: 25
+    DeletePublicKey: 31
     Exists: 8
     // DeriveKeypair: 3
-    // FindObjects: 9
+    FindObjects: 9
     GenerateKey: 10
     GenerateSecretKey: 11
+    GetKeyInfo: 26
     // GenerateKeypair: 6
     Hash: 12
+    ListKeys: 30
     // TODO: add ReadDir{First,Next}, not loading data, if needed for efficiency
     ReadDirFilesFirst: 13
     ReadDirFilesNext: 14
     ReadFile: 15
-    // ReadCounter: 7
     RandomBytes: 16
     SerializeKey: 17
     Sign: 18
+    SplitKey: 29
     WriteFile: 19
     UnsafeInjectKey: 20
     UnsafeInjectSharedKey: 21
@@ -56,6 +62,7 @@ generate_enums! {
     WrapKey: 24
 
     Attest: 0xFF
+    AttestationStatement: 0xFE
 
     /////////////
     // Storage //
@@ -88,16 +95,58 @@ generate_enums! {
     //////////////
 
     CreateCounter: 50
+    DeleteCounter: 53
     IncrementCounter: 51
+    ListCounters: 54
+    ReadCounter: 52
+    SetCounterAtLeast: 55
 
     //////////////////
     // Certificates //
     //////////////////
 
     DeleteCertificate: 60
+    GenerateCsr: 63
+    IssueCertificate: 64
+    ListCertificates: 66
     ReadCertificate: 61
+    SetCertificateAttributes: 67
+    VerifyCertificateChain: 65
     WriteCertificate: 62
 
+    //////////
+    // PINs //
+    //////////
+
+    SetPin: 70
+    VerifyPin: 71
+    ChangePin: 72
+    ResetPin: 73
+    PinRetries: 74
+    DeletePin: 75
+
+    /////////////
+    // Backups //
+    /////////////
+
+    Backup: 80
+    Restore: 81
+
+    //////////////////
+    // Provisioning //
+    //////////////////
+
+    InstallAttestationCertificates: 91
+    LockProvisioning: 92
+    ProvisionAttestationKey: 90
+
+    //////////
+    // DICE //
+    //////////
+
+    DiceCertificates: 100
+    DiceSign: 101
+
     ///////////
     // Other //
     ///////////
@@ -119,6 +168,16 @@ pub mod request {
             - signing_mechanism: Mechanism
             // only Ed255 + P256
             - private_key: KeyId
+            - template: CertificateTemplate
+
+        AttestationStatement:
+            - format: AttestationStatementFormat
+            // only Ed255 + P256
+            - signing_mechanism: Mechanism
+            // with the attested credential data
+            - authenticator_data: Message
+            // 32 bytes
+            - client_data_hash: ShortData
 
         // // examples:
         // // - store public keys from external source
@@ -136,12 +195,25 @@ pub mod request {
           - nonce: ShortData
           - tag: ShortData
 
+        ChangeKeyLocation:
+          - key: KeyId
+          - location: Location
+
+        CombineKey:
+          - mechanism: Mechanism        // -> implies key type
+          - shares: Vec<ShortData, { crate::config::MAX_SHARES }>
+          - format: ShareFormat
+          - attributes: StorageAttributes
+
         Delete:
           - key: KeyId
 
         DeleteAllKeys:
           - location: Location
 
+        DeletePublicKey:
+          - key: KeyId
+
         // DeleteBlob:
         //   - prefix: Option<Letters>
         //   - name: ShortData
@@ -181,8 +253,13 @@ pub mod request {
           - mechanism: Mechanism
           - key: KeyId
 
-        // FindObjects:
-        //     // - attributes: Attributes
+        // paginated: pass the last `KeyId` of the previous reply as `after`
+        FindObjects:
+          - secrecy: Option<key::Secrecy>
+          - kind: Option<key::Kind>
+          - location: Option<Location>
+          - label: Option<key::Label>
+          - after: Option<KeyId>
 
         GenerateKey:
             - mechanism: Mechanism        // -> implies key type
@@ -205,10 +282,20 @@ pub mod request {
         //     - object: ObjectHandle
         //     - attributes: Attributes
 
+        // metadata of a key, never its material
+        GetKeyInfo:
+          - key: KeyId
+
         Hash:
           - mechanism: Mechanism
           - message: Message
 
+        ListKeys:
+          - kind: Option<key::Kind>
+          - location: Option<Location>
+          // the last key of the previous page
+          - after: Option<(KeyId, key::Secrecy)>
+
         LocateFile:
           - location: Location
           - dir: Option<PathBuf>
@@ -244,10 +331,6 @@ pub mod request {
           - location: Location
           - path: PathBuf
 
-        // use GetAttribute(value) on counter instead
-        // ReadCounter:
-        //     - counter: ObjectHandle
-
         RandomBytes:
           - count: usize
 
@@ -262,6 +345,12 @@ pub mod request {
           - message: Message
           - format: SignatureSerialization
 
+        SplitKey:
+          - key: KeyId
+          - threshold: u8
+          - shares: u8
+          - format: ShareFormat
+
         WriteFile:
           - location: Location
           - path: PathBuf
@@ -313,19 +402,123 @@ pub mod request {
         CreateCounter:
           - location: Location
 
+        DeleteCounter:
+          - id: CounterId
+
+        // returns the value before incrementing
         IncrementCounter:
           - id: CounterId
+          - step: u128
+
+        // paginated: pass the last `CounterId` of the previous reply as `after`
+        ListCounters:
+          - location: Option<Location>
+          - after: Option<CounterId>
+
+        ReadCounter:
+          - id: CounterId
+
+        // never lowers the counter
+        SetCounterAtLeast:
+          - id: CounterId
+          - value: u128
 
         DeleteCertificate:
           - id: CertId
 
+        // only Ed255 + P256
+        GenerateCsr:
+          - mechanism: Mechanism
+          - private_key: KeyId
+          - template: CertificateTemplate
+
+        // only Ed255 + P256, for both keys
+        IssueCertificate:
+          - mechanism: Mechanism
+          - issuer_key: KeyId
+          - issuer: DistinguishedName
+          - subject_key: KeyId
+          - template: CertificateTemplate
+          - location: Location
+
+        // paginated: pass the last `CertId` of the previous reply as `after`
+        ListCertificates:
+          - filter: CertificateFilter
+          - after: Option<CertId>
+
         ReadCertificate:
           - id: CertId
 
+        SetCertificateAttributes:
+          - id: CertId
+          - attributes: CertificateAttributes
+
+        // concatenated DER certificates, the end entity first; `None` for `time`
+        // uses the platform clock, and without one validity periods are not checked
+        VerifyCertificateChain:
+          - chain: Message
+          - anchors: Vec<CertId, { crate::config::MAX_TRUST_ANCHORS }>
+          - time: Option<DateTime>
+
         WriteCertificate:
           - location: Location
           - der: Message
 
+        SetPin:
+          - pin: PinId
+          - value: ShortData
+          - retries: u8
+          // PUK and its retries
+          - puk: Option<(ShortData, u8)>
+
+        VerifyPin:
+          - pin: PinId
+          - value: ShortData
+
+        ChangePin:
+          - pin: PinId
+          - old_value: ShortData
+          - new_value: ShortData
+
+        ResetPin:
+          - pin: PinId
+          - puk: ShortData
+          - new_value: ShortData
+
+        PinRetries:
+          - pin: PinId
+
+        // requires the PIN
+        DeletePin:
+          - pin: PinId
+          - value: ShortData
+
+        Backup:
+          - wrapping_key: KeyId
+          // `None` for the first chunk
+          - cursor: Option<BackupCursor>
+
+        Restore:
+          - wrapping_key: KeyId
+          - chunk: Message
+
+        // the attestation certificate first, each followed by its issuer
+        InstallAttestationCertificates:
+          - mechanism: Mechanism
+          - chain: Message
+
+        LockProvisioning:
+
+        // `None` generates the key
+        ProvisionAttestationKey:
+          - mechanism: Mechanism
+          - raw_key: Option<ShortData>
+
+        DiceCertificates:
+
+        DiceSign:
+          - message: Message
+
     }
 }
 
@@ -345,13 +538,22 @@ pub mod reply {
         Attest:
             - certificate: CertId
 
+        // the CBOR encoded `attStmt`
+        AttestationStatement:
+            - statement: Message
+
+        ChangeKeyLocation:
+
+        CombineKey:
+            - key: KeyId
+
         // CreateObject:
         //     - object: ObjectHandle
 
-        // FindObjects:
-        //     - objects: Vec<ObjectHandle, config::MAX_OBJECT_HANDLES>
-        //     // can be higher than capacity of vector
-        //     - num_objects: usize
+        FindObjects:
+            - objects: Vec<KeyId, { crate::config::MAX_OBJECT_HANDLES }>
+            // can be higher than capacity of vector
+            - num_objects: usize
 
         DebugDumpStore:
 
@@ -364,6 +566,9 @@ pub mod reply {
         DeleteAllKeys:
             - count: usize
 
+        DeletePublicKey:
+            - success: bool
+
         DeriveKey:
             - key: KeyId
 
@@ -388,6 +593,22 @@ pub mod reply {
         GenerateSecretKey:
             - key: KeyId
 
+        GetKeyInfo:
+            - secrecy: key::Secrecy
+            - kind: key::Kind
+            - flags: key::Flags
+            - location: Location
+            // in bytes
+            - size: usize
+            - policy: key::Policy
+            - limits: key::Limits
+            - metadata: key::Metadata
+
+        ListKeys:
+            - keys: Vec<KeyListing, { crate::config::MAX_OBJECT_HANDLES }>
+            // can be higher than capacity of vector
+            - num_keys: usize
+
         // GenerateKeypair:
         //     - private_key: KeyId
         //     - public_key: KeyId
@@ -420,9 +641,6 @@ pub mod reply {
 
         RemoveFile:
 
-        // ReadCounter:
-        //     - counter: u32
-
         RandomBytes:
             - bytes: Message
 
@@ -432,6 +650,9 @@ pub mod reply {
         Sign:
             - signature: Signature
 
+        SplitKey:
+            - shares: Vec<ShortData, { crate::config::MAX_SHARES }>
+
         WriteFile:
 
         Verify:
@@ -463,16 +684,94 @@ pub mod reply {
         CreateCounter:
           - id: CounterId
 
+        DeleteCounter:
+
         IncrementCounter:
           - counter: u128
 
+        ListCounters:
+            - counters: Vec<CounterListing, { crate::config::MAX_OBJECT_HANDLES }>
+            // can be higher than capacity of vector
+            - num_counters: usize
+
+        ReadCounter:
+          - counter: u128
+
+        SetCounterAtLeast:
+          - counter: u128
+
         DeleteCertificate:
 
+        GenerateCsr:
+          - csr: Message
+
+        IssueCertificate:
+          - certificate: CertId
+
+        ListCertificates:
+            - certificates: Vec<CertificateListing, { crate::config::MAX_OBJECT_HANDLES }>
+            // can be higher than capacity of vector
+            - num_certificates: usize
+
         ReadCertificate:
           - der: Message
+          - attributes: CertificateAttributes
+
+        SetCertificateAttributes:
+
+        VerifyCertificateChain:
+          - anchor: CertId
 
         WriteCertificate:
           - id: CertId
+
+        SetPin:
+
+        VerifyPin:
+          - success: bool
+          - retries_left: u8
+
+        ChangePin:
+          - success: bool
+          - retries_left: u8
+
+        ResetPin:
+          - success: bool
+          // of the PUK
+          - retries_left: u8
+
+        PinRetries:
+          - retries_left: u8
+          - puk_retries_left: Option<u8>
+
+        DeletePin:
+          - success: bool
+          - retries_left: u8
+
+        Backup:
+          - chunk: Message
+          // `None` after the last chunk
+          - next: Option<BackupCursor>
+
+        Restore:
+          - index: u32
+          - last: bool
+
+        InstallAttestationCertificates:
+
+        LockProvisioning:
+
+        // Ed255: 32 bytes, P256: the 64 bytes of the uncompressed point without its 0x04
+        ProvisionAttestationKey:
+          - public_key: ShortData
+
+        // the Alias certificate, followed by the DeviceID certificate
+        DiceCertificates:
+          - chain: Message
+
+        // a raw Ed25519 signature by the Alias key
+        DiceSign:
+          - signature: Signature
     }
 
 }
@@ -81,6 +81,7 @@ use interchange::Requester;
 
 use crate::api::*;
 use crate::error::*;
+use crate::key;
 use crate::pipe::TrussedInterchange;
 use crate::types::*;
 
@@ -101,7 +102,7 @@ pub enum ClientError {
 pub type ClientResult<'c, T, C> = core::result::Result<FutureResult<'c, T, C>, ClientError>;
 
 /// All-in-one trait bounding on the sub-traits.
-pub trait Client: CertificateClient + CryptoClient + CounterClient + FilesystemClient + ManagementClient + UiClient {}
+pub trait Client: CertificateClient + CryptoClient + CounterClient + FilesystemClient + ManagementClient + PinClient + UiClient {}
 
 impl<S: Syscall> Client for ClientImplementation<S> {}
 
@@ -230,6 +231,7 @@ impl<S: Syscall> CryptoClient for ClientImplementation<S> {}
 impl<S: Syscall> CounterClient for ClientImplementation<S> {}
 impl<S: Syscall> FilesystemClient for ClientImplementation<S> {}
 impl<S: Syscall> ManagementClient for ClientImplementation<S> {}
+impl<S: Syscall> PinClient for ClientImplementation<S> {}
 impl<S: Syscall> UiClient for ClientImplementation<S> {}
 
 /// Read/Write + Delete certificates
@@ -243,6 +245,48 @@ pub trait CertificateClient: PollClient {
         Ok(r)
     }
 
+    /// Build a PKCS #10 certificate signing request for a P256 or Ed255 key, signed by the key.
+    fn generate_csr(&mut self, mechanism: Mechanism, private_key: KeyId, template: CertificateTemplate)
+        -> ClientResult<'_, reply::GenerateCsr, Self>
+    {
+        let r = self.request(request::GenerateCsr { mechanism, private_key, template })?;
+        r.client.syscall();
+        Ok(r)
+    }
+
+    /// Issue a certificate for the subject's public key, signed by the issuer's private key,
+    /// and store it.
+    ///
+    /// For a self-signed certificate, pass the same key as issuer and subject.
+    fn issue_certificate(
+        &mut self, mechanism: Mechanism,
+        issuer_key: KeyId, issuer: DistinguishedName,
+        subject_key: KeyId, template: CertificateTemplate,
+        location: Location,
+    )
+        -> ClientResult<'_, reply::IssueCertificate, Self>
+    {
+        let r = self.request(request::IssueCertificate {
+            mechanism,
+            issuer_key,
+            issuer,
+            subject_key,
+            template,
+            location,
+        })?;
+        r.client.syscall();
+        Ok(r)
+    }
+
+    /// Lists certificates, passing the last certificate of the previous page as `after`.
+    fn list_certificates(&mut self, filter: CertificateFilter, after: Option<CertId>)
+        -> ClientResult<'_, reply::ListCertificates, Self>
+    {
+        let r = self.request(request::ListCertificates { filter, after })?;
+        r.client.syscall();
+        Ok(r)
+    }
+
     fn read_certificate(&mut self, id: CertId)
         -> ClientResult<'_, reply::ReadCertificate, Self>
     {
@@ -251,6 +295,30 @@ pub trait CertificateClient: PollClient {
         Ok(r)
     }
 
+    /// Replaces the label of a certificate, and the key it is linked to.
+    fn set_certificate_attributes(&mut self, id: CertId, attributes: CertificateAttributes)
+        -> ClientResult<'_, reply::SetCertificateAttributes, Self>
+    {
+        let r = self.request(request::SetCertificateAttributes { id, attributes })?;
+        r.client.syscall();
+        Ok(r)
+    }
+
+    /// Verify a chain of concatenated DER certificates, the end entity first, against
+    /// trust anchors in the certstore. Replies with the anchor the chain leads to.
+    ///
+    /// Without a `time`, the platform clock is used. If there is none either, verification fails
+    /// with `FunctionNotSupported`, as expired certificates could not be told apart.
+    fn verify_certificate_chain(&mut self, chain: &[u8], anchors: &[CertId], time: Option<DateTime>)
+        -> ClientResult<'_, reply::VerifyCertificateChain, Self>
+    {
+        let chain = Message::from_slice(chain).map_err(|_| ClientError::DataTooLarge)?;
+        let anchors = Vec::from_slice(anchors).map_err(|_| ClientError::DataTooLarge)?;
+        let r = self.request(request::VerifyCertificateChain { chain, anchors, time })?;
+        r.client.syscall();
+        Ok(r)
+    }
+
     /// Currently, this writes the cert (assumed but not verified to be DER)
     /// as-is. It might make sense to add attributes (such as "deletable").
     /// (On the other hand, the attn CA certs are not directly accessible to clients,
@@ -291,15 +359,56 @@ pub trait CryptoClient: PollClient {
 
     fn attest(&mut self, signing_mechanism: Mechanism, private_key: KeyId)
         -> ClientResult<'_, reply::Attest, Self>
+    {
+        self.attest_with_template(signing_mechanism, private_key, CertificateTemplate::default())
+    }
+
+    /// Attest a key with a certificate made from the template.
+    fn attest_with_template(&mut self, signing_mechanism: Mechanism, private_key: KeyId, template: CertificateTemplate)
+        -> ClientResult<'_, reply::Attest, Self>
     {
         let r = self.request(request::Attest {
             signing_mechanism,
             private_key,
+            template,
+        })?;
+        r.client.syscall();
+        Ok(r)
+    }
+
+    /// Makes a WebAuthn attestation statement for a new credential, signed by the attestation key.
+    ///
+    /// The authenticator data must contain the attested credential data.
+    fn attestation_statement(&mut self, format: AttestationStatementFormat, signing_mechanism: Mechanism,
+                             authenticator_data: &[u8], client_data_hash: &[u8; 32])
+        -> ClientResult<'_, reply::AttestationStatement, Self>
+    {
+        let authenticator_data = Message::from_slice(authenticator_data).map_err(|_| ClientError::DataTooLarge)?;
+        let r = self.request(request::AttestationStatement {
+            format,
+            signing_mechanism,
+            authenticator_data,
+            client_data_hash: ShortData::from_slice(client_data_hash).unwrap(),
         })?;
         r.client.syscall();
         Ok(r)
     }
 
+    /// The DICE certificate chain: the Alias certificate, followed by the DeviceID certificate.
+    fn dice_certificates(&mut self) -> ClientResult<'_, reply::DiceCertificates, Self> {
+        let r = self.request(request::DiceCertificates {})?;
+        r.client.syscall();
+        Ok(r)
+    }
+
+    /// Signs with the DICE Alias key. Only DICE signers may do this.
+    fn dice_sign(&mut self, message: &[u8]) -> ClientResult<'_, reply::DiceSign, Self> {
+        let message = Message::from_slice(message).map_err(|_| ClientError::DataTooLarge)?;
+        let r = self.request(request::DiceSign { message })?;
+        r.client.syscall();
+        Ok(r)
+    }
+
     fn decrypt<'c>(&'c mut self, mechanism: Mechanism, key: KeyId,
                        message: &[u8], associated_data: &[u8],
                        nonce: &[u8], tag: &[u8],
@@ -315,6 +424,17 @@ pub trait CryptoClient: PollClient {
         Ok(r)
     }
 
+    fn change_key_location(&mut self, key: KeyId, location: Location)
+        -> ClientResult<'_, reply::ChangeKeyLocation, Self>
+    {
+        let r = self.request(request::ChangeKeyLocation { key, location })?;
+        r.client.syscall();
+        Ok(r)
+    }
+
+    /// Deletes the key, or both halves of a keypair.
+    ///
+    /// To drop only the public half of a keypair, use `delete_public_key`.
     fn delete(&mut self, key: KeyId)
         -> ClientResult<'_, reply::Delete, Self>
     {
@@ -326,6 +446,15 @@ pub trait CryptoClient: PollClient {
         Ok(r)
     }
 
+    /// Deletes the public half of a keypair, or a public key, and never a private key.
+    fn delete_public_key(&mut self, key: KeyId)
+        -> ClientResult<'_, reply::DeletePublicKey, Self>
+    {
+        let r = self.request(request::DeletePublicKey { key })?;
+        r.client.syscall();
+        Ok(r)
+    }
+
     /// Skips deleting read-only / manufacture keys (currently, "low ID").
     fn delete_all(&mut self, location: Location)
         -> ClientResult<'_, reply::DeleteAllKeys, Self>
@@ -335,6 +464,8 @@ pub trait CryptoClient: PollClient {
         Ok(r)
     }
 
+    /// For asymmetric mechanisms, stores the public key of the private `base_key`, and replies
+    /// with `base_key` itself, as the halves of a keypair share their `KeyId`.
     fn derive_key(&mut self, mechanism: Mechanism, base_key: KeyId, additional_data: Option<MediumData>, attributes: StorageAttributes)
         -> ClientResult<'_, reply::DeriveKey, Self>
     {
@@ -382,6 +513,28 @@ pub trait CryptoClient: PollClient {
         Ok(r)
     }
 
+    /// Find keys by secrecy, kind, location and label, where `None` matches any.
+    ///
+    /// Returns up to `MAX_OBJECT_HANDLES` keys following `after`, and the total number of matches.
+    fn find_objects(&mut self, secrecy: Option<key::Secrecy>, kind: Option<key::Kind>,
+                    location: Option<Location>, label: Option<&[u8]>, after: Option<KeyId>)
+        -> ClientResult<'_, reply::FindObjects, Self>
+    {
+        let label = match label {
+            Some(label) => Some(key::Label::from_slice(label).map_err(|_| ClientError::DataTooLarge)?),
+            None => None,
+        };
+        let r = self.request(request::FindObjects {
+            secrecy,
+            kind,
+            location,
+            label,
+            after,
+        })?;
+        r.client.syscall();
+        Ok(r)
+    }
+
     fn generate_key(&mut self, mechanism: Mechanism, attributes: StorageAttributes)
         -> ClientResult<'_, reply::GenerateKey, Self>
     {
@@ -404,6 +557,15 @@ pub trait CryptoClient: PollClient {
         Ok(r)
     }
 
+    /// Kind, flags, location, usage policy and metadata of a key.
+    fn get_key_info(&mut self, key: KeyId)
+        -> ClientResult<'_, reply::GetKeyInfo, Self>
+    {
+        let r = self.request(request::GetKeyInfo { key })?;
+        r.client.syscall();
+        Ok(r)
+    }
+
     fn hash(&mut self, mechanism: Mechanism, message: Message)
         -> ClientResult<'_, reply::Hash, Self>
     {
@@ -412,6 +574,16 @@ pub trait CryptoClient: PollClient {
         Ok(r)
     }
 
+    /// Lists keys, passing the last key of the previous page as `after`; keypair halves are listed separately.
+    fn list_keys(&mut self, kind: Option<key::Kind>, location: Option<Location>,
+                 after: Option<(KeyId, key::Secrecy)>)
+        -> ClientResult<'_, reply::ListKeys, Self>
+    {
+        let r = self.request(request::ListKeys { kind, location, after })?;
+        r.client.syscall();
+        Ok(r)
+    }
+
     fn random_bytes(&mut self, count: usize)
         -> ClientResult<'_, reply::RandomBytes, Self>
     {
@@ -472,19 +644,17 @@ pub trait CryptoClient: PollClient {
         Ok(r)
     }
 
-    #[deprecated]
-    /// This mechanism-specific key injection is deprecated in favor of the general
-    /// injection mechanism for symmetric keys of unknown quality, i.e., "shared" keys,
-    /// of trussed::key::Kind::Shared.
+    /// Import a raw private key (P256 scalar, Ed255 or X255 seed) or symmetric key,
+    /// to be used with the given mechanism.
     ///
-    /// The implementations have been removed, so replace your usage with the
-    /// methoed `unsafe_inject_shared_key`.
+    /// The key is validated, but not marked as `LOCAL`, so it can't be attested.
+    /// For secrets of unknown use, see `unsafe_inject_shared_key`.
     fn unsafe_inject_key(&mut self, mechanism: Mechanism, raw_key: &[u8], persistence: Location)
         -> ClientResult<'_, reply::UnsafeInjectKey, Self>
     {
         let r = self.request(request::UnsafeInjectKey {
             mechanism,
-            raw_key: ShortData::from_slice(raw_key).unwrap(),
+            raw_key: ShortData::from_slice(raw_key).map_err(|_| ClientError::DataTooLarge)?,
             attributes: StorageAttributes::new().set_persistence(persistence),
         })?;
         r.client.syscall();
@@ -528,9 +698,32 @@ pub trait CryptoClient: PollClient {
         Ok(r)
     }
 
+    /// Splits an extractable key into `shares` shares, any `threshold` of which recombine to it.
+    fn split_key(&mut self, key: KeyId, threshold: u8, shares: u8, format: ShareFormat)
+        -> ClientResult<'_, reply::SplitKey, Self>
+    {
+        let r = self.request(request::SplitKey { key, threshold, shares, format })?;
+        r.client.syscall();
+        Ok(r)
+    }
+
+    /// Recombines shares from `split_key` into a key for the mechanism.
+    fn combine_key(&mut self, mechanism: Mechanism, shares: &[&[u8]], format: ShareFormat, attributes: StorageAttributes)
+        -> ClientResult<'_, reply::CombineKey, Self>
+    {
+        let mut share_data = Vec::new();
+        for share in shares {
+            let share = ShortData::from_slice(share).map_err(|_| ClientError::DataTooLarge)?;
+            share_data.push(share).map_err(|_| ClientError::DataTooLarge)?;
+        }
+        let r = self.request(request::CombineKey { mechanism, shares: share_data, format, attributes })?;
+        r.client.syscall();
+        Ok(r)
+    }
+
 }
 
-/// Create counters, increment existing counters.
+/// Create, read, increment, raise, list and delete counters.
 pub trait CounterClient: PollClient {
 
     fn create_counter(&mut self, location: Location)
@@ -541,10 +734,120 @@ pub trait CounterClient: PollClient {
         Ok(r)
     }
 
+    /// Fails with `ObjectHandleInvalid` for counter zero.
+    fn delete_counter(&mut self, id: CounterId)
+        -> ClientResult<'_, reply::DeleteCounter, Self>
+    {
+        let r = self.request(request::DeleteCounter { id })?;
+        r.client.syscall();
+        Ok(r)
+    }
+
     fn increment_counter(&mut self, id: CounterId)
         -> ClientResult<'_, reply::IncrementCounter, Self>
     {
-        let r = self.request(request::IncrementCounter { id })?;
+        self.increment_counter_by(id, 1)
+    }
+
+    /// Fails with `CounterOverflow` if the counter would wrap.
+    fn increment_counter_by(&mut self, id: CounterId, step: u128)
+        -> ClientResult<'_, reply::IncrementCounter, Self>
+    {
+        let r = self.request(request::IncrementCounter { id, step })?;
+        r.client.syscall();
+        Ok(r)
+    }
+
+    fn list_counters(&mut self, location: Option<Location>, after: Option<CounterId>)
+        -> ClientResult<'_, reply::ListCounters, Self>
+    {
+        let r = self.request(request::ListCounters { location, after })?;
+        r.client.syscall();
+        Ok(r)
+    }
+
+    fn read_counter(&mut self, id: CounterId)
+        -> ClientResult<'_, reply::ReadCounter, Self>
+    {
+        let r = self.request(request::ReadCounter { id })?;
+        r.client.syscall();
+        Ok(r)
+    }
+
+    /// Raises the counter to `value` if it is lower, returning the resulting value.
+    fn set_counter_at_least(&mut self, id: CounterId, value: u128)
+        -> ClientResult<'_, reply::SetCounterAtLeast, Self>
+    {
+        let r = self.request(request::SetCounterAtLeast { id, value })?;
+        r.client.syscall();
+        Ok(r)
+    }
+
+}
+
+/// Set, verify, change and reset PINs with retry counters.
+pub trait PinClient: PollClient {
+
+    /// Fails if the PIN is already set, change or reset it instead.
+    fn set_pin(&mut self, pin: PinId, value: &[u8], retries: u8, puk: Option<(&[u8], u8)>)
+        -> ClientResult<'_, reply::SetPin, Self>
+    {
+        let value = ShortData::from_slice(value).map_err(|_| ClientError::DataTooLarge)?;
+        let puk = match puk {
+            Some((puk, retries)) => Some((ShortData::from_slice(puk).map_err(|_| ClientError::DataTooLarge)?, retries)),
+            None => None,
+        };
+        let r = self.request(request::SetPin { pin, value, retries, puk })?;
+        r.client.syscall();
+        Ok(r)
+    }
+
+    fn verify_pin(&mut self, pin: PinId, value: &[u8])
+        -> ClientResult<'_, reply::VerifyPin, Self>
+    {
+        let value = ShortData::from_slice(value).map_err(|_| ClientError::DataTooLarge)?;
+        let r = self.request(request::VerifyPin { pin, value })?;
+        r.client.syscall();
+        Ok(r)
+    }
+
+    fn change_pin(&mut self, pin: PinId, old_value: &[u8], new_value: &[u8])
+        -> ClientResult<'_, reply::ChangePin, Self>
+    {
+        let old_value = ShortData::from_slice(old_value).map_err(|_| ClientError::DataTooLarge)?;
+        let new_value = ShortData::from_slice(new_value).map_err(|_| ClientError::DataTooLarge)?;
+        let r = self.request(request::ChangePin { pin, old_value, new_value })?;
+        r.client.syscall();
+        Ok(r)
+    }
+
+    fn reset_pin(&mut self, pin: PinId, puk: &[u8], new_value: &[u8])
+        -> ClientResult<'_, reply::ResetPin, Self>
+    {
+        let puk = ShortData::from_slice(puk).map_err(|_| ClientError::DataTooLarge)?;
+        let new_value = ShortData::from_slice(new_value).map_err(|_| ClientError::DataTooLarge)?;
+        let r = self.request(request::ResetPin { pin, puk, new_value })?;
+        r.client.syscall();
+        Ok(r)
+    }
+
+    fn pin_retries(&mut self, pin: PinId)
+        -> ClientResult<'_, reply::PinRetries, Self>
+    {
+        let r = self.request(request::PinRetries { pin })?;
+        r.client.syscall();
+        Ok(r)
+    }
+
+    /// Deletes the PIN if `value` is correct; a blocked PIN must be reset first.
+    ///
+    /// A PIN that is blocked, without a PUK or with a blocked PUK, is deleted whatever `value`
+    /// is, together with the keys bound to it.
+    fn delete_pin(&mut self, pin: PinId, value: &[u8])
+        -> ClientResult<'_, reply::DeletePin, Self>
+    {
+        let value = ShortData::from_slice(value).map_err(|_| ClientError::DataTooLarge)?;
+        let r = self.request(request::DeletePin { pin, value })?;
         r.client.syscall();
         Ok(r)
     }
@@ -685,6 +988,61 @@ pub trait ManagementClient: PollClient {
         Ok(r)
     }
 
+    /// Returns a chunk of an encrypted backup of everything this client stores.
+    ///
+    /// Start with `cursor` `None`, then pass the `next` cursor of each reply until it is `None`.
+    fn backup(&mut self, wrapping_key: KeyId, cursor: Option<BackupCursor>)
+        -> ClientResult<'_, reply::Backup, Self>
+    {
+        let r = self.request(request::Backup { wrapping_key, cursor })?;
+        r.client.syscall();
+        Ok(r)
+    }
+
+    /// Restores a chunk of a backup; chunks must be restored in order, and nothing is
+    /// restored before the last one.
+    fn restore(&mut self, wrapping_key: KeyId, chunk: &[u8])
+        -> ClientResult<'_, reply::Restore, Self>
+    {
+        let chunk = Message::from_slice(chunk).map_err(|_| ClientError::DataTooLarge)?;
+        let r = self.request(request::Restore { wrapping_key, chunk })?;
+        r.client.syscall();
+        Ok(r)
+    }
+
+    /// Generates the attestation key for the mechanism, or injects `raw_key`, and returns its
+    /// public key. Only provisioners may do this, until provisioning is locked.
+    fn provision_attestation_key(&mut self, mechanism: Mechanism, raw_key: Option<&[u8]>)
+        -> ClientResult<'_, reply::ProvisionAttestationKey, Self>
+    {
+        let raw_key = raw_key
+            .map(|raw_key| ShortData::from_slice(raw_key).map_err(|_| ClientError::DataTooLarge))
+            .transpose()?;
+        let r = self.request(request::ProvisionAttestationKey { mechanism, raw_key })?;
+        r.client.syscall();
+        Ok(r)
+    }
+
+    /// Installs the concatenated DER encoded certificates of the attestation key, starting
+    /// with its own certificate.
+    fn install_attestation_certificates(&mut self, mechanism: Mechanism, chain: &[u8])
+        -> ClientResult<'_, reply::InstallAttestationCertificates, Self>
+    {
+        let chain = Message::from_slice(chain).map_err(|_| ClientError::DataTooLarge)?;
+        let r = self.request(request::InstallAttestationCertificates { mechanism, chain })?;
+        r.client.syscall();
+        Ok(r)
+    }
+
+    /// Refuses all further provisioning, for good.
+    fn lock_provisioning(&mut self)
+        -> ClientResult<'_, reply::LockProvisioning, Self>
+    {
+        let r = self.request(request::LockProvisioning {})?;
+        r.client.syscall();
+        Ok(r)
+    }
+
 
 }
 
@@ -1,5 +1,145 @@
 use super::*;
 
+#[cfg(feature = "aes128")]
+impl<S: Syscall> Aes128 for ClientImplementation<S> {}
+
+/// AES-128 in CBC, CTR and ECB mode.
+///
+/// CBC and ECB do not pad, so messages must be a multiple of 16 bytes. Without an IV resp.
+/// initial counter block, encryption picks a random one, and replies with it as `nonce`.
+pub trait Aes128: CryptoClient {
+    fn decrypt_aes128cbc<'c>(&'c mut self, key: KeyId, message: &[u8], iv: &[u8; 16])
+        -> ClientResult<'c, reply::Decrypt, Self>
+    {
+        self.decrypt(Mechanism::Aes128Cbc, key, message, &[], iv, &[])
+    }
+
+    fn encrypt_aes128cbc<'c>(&'c mut self, key: KeyId, message: &[u8], iv: Option<&[u8; 16]>)
+        -> ClientResult<'c, reply::Encrypt, Self>
+    {
+        self.encrypt(Mechanism::Aes128Cbc, key, message, &[],
+            iv.and_then(|iv| ShortData::from_slice(iv).ok()))
+    }
+
+    fn decrypt_aes128ctr<'c>(&'c mut self, key: KeyId, message: &[u8], counter_block: &[u8; 16])
+        -> ClientResult<'c, reply::Decrypt, Self>
+    {
+        self.decrypt(Mechanism::Aes128Ctr, key, message, &[], counter_block, &[])
+    }
+
+    fn encrypt_aes128ctr<'c>(&'c mut self, key: KeyId, message: &[u8], counter_block: Option<&[u8; 16]>)
+        -> ClientResult<'c, reply::Encrypt, Self>
+    {
+        self.encrypt(Mechanism::Aes128Ctr, key, message, &[],
+            counter_block.and_then(|counter_block| ShortData::from_slice(counter_block).ok()))
+    }
+
+    fn decrypt_aes128ecb<'c>(&'c mut self, key: KeyId, message: &[u8])
+        -> ClientResult<'c, reply::Decrypt, Self>
+    {
+        self.decrypt(Mechanism::Aes128Ecb, key, message, &[], &[], &[])
+    }
+
+    fn encrypt_aes128ecb<'c>(&'c mut self, key: KeyId, message: &[u8])
+        -> ClientResult<'c, reply::Encrypt, Self>
+    {
+        self.encrypt(Mechanism::Aes128Ecb, key, message, &[], None)
+    }
+
+    fn generate_aes128_key(&mut self, persistence: Location)
+        -> ClientResult<'_, reply::GenerateKey, Self>
+    {
+        self.generate_key(Mechanism::Aes128Ecb, StorageAttributes::new().set_persistence(persistence))
+    }
+}
+
+#[cfg(feature = "aes192")]
+impl<S: Syscall> Aes192 for ClientImplementation<S> {}
+
+/// AES-192 in CBC, CTR and ECB mode, like `Aes128`.
+pub trait Aes192: CryptoClient {
+    fn decrypt_aes192cbc<'c>(&'c mut self, key: KeyId, message: &[u8], iv: &[u8; 16])
+        -> ClientResult<'c, reply::Decrypt, Self>
+    {
+        self.decrypt(Mechanism::Aes192Cbc, key, message, &[], iv, &[])
+    }
+
+    fn encrypt_aes192cbc<'c>(&'c mut self, key: KeyId, message: &[u8], iv: Option<&[u8; 16]>)
+        -> ClientResult<'c, reply::Encrypt, Self>
+    {
+        self.encrypt(Mechanism::Aes192Cbc, key, message, &[],
+            iv.and_then(|iv| ShortData::from_slice(iv).ok()))
+    }
+
+    fn decrypt_aes192ctr<'c>(&'c mut self, key: KeyId, message: &[u8], counter_block: &[u8; 16])
+        -> ClientResult<'c, reply::Decrypt, Self>
+    {
+        self.decrypt(Mechanism::Aes192Ctr, key, message, &[], counter_block, &[])
+    }
+
+    fn encrypt_aes192ctr<'c>(&'c mut self, key: KeyId, message: &[u8], counter_block: Option<&[u8; 16]>)
+        -> ClientResult<'c, reply::Encrypt, Self>
+    {
+        self.encrypt(Mechanism::Aes192Ctr, key, message, &[],
+            counter_block.and_then(|counter_block| ShortData::from_slice(counter_block).ok()))
+    }
+
+    fn decrypt_aes192ecb<'c>(&'c mut self, key: KeyId, message: &[u8])
+        -> ClientResult<'c, reply::Decrypt, Self>
+    {
+        self.decrypt(Mechanism::Aes192Ecb, key, message, &[], &[], &[])
+    }
+
+    fn encrypt_aes192ecb<'c>(&'c mut self, key: KeyId, message: &[u8])
+        -> ClientResult<'c, reply::Encrypt, Self>
+    {
+        self.encrypt(Mechanism::Aes192Ecb, key, message, &[], None)
+    }
+
+    fn generate_aes192_key(&mut self, persistence: Location)
+        -> ClientResult<'_, reply::GenerateKey, Self>
+    {
+        self.generate_key(Mechanism::Aes192Ecb, StorageAttributes::new().set_persistence(persistence))
+    }
+}
+
+#[cfg(feature = "aes256")]
+impl<S: Syscall> Aes256 for ClientImplementation<S> {}
+
+/// AES-256 in CTR and ECB mode, like `Aes128`.
+pub trait Aes256: CryptoClient {
+    fn decrypt_aes256ctr<'c>(&'c mut self, key: KeyId, message: &[u8], counter_block: &[u8; 16])
+        -> ClientResult<'c, reply::Decrypt, Self>
+    {
+        self.decrypt(Mechanism::Aes256Ctr, key, message, &[], counter_block, &[])
+    }
+
+    fn encrypt_aes256ctr<'c>(&'c mut self, key: KeyId, message: &[u8], counter_block: Option<&[u8; 16]>)
+        -> ClientResult<'c, reply::Encrypt, Self>
+    {
+        self.encrypt(Mechanism::Aes256Ctr, key, message, &[],
+            counter_block.and_then(|counter_block| ShortData::from_slice(counter_block).ok()))
+    }
+
+    fn decrypt_aes256ecb<'c>(&'c mut self, key: KeyId, message: &[u8])
+        -> ClientResult<'c, reply::Decrypt, Self>
+    {
+        self.decrypt(Mechanism::Aes256Ecb, key, message, &[], &[], &[])
+    }
+
+    fn encrypt_aes256ecb<'c>(&'c mut self, key: KeyId, message: &[u8])
+        -> ClientResult<'c, reply::Encrypt, Self>
+    {
+        self.encrypt(Mechanism::Aes256Ecb, key, message, &[], None)
+    }
+
+    fn generate_aes256_key(&mut self, persistence: Location)
+        -> ClientResult<'_, reply::GenerateKey, Self>
+    {
+        self.generate_key(Mechanism::Aes256Ecb, StorageAttributes::new().set_persistence(persistence))
+    }
+}
+
 #[cfg(feature = "aes256-cbc")]
 impl<S: Syscall> Aes256Cbc for ClientImplementation<S> {}
 
@@ -12,6 +152,12 @@ pub trait Aes256Cbc: CryptoClient {
         )
     }
 
+    fn generate_aes256cbc_key(&mut self, persistence: Location)
+        -> ClientResult<'_, reply::GenerateKey, Self>
+    {
+        self.generate_key(Mechanism::Aes256Cbc, StorageAttributes::new().set_persistence(persistence))
+    }
+
     fn wrap_key_aes256cbc(&mut self, wrapping_key: KeyId, key: KeyId)
         -> ClientResult<'_, reply::WrapKey, Self>
     {
@@ -75,6 +221,12 @@ pub trait HmacBlake2s: CryptoClient {
             StorageAttributes::new().set_persistence(persistence))
     }
 
+    fn generate_hmacblake2s_key(&mut self, persistence: Location)
+        -> ClientResult<'_, reply::GenerateKey, Self>
+    {
+        self.generate_key(Mechanism::HmacBlake2s, StorageAttributes::new().set_persistence(persistence))
+    }
+
     fn sign_hmacblake2s<'c>(&'c mut self, key: KeyId, message: &[u8])
         -> ClientResult<'c, reply::Sign, Self>
     {
@@ -96,6 +248,12 @@ pub trait HmacSha1: CryptoClient {
             StorageAttributes::new().set_persistence(persistence))
     }
 
+    fn generate_hmacsha1_key(&mut self, persistence: Location)
+        -> ClientResult<'_, reply::GenerateKey, Self>
+    {
+        self.generate_key(Mechanism::HmacSha1, StorageAttributes::new().set_persistence(persistence))
+    }
+
     fn sign_hmacsha1<'c>(&'c mut self, key: KeyId, message: &[u8])
         -> ClientResult<'c, reply::Sign, Self>
     {
@@ -117,6 +275,12 @@ pub trait HmacSha256: CryptoClient {
             StorageAttributes::new().set_persistence(persistence))
     }
 
+    fn generate_hmacsha256_key(&mut self, persistence: Location)
+        -> ClientResult<'_, reply::GenerateKey, Self>
+    {
+        self.generate_key(Mechanism::HmacSha256, StorageAttributes::new().set_persistence(persistence))
+    }
+
     fn sign_hmacsha256<'c>(&'c mut self, key: KeyId, message: &[u8])
         -> ClientResult<'c, reply::Sign, Self>
     {
@@ -138,6 +302,12 @@ pub trait HmacSha512: CryptoClient {
             StorageAttributes::new().set_persistence(persistence))
     }
 
+    fn generate_hmacsha512_key(&mut self, persistence: Location)
+        -> ClientResult<'_, reply::GenerateKey, Self>
+    {
+        self.generate_key(Mechanism::HmacSha512, StorageAttributes::new().set_persistence(persistence))
+    }
+
     fn sign_hmacsha512<'c>(&'c mut self, key: KeyId, message: &[u8])
         -> ClientResult<'c, reply::Sign, Self>
     {
@@ -265,6 +435,7 @@ pub trait Sha256: CryptoClient {
 #[cfg(feature = "tdes")]
 impl<S: Syscall> Tdes for ClientImplementation<S> {}
 
+/// Triple-DES in CBC, CTR and ECB mode, like `Aes128`, with 8 byte blocks.
 pub trait Tdes: CryptoClient {
     fn decrypt_tdes<'c>(&'c mut self, key: KeyId, message: &[u8])
         -> ClientResult<'c, reply::Decrypt, Self>
@@ -277,12 +448,62 @@ pub trait Tdes: CryptoClient {
     {
         self.encrypt(Mechanism::Tdes, key, message, &[], None)
     }
+
+    fn decrypt_tdescbc<'c>(&'c mut self, key: KeyId, message: &[u8], iv: &[u8; 8])
+        -> ClientResult<'c, reply::Decrypt, Self>
+    {
+        self.decrypt(Mechanism::TdesCbc, key, message, &[], iv, &[])
+    }
+
+    fn encrypt_tdescbc<'c>(&'c mut self, key: KeyId, message: &[u8], iv: Option<&[u8; 8]>)
+        -> ClientResult<'c, reply::Encrypt, Self>
+    {
+        self.encrypt(Mechanism::TdesCbc, key, message, &[],
+            iv.and_then(|iv| ShortData::from_slice(iv).ok()))
+    }
+
+    fn decrypt_tdesctr<'c>(&'c mut self, key: KeyId, message: &[u8], counter_block: &[u8; 8])
+        -> ClientResult<'c, reply::Decrypt, Self>
+    {
+        self.decrypt(Mechanism::TdesCtr, key, message, &[], counter_block, &[])
+    }
+
+    fn encrypt_tdesctr<'c>(&'c mut self, key: KeyId, message: &[u8], counter_block: Option<&[u8; 8]>)
+        -> ClientResult<'c, reply::Encrypt, Self>
+    {
+        self.encrypt(Mechanism::TdesCtr, key, message, &[],
+            counter_block.and_then(|counter_block| ShortData::from_slice(counter_block).ok()))
+    }
+
+    fn decrypt_tdesecb<'c>(&'c mut self, key: KeyId, message: &[u8])
+        -> ClientResult<'c, reply::Decrypt, Self>
+    {
+        self.decrypt(Mechanism::TdesEcb, key, message, &[], &[], &[])
+    }
+
+    fn encrypt_tdesecb<'c>(&'c mut self, key: KeyId, message: &[u8])
+        -> ClientResult<'c, reply::Encrypt, Self>
+    {
+        self.encrypt(Mechanism::TdesEcb, key, message, &[], None)
+    }
+
+    fn generate_tdes_key(&mut self, persistence: Location)
+        -> ClientResult<'_, reply::GenerateKey, Self>
+    {
+        self.generate_key(Mechanism::Tdes, StorageAttributes::new().set_persistence(persistence))
+    }
 }
 
 #[cfg(feature = "totp")]
 impl<S: Syscall> Totp for ClientImplementation<S> {}
 
 pub trait Totp: CryptoClient {
+    fn generate_totp_key(&mut self, persistence: Location)
+        -> ClientResult<'_, reply::GenerateKey, Self>
+    {
+        self.generate_key(Mechanism::Totp, StorageAttributes::new().set_persistence(persistence))
+    }
+
     fn sign_totp(&mut self, key: KeyId, timestamp: u64)
         -> ClientResult<'_, reply::Sign, Self>
     {
@@ -9,16 +9,35 @@ use littlefs2::consts;
 pub type MAX_APPLICATION_NAME_LENGTH = consts::U256;
 pub const MAX_LONG_DATA_LENGTH: usize = 1024;
 pub const MAX_MESSAGE_LENGTH: usize = 1024;
+pub const MAX_OBJECT_HANDLES: usize = 16;
+// the typenum form it had before, for code not yet using the constant
+#[deprecated(note = "use the `usize` constant `MAX_OBJECT_HANDLES` instead")]
 pub type MAX_OBJECT_HANDLES = consts::U16;
 pub type MAX_LABEL_LENGTH = consts::U256;
 pub const MAX_MEDIUM_DATA_LENGTH: usize = 256;
 pub type MAX_PATH_LENGTH = consts::U256;
 pub const MAX_KEY_MATERIAL_LENGTH: usize = 128;
-// must be above + 4
-pub const MAX_SERIALIZED_KEY_LENGTH: usize = 132;
+pub const MAX_ALLOWED_MECHANISMS: usize = 4;
+pub const MAX_KEY_LABEL_LENGTH: usize = 32;
+pub const MAX_KEY_TAG_LENGTH: usize = 16;
+pub const MAX_ALTERNATIVE_NAMES: usize = 4;
+pub const MAX_CERTIFICATE_CHAIN_LENGTH: usize = 4;
+pub const MAX_CERTIFICATE_EXTENSIONS: usize = 4;
+// DER encoded OID contents, and extension values
+pub const MAX_EXTENSION_OID_LENGTH: usize = 16;
+pub const MAX_EXTENSION_VALUE_LENGTH: usize = 64;
+// the upper bound of RFC 5280 for common names, organizations and units
+pub const MAX_NAME_ATTRIBUTE_LENGTH: usize = 64;
+// must be above + 7 (header) + 3 + MAX_ALLOWED_MECHANISMS (policy) + 20 (PIN)
+// + 13 (limits) + 10 + MAX_KEY_LABEL_LENGTH + MAX_KEY_TAG_LENGTH (metadata)
+pub const MAX_SERIALIZED_KEY_LENGTH: usize = 233;
+// must be above + 3 (flags and version) + 12 (nonce) + 16 (tag), for keys encrypted at rest
+pub const MAX_STORED_KEY_LENGTH: usize = 264;
 pub type MAX_SERVICE_CLIENTS = consts::U5;
+pub const MAX_SHARES: usize = 8;
 pub const MAX_SHORT_DATA_LENGTH: usize = 128;
 pub const MAX_SIGNATURE_LENGTH: usize = 72;
+pub const MAX_TRUST_ANCHORS: usize = 4;
 pub const MAX_USER_ATTRIBUTE_LENGTH: usize = 256;
 
 pub const USER_ATTRIBUTE_NUMBER: u8 = 37;
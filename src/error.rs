@@ -15,6 +15,10 @@ pub enum Error {
     // supposed to support "stub" function for everything,
     // returning this error
     FunctionNotSupported = 0x0000_0054,
+    // key usage policy does not allow the operation
+    KeyFunctionNotPermitted = 0x0000_0068,
+    // key is not extractable, so may not be wrapped
+    KeyUnextractable = 0x0000_006A,
     // unknown, or cannot be used in this token with selected function
     MechanismInvalid = 0x0000_0070,
     MechanismParamInvalid = 0x0000_0071,
@@ -22,21 +26,39 @@ pub enum Error {
 
     // our errors
     AeadError,
+    BackupChunkOutOfSequence,
     CborError,
+    CertificateExpired,
+    CounterOverflow,
     EntropyMalfunction,
     FilesystemReadFailure,
     FilesystemWriteFailure,
     ImplementationError,
     InternalError,
+    InvalidCertificate,
+    InvalidCertificateTemplate,
+    InvalidPinRetries,
     InvalidSerializedKey,
     InvalidSerializationFormat,
+    InvalidShares,
+    InvalidShareThreshold,
+    KeyExpired,
+    KeyLocationNotPermitted,
     MechanismNotAvailable,
     NonceOverflow,
     NoSuchCertificate,
     NoSuchKey,
+    NoSuchPin,
     NotJustLetters,
+    PinAlreadySet,
+    PinBlocked,
+    PinNotVerified,
+    ProvisioningLocked,
     RequestNotAvailable,
     SignDataTooLarge,
+    TooManyMechanisms,
+    UnsupportedKeyVersion,
+    UntrustedCertificate,
     WrongKeyKind,
     WrongMessageLength,
     WrongSignatureLength,
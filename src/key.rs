@@ -1,3 +1,5 @@
+use core::convert::TryInto;
+
 use heapless::Vec;
 use serde::{Deserialize, Serialize};
 use serde_indexed::{DeserializeIndexed, SerializeIndexed};
@@ -6,11 +8,17 @@ use zeroize::Zeroize;
 pub use crate::Bytes;
 use crate::{
     Error,
-    config::{MAX_KEY_MATERIAL_LENGTH, MAX_SERIALIZED_KEY_LENGTH},
+    config::{
+        MAX_ALLOWED_MECHANISMS, MAX_KEY_LABEL_LENGTH, MAX_KEY_MATERIAL_LENGTH, MAX_KEY_TAG_LENGTH,
+        MAX_SERIALIZED_KEY_LENGTH,
+    },
+    types::{Id, Mechanism, PinId, StorageAttributes},
 };
 
 pub type Material = Vec<u8, {MAX_KEY_MATERIAL_LENGTH}>;
 pub type SerializedKeyBytes = Vec<u8, {MAX_SERIALIZED_KEY_LENGTH}>;
+pub type Label = Bytes<MAX_KEY_LABEL_LENGTH>;
+pub type Tag = Bytes<MAX_KEY_TAG_LENGTH>;
 
 // We don't implement serde to make sure nobody inadvertently still uses it
 // Should we use references here only?
@@ -25,6 +33,12 @@ pub type SerializedKeyBytes = Vec<u8, {MAX_SERIALIZED_KEY_LENGTH}>;
 pub struct Key {
    pub flags: Flags,
    pub kind: Kind,
+   #[zeroize(skip)]
+   pub policy: Policy,
+   #[zeroize(skip)]
+   pub limits: Limits,
+   #[zeroize(skip)]
+   pub metadata: Metadata,
    pub material: Material,
 }
 
@@ -32,6 +46,12 @@ pub struct Key {
 pub struct Info {
    pub flags: Flags,
    pub kind: Kind,
+   #[zeroize(skip)]
+   pub policy: Policy,
+   #[zeroize(skip)]
+   pub limits: Limits,
+   #[zeroize(skip)]
+   pub metadata: Metadata,
 }
 
 impl Info {
@@ -39,11 +59,175 @@ impl Info {
         self.flags |= Flags::LOCAL;
         self
     }
+
+    pub fn with_policy(mut self, policy: Policy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Applies the attributes a key is created with.
+    ///
+    /// Keys with limits are never extractable, as their copies would not share the limits.
+    pub fn with_attributes(mut self, attributes: &StorageAttributes) -> Self {
+        if attributes.extractable && attributes.limits.is_unlimited() {
+            self.flags |= Flags::EXTRACTABLE;
+        } else {
+            self.flags |= Flags::NEVER_EXTRACTABLE;
+        }
+        self.metadata.label = attributes.label.clone();
+        self.metadata.tag = attributes.tag.clone();
+        self.limits = attributes.limits.clone();
+        self.with_policy(attributes.policy.clone())
+    }
 }
 
 impl From<Kind> for Info {
     fn from(kind: Kind) -> Self {
-        Self { flags: Default::default(), kind }
+        Self {
+            flags: Default::default(),
+            kind,
+            policy: Default::default(),
+            limits: Default::default(),
+            metadata: Default::default(),
+        }
+    }
+}
+
+/// Descriptive data about a key, not used by any cryptographic operation.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Metadata {
+    /// Name given by the app, to find the key again
+    pub label: Option<Label>,
+    /// Seconds since the UNIX epoch, if the platform has a clock
+    pub created: Option<u64>,
+    /// Opaque app-defined data
+    pub tag: Option<Tag>,
+}
+
+bitflags::bitflags! {
+    #[derive(DeserializeIndexed, SerializeIndexed)]
+    /// The operations a key may be used for, modeled after the PKCS #11 `CKA_SIGN` etc. attributes.
+    ///
+    /// Deriving the public key of a keypair and serializing a public key are not usages,
+    /// they are only limited by the allowed mechanisms.
+    pub struct Usage: u16 {
+        const SIGN = 1 << 0;
+        const VERIFY = 1 << 1;
+        const ENCRYPT = 1 << 2;
+        const DECRYPT = 1 << 3;
+        const DERIVE = 1 << 4;
+        const AGREE = 1 << 5;
+        const WRAP = 1 << 6;
+        const UNWRAP = 1 << 7;
+    }
+}
+
+impl Default for Usage {
+    fn default() -> Self {
+        Usage::all()
+    }
+}
+
+/// What a key may be used for, and with which mechanisms.
+///
+/// The default policy is unrestricted, which is also what keys stored before policies
+/// existed get. The policy is fixed when the key is stored.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Policy {
+    pub usage: Usage,
+    /// If empty, the key may be used with any mechanism accepting its kind.
+    pub mechanisms: Vec<Mechanism, MAX_ALLOWED_MECHANISMS>,
+    /// If set, the key may only be used after verifying this PIN.
+    pub pin: Option<PinRequirement>,
+}
+
+/// A PIN that must have been verified before a key can be used.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct PinRequirement {
+    pub pin: PinId,
+    /// Seconds of uptime a verification stays valid for, or `None` until reboot.
+    pub validity: Option<u32>,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self { usage: Usage::default(), mechanisms: Vec::new(), pin: None }
+    }
+}
+
+impl Policy {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn set_usage(mut self, usage: Usage) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    /// Fails with `TooManyMechanisms` if passed more than `MAX_ALLOWED_MECHANISMS` mechanisms.
+    pub fn set_mechanisms(mut self, mechanisms: &[Mechanism]) -> Result<Self, Error> {
+        self.mechanisms = Vec::from_slice(mechanisms).map_err(|_| Error::TooManyMechanisms)?;
+        Ok(self)
+    }
+
+    pub fn set_pin(mut self, pin: PinId, validity: Option<u32>) -> Self {
+        self.pin = Some(PinRequirement { pin, validity });
+        self
+    }
+
+    pub fn is_unrestricted(&self) -> bool {
+        self.usage.is_all() && self.mechanisms.is_empty() && self.pin.is_none()
+    }
+
+    pub fn permits(&self, mechanism: Mechanism, usage: Usage) -> bool {
+        self.usage.contains(usage)
+            && (self.mechanisms.is_empty() || self.mechanisms.contains(&mechanism))
+    }
+}
+
+/// Point in time after which a key may no longer be used, in seconds.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Deadline {
+    /// Measured by `UserInterface::uptime`, so only meaningful for volatile keys.
+    Uptime(u64),
+    /// Measured by `UserInterface::time`, since the UNIX epoch. Keys with such a deadline
+    /// can not be used on platforms without a clock.
+    Time(u64),
+}
+
+/// Limits after which a key disables itself.
+///
+/// Checked each time the key is loaded for use by a mechanism; keys used up
+/// or past their deadline are deleted. Keys with limits can not be extractable, so they
+/// can not be copied by wrapping, or by a backup.
+///
+/// `Deadline::Uptime` is only allowed for volatile keys, as the uptime restarts at boot,
+/// and `Deadline::Time` only on platforms with a clock.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Limits {
+    /// Number of times the key may still be used.
+    pub remaining_uses: Option<u32>,
+    pub deadline: Option<Deadline>,
+}
+
+impl Limits {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn set_remaining_uses(mut self, remaining_uses: u32) -> Self {
+        self.remaining_uses = Some(remaining_uses);
+        self
+    }
+
+    pub fn set_deadline(mut self, deadline: Deadline) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.remaining_uses.is_none() && self.deadline.is_none()
     }
 }
 
@@ -69,15 +253,37 @@ bitflags::bitflags! {
     #[derive(DeserializeIndexed, SerializeIndexed, Zeroize)]
     /// All non-used bits are RFU.
     ///
-    /// In particular, top bit is intended to be used to accomodate breaking format changes,
-    /// i.e., if `flags >> 32 != 0`, then the format is different.
+    /// The top bit accomodates breaking format changes: if it is set, the flags are
+    /// followed by a format version, see `SERIALIZATION_VERSION`.
     pub struct Flags: u16 {
         const LOCAL = 1 << 0;
         const SENSITIVE = 1 << 1;
+        /// The header carries a usage policy. Managed by (de)serialization,
+        /// set if and only if the policy is restricted.
+        const POLICY = 1 << 2;
+        /// The key may leave the keystore wrapped. Can only be set at creation.
+        const EXTRACTABLE = 1 << 3;
+        /// The key was created non-extractable, and was never extractable since.
+        const NEVER_EXTRACTABLE = 1 << 4;
+        /// The header carries metadata. Managed by (de)serialization,
+        /// set if and only if there is any.
+        const METADATA = 1 << 5;
+        /// The header carries usage limits. Managed by (de)serialization,
+        /// set if and only if there are any.
+        const LIMITS = 1 << 6;
+        /// The file holds an encrypted key, only these flags are in the clear.
+        /// Managed by the keystore, never set on a deserialized key.
+        const ENCRYPTED = 1 << 7;
+        /// The header carries a PIN requirement. Managed by (de)serialization,
+        /// set if and only if the policy has one.
+        const PIN = 1 << 8;
+        /// The flags are followed by the format version. Managed by (de)serialization and
+        /// the keystore, set on everything they write.
+        const VERSIONED = 1 << 15;
     }
 }
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 /// A key can either be public, of secret.
 ///
 /// The secret case also applies to private keys for asymmetric algorithms.
@@ -92,27 +298,169 @@ impl Key {
         let mut buffer = SerializedKeyBytes::new();
         // big-endian here to ensure the first bit is enough to check compatibility
         // on breaking format change
-        buffer.extend_from_slice(&self.flags.bits().to_be_bytes()).unwrap();
+        let mut flags = self.flags - Flags::POLICY - Flags::PIN - Flags::METADATA - Flags::LIMITS - Flags::ENCRYPTED;
+        flags |= Flags::VERSIONED;
+        if !self.policy.usage.is_all() || !self.policy.mechanisms.is_empty() {
+            flags |= Flags::POLICY;
+        }
+        if self.policy.pin.is_some() {
+            flags |= Flags::PIN;
+        }
+        if !self.limits.is_unlimited() {
+            flags |= Flags::LIMITS;
+        }
+        if self.metadata != Metadata::default() {
+            flags |= Flags::METADATA;
+        }
+        buffer.extend_from_slice(&flags.bits().to_be_bytes()).unwrap();
+        buffer.push(SERIALIZATION_VERSION).unwrap();
         buffer.extend_from_slice(&(self.kind.code()).to_be_bytes()).unwrap();
-        // can't fail, since MAX_SERIALIZED_KEY_LENGTH is defined as MAX_KEY_MATERIAL_LENGTH + 4
+        buffer.extend_from_slice(&(self.kind.parameter()).to_be_bytes()).unwrap();
+        // unrestricted policies are not stored, keeping the format of keys without policy
+        if flags.contains(Flags::POLICY) {
+            buffer.extend_from_slice(&self.policy.usage.bits().to_be_bytes()).unwrap();
+            buffer.push(self.policy.mechanisms.len() as u8).unwrap();
+            for mechanism in self.policy.mechanisms.iter() {
+                buffer.push(mechanism_code(*mechanism)).unwrap();
+            }
+        }
+        if let Some(requirement) = self.policy.pin {
+            buffer.extend_from_slice(&requirement.pin.0.0.to_be_bytes()).unwrap();
+            // zero is "until reboot"
+            buffer.extend_from_slice(&requirement.validity.unwrap_or(0).to_be_bytes()).unwrap();
+        }
+        if flags.contains(Flags::LIMITS) {
+            // a presence byte, followed by the limits that are present
+            let presence = self.limits.remaining_uses.is_some() as u8
+                | match self.limits.deadline {
+                    None => 0,
+                    Some(Deadline::Uptime(_)) => 2,
+                    Some(Deadline::Time(_)) => 4,
+                };
+            buffer.push(presence).unwrap();
+            if let Some(remaining_uses) = self.limits.remaining_uses {
+                buffer.extend_from_slice(&remaining_uses.to_be_bytes()).unwrap();
+            }
+            if let Some(Deadline::Uptime(seconds)) | Some(Deadline::Time(seconds)) = self.limits.deadline {
+                buffer.extend_from_slice(&seconds.to_be_bytes()).unwrap();
+            }
+        }
+        if flags.contains(Flags::METADATA) {
+            // zero is "no creation time"
+            buffer.extend_from_slice(&self.metadata.created.unwrap_or(0).to_be_bytes()).unwrap();
+            serialize_optional_bytes(&mut buffer, self.metadata.label.as_ref().map(|label| &label[..]));
+            serialize_optional_bytes(&mut buffer, self.metadata.tag.as_ref().map(|tag| &tag[..]));
+        }
+        // can't fail, since MAX_SERIALIZED_KEY_LENGTH accounts for all of the header
         buffer.extend_from_slice(&self.material).unwrap();
         buffer
     }
 
+    /// Deserializes a key in the current format, or any earlier one.
+    ///
+    /// Version 0 has no version byte, and derives the parameter of the kind from
+    /// the length of the material.
     pub fn try_deserialize(bytes: &[u8]) -> Result<Self, Error> {
-        if bytes.len() < 4 {
+        let version = serialized_version(bytes)?;
+        let header_length = match version {
+            0 => 4,
+            _ => 7,
+        };
+        if bytes.len() < header_length {
             return Err(Error::InvalidSerializedKey);
         }
-        let (info, material) = bytes.split_at(4);
+        let (info, mut material) = bytes.split_at(header_length);
         let flags_bits = u16::from_be_bytes([info[0], info[1]]);
         let flags = Flags::from_bits(flags_bits).ok_or(Error::InvalidSerializedKey)?;
+        if flags.contains(Flags::ENCRYPTED) {
+            return Err(Error::InvalidSerializedKey);
+        }
+
+        let mut policy = Policy::default();
+        if flags.contains(Flags::POLICY) {
+            if material.len() < 3 {
+                return Err(Error::InvalidSerializedKey);
+            }
+            let usage_bits = u16::from_be_bytes([material[0], material[1]]);
+            policy.usage = Usage::from_bits(usage_bits).ok_or(Error::InvalidSerializedKey)?;
+            let count = material[2] as usize;
+            if material.len() < 3 + count {
+                return Err(Error::InvalidSerializedKey);
+            }
+            for code in &material[3..3 + count] {
+                policy.mechanisms.push(mechanism_from_code(*code)?)
+                    .map_err(|_| Error::InvalidSerializedKey)?;
+            }
+            material = &material[3 + count..];
+        }
+        if flags.contains(Flags::PIN) {
+            if material.len() < 20 {
+                return Err(Error::InvalidSerializedKey);
+            }
+            let (requirement, rest) = material.split_at(20);
+            let validity = u32::from_be_bytes(requirement[16..].try_into().unwrap());
+            policy.pin = Some(PinRequirement {
+                pin: PinId(Id(u128::from_be_bytes(requirement[..16].try_into().unwrap()))),
+                validity: Some(validity).filter(|validity| *validity != 0),
+            });
+            material = rest;
+        }
+
+        let mut limits = Limits::default();
+        if flags.contains(Flags::LIMITS) {
+            let (presence, rest) = material.split_first().ok_or(Error::InvalidSerializedKey)?;
+            material = rest;
+            if presence & 1 != 0 {
+                if material.len() < 4 {
+                    return Err(Error::InvalidSerializedKey);
+                }
+                let (remaining_uses, rest) = material.split_at(4);
+                limits.remaining_uses = Some(u32::from_be_bytes(remaining_uses.try_into().unwrap()));
+                material = rest;
+            }
+            if presence & 6 != 0 {
+                if material.len() < 8 {
+                    return Err(Error::InvalidSerializedKey);
+                }
+                let (seconds, rest) = material.split_at(8);
+                let seconds = u64::from_be_bytes(seconds.try_into().unwrap());
+                limits.deadline = Some(match presence & 6 {
+                    2 => Deadline::Uptime(seconds),
+                    4 => Deadline::Time(seconds),
+                    _ => return Err(Error::InvalidSerializedKey),
+                });
+                material = rest;
+            }
+        }
+
+        let mut metadata = Metadata::default();
+        if flags.contains(Flags::METADATA) {
+            if material.len() < 8 {
+                return Err(Error::InvalidSerializedKey);
+            }
+            let mut created = [0u8; 8];
+            created.copy_from_slice(&material[..8]);
+            metadata.created = Some(u64::from_be_bytes(created)).filter(|created| *created != 0);
+            material = &material[8..];
+            metadata.label = deserialize_optional_bytes(&mut material)?;
+            metadata.tag = deserialize_optional_bytes(&mut material)?;
+        }
 
-        let kind_bits = u16::from_be_bytes([info[2], info[3]]);
-        let kind = Kind::try_from(kind_bits, material.len()).map_err(|_| Error::InvalidSerializedKey)?;
+        let kind = match version {
+            0 => Kind::try_from(u16::from_be_bytes([info[2], info[3]]), material.len())?,
+            _ => Kind::try_from_parameter(
+                u16::from_be_bytes([info[3], info[4]]),
+                u16::from_be_bytes([info[5], info[6]]),
+                material.len(),
+            )?,
+        };
 
         Ok(Key {
-            flags,
+            flags: flags - Flags::POLICY - Flags::PIN - Flags::METADATA - Flags::LIMITS - Flags::VERSIONED,
             kind,
+            policy,
+            limits,
+            metadata,
             material: Material::from_slice(material).map_err(|_| Error::InvalidSerializedKey)?,
         })
     }
@@ -139,11 +487,44 @@ impl Kind {
         }
     }
 
+    /// Size of the key in bytes, not counting parameters such as nonces.
+    pub fn size(self) -> usize {
+        match self {
+            Kind::Shared(size) => size,
+            Kind::Symmetric(size) => size,
+            Kind::Symmetric32Nonce(_) => 32,
+            Kind::Ed255 => 32,
+            Kind::P256 => 32,
+            Kind::X255 => 32,
+        }
+    }
+
+    /// The parameter of the kind that is not implied by the length of the material.
+    pub fn parameter(self) -> u16 {
+        match self {
+            Kind::Symmetric32Nonce(nonce_length) => nonce_length as u16,
+            _ => 0,
+        }
+    }
+
+    /// Decodes the kind stored in the current format, checking the length of its material.
+    pub fn try_from_parameter(code: u16, parameter: u16, length: usize) -> Result<Self, Error> {
+        let kind = match code {
+            3 => Self::Symmetric32Nonce(parameter as usize),
+            _ => Self::try_from(code, length)?,
+        };
+        if parameter != kind.parameter() || matches!(kind, Kind::Symmetric32Nonce(nonce_length) if length != 32 + nonce_length) {
+            return Err(Error::InvalidSerializedKey);
+        }
+        Ok(kind)
+    }
+
+    /// Decodes the kind stored in version 0 of the format.
     pub fn try_from(code: u16, length: usize) -> Result<Self, Error> {
         Ok(match code {
             1 => Self::Shared(length),
             2 => Self::Symmetric(length),
-            3 => Self::Symmetric32Nonce(length - 32),
+            3 => Self::Symmetric32Nonce(length.checked_sub(32).ok_or(Error::InvalidSerializedKey)?),
             4 => Self::Ed255,
             5 => Self::P256,
             6 => Self::X255,
@@ -151,3 +532,115 @@ impl Kind {
         })
     }
 }
+
+/// Version of the format that keys are serialized in.
+///
+/// Version 0 headers are the flags, without `Flags::VERSIONED`, and the kind. Later headers
+/// are the flags, the version, the kind and its parameter. Keys in an earlier format are
+/// upgraded by the keystore as they are used.
+pub const SERIALIZATION_VERSION: u8 = 1;
+
+/// Returns the format version of a serialized key, or of a key file encrypted at rest.
+pub fn serialized_version(bytes: &[u8]) -> Result<u8, Error> {
+    let flags = match bytes {
+        [first, second, ..] => Flags::from_bits_truncate(u16::from_be_bytes([*first, *second])),
+        _ => return Err(Error::InvalidSerializedKey),
+    };
+    if !flags.contains(Flags::VERSIONED) {
+        return Ok(0);
+    }
+    match bytes.get(2) {
+        Some(version) if *version != 0 && *version <= SERIALIZATION_VERSION => Ok(*version),
+        Some(_) => Err(Error::UnsupportedKeyVersion),
+        None => Err(Error::InvalidSerializedKey),
+    }
+}
+
+// Optional byte strings are stored as length plus one (zero for none), followed by the bytes.
+fn serialize_optional_bytes(buffer: &mut SerializedKeyBytes, bytes: Option<&[u8]>) {
+    match bytes {
+        Some(bytes) => {
+            buffer.push(bytes.len() as u8 + 1).unwrap();
+            buffer.extend_from_slice(bytes).unwrap();
+        }
+        None => buffer.push(0).unwrap(),
+    }
+}
+
+fn deserialize_optional_bytes<const N: usize>(bytes: &mut &[u8]) -> Result<Option<Bytes<N>>, Error> {
+    let (length, rest) = bytes.split_first().ok_or(Error::InvalidSerializedKey)?;
+    if *length == 0 {
+        *bytes = rest;
+        return Ok(None);
+    }
+    let length = *length as usize - 1;
+    if rest.len() < length {
+        return Err(Error::InvalidSerializedKey);
+    }
+    let (value, rest) = rest.split_at(length);
+    *bytes = rest;
+    Bytes::from_slice(value).map(Some).map_err(|_| Error::InvalidSerializedKey)
+}
+
+// Storage codes of mechanisms in allowed-mechanism lists. These must stay stable,
+// new mechanisms get new codes.
+fn mechanism_code(mechanism: Mechanism) -> u8 {
+    match mechanism {
+        Mechanism::Aes128Cbc => 1,
+        Mechanism::Aes128Ctr => 2,
+        Mechanism::Aes128Ecb => 3,
+        Mechanism::Aes192Cbc => 4,
+        Mechanism::Aes192Ctr => 5,
+        Mechanism::Aes192Ecb => 6,
+        Mechanism::Aes256Cbc => 7,
+        Mechanism::Aes256Ctr => 8,
+        Mechanism::Aes256Ecb => 9,
+        Mechanism::Chacha8Poly1305 => 10,
+        Mechanism::Ed255 => 11,
+        Mechanism::HmacBlake2s => 12,
+        Mechanism::HmacSha1 => 13,
+        Mechanism::HmacSha256 => 14,
+        Mechanism::HmacSha512 => 15,
+        Mechanism::P256 => 16,
+        Mechanism::P256Prehashed => 17,
+        Mechanism::Sha256 => 18,
+        Mechanism::Tdes => 19,
+        Mechanism::TdesCbc => 20,
+        Mechanism::TdesCtr => 21,
+        Mechanism::TdesEcb => 22,
+        Mechanism::Totp => 23,
+        Mechanism::Trng => 24,
+        Mechanism::X255 => 25,
+    }
+}
+
+fn mechanism_from_code(code: u8) -> Result<Mechanism, Error> {
+    Ok(match code {
+        1 => Mechanism::Aes128Cbc,
+        2 => Mechanism::Aes128Ctr,
+        3 => Mechanism::Aes128Ecb,
+        4 => Mechanism::Aes192Cbc,
+        5 => Mechanism::Aes192Ctr,
+        6 => Mechanism::Aes192Ecb,
+        7 => Mechanism::Aes256Cbc,
+        8 => Mechanism::Aes256Ctr,
+        9 => Mechanism::Aes256Ecb,
+        10 => Mechanism::Chacha8Poly1305,
+        11 => Mechanism::Ed255,
+        12 => Mechanism::HmacBlake2s,
+        13 => Mechanism::HmacSha1,
+        14 => Mechanism::HmacSha256,
+        15 => Mechanism::HmacSha512,
+        16 => Mechanism::P256,
+        17 => Mechanism::P256Prehashed,
+        18 => Mechanism::Sha256,
+        19 => Mechanism::Tdes,
+        20 => Mechanism::TdesCbc,
+        21 => Mechanism::TdesCtr,
+        22 => Mechanism::TdesEcb,
+        23 => Mechanism::Totp,
+        24 => Mechanism::Trng,
+        25 => Mechanism::X255,
+        _ => return Err(Error::InvalidSerializedKey),
+    })
+}
@@ -29,6 +29,7 @@ pub mod platform;
 pub mod service;
 pub mod store;
 pub mod types;
+pub mod x509;
 
 pub use api::Reply;
 pub use error::Error;
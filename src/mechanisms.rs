@@ -10,6 +10,20 @@
 // The question of breaking down `reply_to` into smaller, more globally understandable pieces,
 // should be revisited.
 
+// shared ECB/CBC/CTR implementation, defines `impl_block_mode!`
+#[macro_use]
+mod block;
+
+pub struct Aes128Cbc {}
+pub struct Aes128Ctr {}
+pub struct Aes128Ecb {}
+pub struct Aes192Cbc {}
+pub struct Aes192Ctr {}
+pub struct Aes192Ecb {}
+pub struct Aes256Ctr {}
+pub struct Aes256Ecb {}
+mod aes;
+
 // TODO: rename to aes256-cbc-zero-iv
 pub struct Aes256Cbc {}
 mod aes256cbc;
@@ -27,6 +41,10 @@ mod hmacblake2s;
 impl crate::service::DeriveKey for HmacBlake2s {}
 #[cfg(not(feature = "hmac-blake2s"))]
 impl crate::service::Sign for HmacBlake2s {}
+#[cfg(not(feature = "hmac-blake2s"))]
+impl crate::service::GenerateKey for HmacBlake2s {}
+#[cfg(not(feature = "hmac-blake2s"))]
+impl crate::service::UnsafeInjectKey for HmacBlake2s {}
 
 pub struct HmacSha1 {}
 mod hmacsha1;
@@ -41,6 +59,10 @@ mod hmacsha512;
 impl crate::service::DeriveKey for HmacSha512 {}
 #[cfg(not(feature = "hmac-sha512"))]
 impl crate::service::Sign for HmacSha512 {}
+#[cfg(not(feature = "hmac-sha512"))]
+impl crate::service::GenerateKey for HmacSha512 {}
+#[cfg(not(feature = "hmac-sha512"))]
+impl crate::service::UnsafeInjectKey for HmacSha512 {}
 
 pub struct P256 {}
 pub struct P256Prehashed {}
@@ -50,6 +72,9 @@ pub struct Sha256 {}
 mod sha256;
 
 pub struct Tdes {}
+pub struct TdesCbc {}
+pub struct TdesCtr {}
+pub struct TdesEcb {}
 mod tdes;
 
 pub struct Totp {}
@@ -61,6 +86,31 @@ mod trng;
 pub struct X255 {}
 mod x255;
 
+/// Stores an injected key of any length as a symmetric key, shared by the HMAC and TOTP mechanisms.
+#[cfg(any(feature = "hmac-blake2s", feature = "hmac-sha1", feature = "hmac-sha256", feature = "hmac-sha512", feature = "totp"))]
+#[inline(never)]
+fn unsafe_inject_symmetric_key(
+    keystore: &mut impl crate::store::keystore::Keystore,
+    request: &crate::api::request::UnsafeInjectKey,
+)
+    -> Result<crate::api::reply::UnsafeInjectKey, crate::error::Error>
+{
+    use crate::key;
+
+    if request.raw_key.is_empty() {
+        return Err(crate::error::Error::InvalidSerializedKey);
+    }
+
+    // not `LOCAL`, we have no idea where this key has been
+    let key_id = keystore.store_key(
+        request.attributes.persistence,
+        key::Secrecy::Secret,
+        key::Info::from(key::Kind::Symmetric(request.raw_key.len())).with_attributes(&request.attributes),
+        &request.raw_key)?;
+
+    Ok(crate::api::reply::UnsafeInjectKey { key: key_id })
+}
+
 // pub enum MechanismEnum {
 //     NotImplemented,
 //     Ed255(ed255::Ed255),
@@ -0,0 +1,24 @@
+//! AES-128, AES-192 and AES-256 in ECB, CBC and CTR mode.
+//!
+//! A raw single-block operation is ECB on a message of one block.
+//! See `super::block` for the conventions on padding, IVs and counter blocks.
+//!
+//! Keys are `Kind::Symmetric(16)`, `Kind::Symmetric(24)` resp. `Kind::Symmetric(32)`,
+//! and can be used with any of the modes of their size.
+//!
+//! `Aes256Cbc` predates these and zero-pads, it is implemented in `super::aes256cbc`.
+
+use crate::api::*;
+use crate::error::Error;
+use crate::service::*;
+
+impl_block_mode!("aes128", aes::Aes128, Aes128Ecb, ecb_encrypt, ecb_decrypt);
+impl_block_mode!("aes128", aes::Aes128, Aes128Cbc, cbc_encrypt, cbc_decrypt);
+impl_block_mode!("aes128", aes::Aes128, Aes128Ctr, ctr_encrypt, ctr_decrypt);
+
+impl_block_mode!("aes192", aes::Aes192, Aes192Ecb, ecb_encrypt, ecb_decrypt);
+impl_block_mode!("aes192", aes::Aes192, Aes192Cbc, cbc_encrypt, cbc_decrypt);
+impl_block_mode!("aes192", aes::Aes192, Aes192Ctr, ctr_encrypt, ctr_decrypt);
+
+impl_block_mode!("aes256", aes::Aes256, Aes256Ecb, ecb_encrypt, ecb_decrypt);
+impl_block_mode!("aes256", aes::Aes256, Aes256Ctr, ctr_encrypt, ctr_decrypt);
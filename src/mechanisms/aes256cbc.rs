@@ -7,10 +7,10 @@ use crate::service::*;
 use crate::types::*;
 
 #[cfg(feature = "aes256-cbc")]
-impl Encrypt for super::Aes256Cbc
+impl super::Aes256Cbc
 {
-    /// Encrypts the input *with zero IV*
-    fn encrypt(keystore: &mut impl Keystore, request: &request::Encrypt)
+    /// Encrypts the input with the IV passed as nonce, *zero IV* if there is none
+    fn encrypt_with_usage(keystore: &mut impl Keystore, request: &request::Encrypt, usage: key::Usage)
         -> Result<reply::Encrypt, Error>
     {
 		use block_modes::{BlockMode, Cbc};
@@ -27,12 +27,15 @@ impl Encrypt for super::Aes256Cbc
         // keystore.load_key(&path, key::Kind::SymmetricKey32, &mut symmetric_key)?;
 
         let symmetric_key: [u8; 32] = keystore
-            .load_key(key::Secrecy::Secret, None, &key_id)?
+            .load_key_for(key::Secrecy::Secret, Some(key::Kind::Symmetric(32)), &key_id, request.mechanism, usage)?
             .material.as_slice().try_into()
             .map_err(|_| Error::InternalError)?;
 
-        let zero_iv = [0u8; 16];
-		let cipher = Aes256Cbc::new_from_slices(&symmetric_key, &zero_iv).unwrap();
+        let iv: [u8; 16] = match &request.nonce {
+            Some(nonce) => nonce.as_slice().try_into().map_err(|_| Error::MechanismParamInvalid)?,
+            None => [0u8; 16],
+        };
+		let cipher = Aes256Cbc::new_from_slices(&symmetric_key, &iv).unwrap();
 
 		// buffer must have enough space for message+padding
 		let mut buffer = request.message.clone();
@@ -52,6 +55,16 @@ impl Encrypt for super::Aes256Cbc
     }
 }
 
+#[cfg(feature = "aes256-cbc")]
+impl Encrypt for super::Aes256Cbc
+{
+    fn encrypt(keystore: &mut impl Keystore, request: &request::Encrypt)
+        -> Result<reply::Encrypt, Error>
+    {
+        Self::encrypt_with_usage(keystore, request, key::Usage::ENCRYPT)
+    }
+}
+
 #[cfg(feature = "aes256-cbc")]
 impl WrapKey for super::Aes256Cbc
 {
@@ -65,7 +78,7 @@ impl WrapKey for super::Aes256Cbc
         // let message: Message = serialized_key.material.try_to_byte_buf().map_err(|_| Error::InternalError)?;
 
         let message = Message::from_slice(keystore
-            .load_key(key::Secrecy::Secret, None, &request.key)?
+            .load_extractable_key(&request.key)?
             .material.as_slice()).map_err(|_| Error::InternalError)?;
 
         let encryption_request = request::Encrypt {
@@ -75,7 +88,7 @@ impl WrapKey for super::Aes256Cbc
             associated_data: ShortData::new(),
             nonce: None,
         };
-        let encryption_reply = <super::Aes256Cbc>::encrypt(keystore, &encryption_request)?;
+        let encryption_reply = <super::Aes256Cbc>::encrypt_with_usage(keystore, &encryption_request, key::Usage::WRAP)?;
 
         let wrapped_key = encryption_reply.ciphertext;
 
@@ -99,13 +112,16 @@ impl Decrypt for super::Aes256Cbc
 
         let key_id = request.key;
         let symmetric_key: [u8; 32] = keystore
-            .load_key(key::Secrecy::Secret, None, &key_id)?
+            .load_key_for(key::Secrecy::Secret, Some(key::Kind::Symmetric(32)), &key_id, request.mechanism, key::Usage::DECRYPT)?
             .material.as_slice()
             .try_into()
             .map_err(|_| Error::InternalError)?;
 
-        let zero_iv = [0u8; 16];
-		let cipher = Aes256Cbc::new_from_slices(&symmetric_key, &zero_iv).unwrap();
+        let iv: [u8; 16] = match request.nonce.len() {
+            0 => [0u8; 16],
+            _ => request.nonce.as_slice().try_into().map_err(|_| Error::MechanismParamInvalid)?,
+        };
+		let cipher = Aes256Cbc::new_from_slices(&symmetric_key, &iv).unwrap();
 
 		// buffer must have enough space for message+padding
 		let mut buffer = request.message.clone();
@@ -127,8 +143,33 @@ impl Decrypt for super::Aes256Cbc
     }
 }
 
+#[cfg(feature = "aes256-cbc")]
+impl GenerateKey for super::Aes256Cbc
+{
+    fn generate_key(keystore: &mut impl Keystore, request: &request::GenerateKey)
+        -> Result<reply::GenerateKey, Error>
+    {
+        super::block::generate_key::<aes::Aes256>(keystore, request)
+    }
+}
+
+#[cfg(feature = "aes256-cbc")]
+impl UnsafeInjectKey for super::Aes256Cbc
+{
+    fn unsafe_inject_key(keystore: &mut impl Keystore, request: &request::UnsafeInjectKey)
+        -> Result<reply::UnsafeInjectKey, Error>
+    {
+        super::block::unsafe_inject_key::<aes::Aes256>(keystore, request)
+    }
+}
+
 #[cfg(not(feature = "aes256-cbc"))]
 impl Decrypt for super::Aes256Cbc {}
 
 #[cfg(not(feature = "aes256-cbc"))]
 impl Encrypt for super::Aes256Cbc {}
+
+#[cfg(not(feature = "aes256-cbc"))]
+impl GenerateKey for super::Aes256Cbc {}
+#[cfg(not(feature = "aes256-cbc"))]
+impl UnsafeInjectKey for super::Aes256Cbc {}
@@ -0,0 +1,267 @@
+//! Shared plumbing for the block cipher mechanisms (AES and Triple-DES).
+//!
+//! ECB and CBC do no padding, messages must be a multiple of the block size,
+//! or they fail with `WrongMessageLength`. The protocols needing these modes
+//! (PIV, OpenPGP, GlobalPlatform SCP) all specify their own padding, which is
+//! left to the client.
+//!
+//! The IV (CBC) resp. the initial counter block (CTR) is passed as `nonce`.
+//! Without one, encryption uses a random IV resp. initial counter block, and
+//! either way, the value used is returned as `nonce` of the reply. Decryption
+//! requires it. The counter block is incremented as one big-endian integer
+//! (NIST SP 800-38A).
+
+use block_modes::{BlockMode, Cbc, Ecb};
+use block_modes::block_padding::NoPadding;
+use block_modes::cipher::{Block, BlockCipher, BlockDecrypt, BlockEncrypt, NewBlockCipher};
+use generic_array::typenum::Unsigned;
+use rand_core::RngCore;
+
+use crate::api::*;
+use crate::error::Error;
+use crate::service::*;
+use crate::types::*;
+
+fn load_key<C: NewBlockCipher>(keystore: &mut impl Keystore, key_id: &KeyId, mechanism: Mechanism, usage: key::Usage)
+    -> Result<key::Material, Error>
+{
+    Ok(keystore
+        .load_key_for(key::Secrecy::Secret, Some(key::Kind::Symmetric(C::KeySize::USIZE)), key_id, mechanism, usage)?
+        .material)
+}
+
+/// The IV or initial counter block to encrypt with, random if the request has none.
+fn nonce_or_random<C: BlockCipher>(keystore: &mut impl Keystore, nonce: Option<&ShortData>) -> Result<ShortData, Error> {
+    match nonce {
+        Some(nonce) if nonce.len() == C::BlockSize::USIZE => Ok(nonce.clone()),
+        Some(_) => Err(Error::MechanismParamInvalid),
+        None => {
+            let mut nonce = ShortData::new();
+            nonce.resize_default(C::BlockSize::USIZE).map_err(|_| Error::InternalError)?;
+            keystore.rng().fill_bytes(&mut nonce);
+            Ok(nonce)
+        }
+    }
+}
+
+/// ECB and CBC do not pad.
+fn check_block_multiple<C: BlockCipher>(message: &[u8]) -> Result<(), Error> {
+    match message.len() % C::BlockSize::USIZE {
+        0 => Ok(()),
+        _ => Err(Error::WrongMessageLength),
+    }
+}
+
+fn apply_mode_encrypt<C, M>(key: &[u8], iv: &[u8], message: &Message) -> Result<Message, Error>
+where
+    C: BlockCipher + NewBlockCipher,
+    M: BlockMode<C, NoPadding>,
+{
+    check_block_multiple::<C>(message)?;
+    let cipher = M::new_from_slices(key, iv).map_err(|_| Error::MechanismParamInvalid)?;
+    let mut buffer = message.clone();
+    let l = buffer.len();
+    cipher.encrypt(&mut buffer, l).map_err(|_| Error::WrongMessageLength)?;
+    Ok(buffer)
+}
+
+fn apply_mode_decrypt<C, M>(key: &[u8], iv: &[u8], message: &Message) -> Result<Message, Error>
+where
+    C: BlockCipher + NewBlockCipher,
+    M: BlockMode<C, NoPadding>,
+{
+    check_block_multiple::<C>(message)?;
+    let cipher = M::new_from_slices(key, iv).map_err(|_| Error::MechanismParamInvalid)?;
+    let mut buffer = message.clone();
+    cipher.decrypt(&mut buffer).map_err(|_| Error::WrongMessageLength)?;
+    Ok(buffer)
+}
+
+/// CTR mode is its own inverse.
+fn apply_ctr<C>(key: &[u8], counter_block: &[u8], message: &Message) -> Result<Message, Error>
+where
+    C: BlockEncrypt + NewBlockCipher,
+{
+    if counter_block.len() != C::BlockSize::USIZE {
+        return Err(Error::MechanismParamInvalid);
+    }
+    let cipher = C::new_from_slice(key).map_err(|_| Error::InternalError)?;
+
+    let mut counter = Block::<C>::default();
+    counter.copy_from_slice(counter_block);
+    let mut buffer = message.clone();
+    for chunk in buffer.chunks_mut(C::BlockSize::USIZE) {
+        let mut keystream = counter.clone();
+        cipher.encrypt_block(&mut keystream);
+        for (byte, key_byte) in chunk.iter_mut().zip(keystream.iter()) {
+            *byte ^= key_byte;
+        }
+        // big-endian increment, wrapping around
+        for byte in counter.iter_mut().rev() {
+            *byte = byte.wrapping_add(1);
+            if *byte != 0 {
+                break;
+            }
+        }
+    }
+    Ok(buffer)
+}
+
+#[inline(never)]
+pub(super) fn generate_key<C: NewBlockCipher>(keystore: &mut impl Keystore, request: &request::GenerateKey)
+    -> Result<reply::GenerateKey, Error>
+{
+    let size = C::KeySize::USIZE;
+    let mut symmetric_key = ShortData::new();
+    symmetric_key.resize_default(size).map_err(|_| Error::InternalError)?;
+    keystore.rng().fill_bytes(&mut symmetric_key);
+
+    let key_id = keystore.store_key(
+        request.attributes.persistence,
+        key::Secrecy::Secret,
+        key::Info::from(key::Kind::Symmetric(size)).with_local_flag().with_attributes(&request.attributes),
+        &symmetric_key,
+    )?;
+
+    Ok(reply::GenerateKey { key: key_id })
+}
+
+#[inline(never)]
+pub(super) fn unsafe_inject_key<C: NewBlockCipher>(keystore: &mut impl Keystore, request: &request::UnsafeInjectKey)
+    -> Result<reply::UnsafeInjectKey, Error>
+{
+    let size = C::KeySize::USIZE;
+    if request.raw_key.len() != size {
+        return Err(Error::InvalidSerializedKey);
+    }
+
+    // not `LOCAL`, we have no idea where this key has been
+    let key_id = keystore.store_key(
+        request.attributes.persistence,
+        key::Secrecy::Secret,
+        key::Info::from(key::Kind::Symmetric(size)).with_attributes(&request.attributes),
+        &request.raw_key,
+    )?;
+
+    Ok(reply::UnsafeInjectKey { key: key_id })
+}
+
+#[inline(never)]
+pub(super) fn ecb_encrypt<C>(keystore: &mut impl Keystore, request: &request::Encrypt)
+    -> Result<reply::Encrypt, Error>
+where
+    C: BlockCipher + BlockEncrypt + BlockDecrypt + NewBlockCipher,
+{
+    let symmetric_key = load_key::<C>(keystore, &request.key, request.mechanism, key::Usage::ENCRYPT)?;
+    let ciphertext = apply_mode_encrypt::<C, Ecb<C, NoPadding>>(&symmetric_key, &[], &request.message)?;
+    Ok(reply::Encrypt { ciphertext, nonce: ShortData::new(), tag: ShortData::new() })
+}
+
+#[inline(never)]
+pub(super) fn ecb_decrypt<C>(keystore: &mut impl Keystore, request: &request::Decrypt)
+    -> Result<reply::Decrypt, Error>
+where
+    C: BlockCipher + BlockEncrypt + BlockDecrypt + NewBlockCipher,
+{
+    let symmetric_key = load_key::<C>(keystore, &request.key, request.mechanism, key::Usage::DECRYPT)?;
+    let plaintext = apply_mode_decrypt::<C, Ecb<C, NoPadding>>(&symmetric_key, &[], &request.message)?;
+    Ok(reply::Decrypt { plaintext: Some(plaintext) })
+}
+
+#[inline(never)]
+pub(super) fn cbc_encrypt<C>(keystore: &mut impl Keystore, request: &request::Encrypt)
+    -> Result<reply::Encrypt, Error>
+where
+    C: BlockCipher + BlockEncrypt + BlockDecrypt + NewBlockCipher,
+{
+    let symmetric_key = load_key::<C>(keystore, &request.key, request.mechanism, key::Usage::ENCRYPT)?;
+    let iv = nonce_or_random::<C>(keystore, request.nonce.as_ref())?;
+    let ciphertext = apply_mode_encrypt::<C, Cbc<C, NoPadding>>(&symmetric_key, &iv, &request.message)?;
+    Ok(reply::Encrypt { ciphertext, nonce: iv, tag: ShortData::new() })
+}
+
+#[inline(never)]
+pub(super) fn cbc_decrypt<C>(keystore: &mut impl Keystore, request: &request::Decrypt)
+    -> Result<reply::Decrypt, Error>
+where
+    C: BlockCipher + BlockEncrypt + BlockDecrypt + NewBlockCipher,
+{
+    let symmetric_key = load_key::<C>(keystore, &request.key, request.mechanism, key::Usage::DECRYPT)?;
+    let plaintext = apply_mode_decrypt::<C, Cbc<C, NoPadding>>(&symmetric_key, &request.nonce, &request.message)?;
+    Ok(reply::Decrypt { plaintext: Some(plaintext) })
+}
+
+#[inline(never)]
+pub(super) fn ctr_encrypt<C>(keystore: &mut impl Keystore, request: &request::Encrypt)
+    -> Result<reply::Encrypt, Error>
+where
+    C: BlockEncrypt + NewBlockCipher,
+{
+    let symmetric_key = load_key::<C>(keystore, &request.key, request.mechanism, key::Usage::ENCRYPT)?;
+    let counter_block = nonce_or_random::<C>(keystore, request.nonce.as_ref())?;
+    let ciphertext = apply_ctr::<C>(&symmetric_key, &counter_block, &request.message)?;
+    Ok(reply::Encrypt { ciphertext, nonce: counter_block, tag: ShortData::new() })
+}
+
+#[inline(never)]
+pub(super) fn ctr_decrypt<C>(keystore: &mut impl Keystore, request: &request::Decrypt)
+    -> Result<reply::Decrypt, Error>
+where
+    C: BlockEncrypt + NewBlockCipher,
+{
+    let symmetric_key = load_key::<C>(keystore, &request.key, request.mechanism, key::Usage::DECRYPT)?;
+    let plaintext = apply_ctr::<C>(&symmetric_key, &request.nonce, &request.message)?;
+    Ok(reply::Decrypt { plaintext: Some(plaintext) })
+}
+
+/// Implements `Encrypt`, `Decrypt`, `GenerateKey` and `UnsafeInjectKey` for a block cipher mode mechanism,
+/// or the unimplemented defaults if the feature is not enabled.
+macro_rules! impl_block_mode {
+    ($feature:literal, $Cipher:ty, $Mechanism:ident, $encrypt:ident, $decrypt:ident) => {
+
+        #[cfg(feature = $feature)]
+        impl Encrypt for super::$Mechanism {
+            fn encrypt(keystore: &mut impl Keystore, request: &request::Encrypt)
+                -> Result<reply::Encrypt, Error>
+            {
+                super::block::$encrypt::<$Cipher>(keystore, request)
+            }
+        }
+
+        #[cfg(feature = $feature)]
+        impl Decrypt for super::$Mechanism {
+            fn decrypt(keystore: &mut impl Keystore, request: &request::Decrypt)
+                -> Result<reply::Decrypt, Error>
+            {
+                super::block::$decrypt::<$Cipher>(keystore, request)
+            }
+        }
+
+        #[cfg(feature = $feature)]
+        impl GenerateKey for super::$Mechanism {
+            fn generate_key(keystore: &mut impl Keystore, request: &request::GenerateKey)
+                -> Result<reply::GenerateKey, Error>
+            {
+                super::block::generate_key::<$Cipher>(keystore, request)
+            }
+        }
+
+        #[cfg(feature = $feature)]
+        impl UnsafeInjectKey for super::$Mechanism {
+            fn unsafe_inject_key(keystore: &mut impl Keystore, request: &request::UnsafeInjectKey)
+                -> Result<reply::UnsafeInjectKey, Error>
+            {
+                super::block::unsafe_inject_key::<$Cipher>(keystore, request)
+            }
+        }
+
+        #[cfg(not(feature = $feature))]
+        impl Encrypt for super::$Mechanism {}
+        #[cfg(not(feature = $feature))]
+        impl Decrypt for super::$Mechanism {}
+        #[cfg(not(feature = $feature))]
+        impl GenerateKey for super::$Mechanism {}
+        #[cfg(not(feature = $feature))]
+        impl UnsafeInjectKey for super::$Mechanism {}
+    }
+}
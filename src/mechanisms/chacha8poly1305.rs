@@ -30,7 +30,7 @@ impl GenerateKey for super::Chacha8Poly1305 {
         let key_id = keystore.store_key(
             request.attributes.persistence,
             key::Secrecy::Secret,
-            key::Kind::Symmetric32Nonce(12),
+            key::Info::from(key::Kind::Symmetric32Nonce(12)).with_attributes(&request.attributes),
             &serialized,
         )?;
 
@@ -54,17 +54,17 @@ fn increment_nonce(nonce: &mut [u8]) -> Result<(), Error> {
 }
 
 #[cfg(feature = "chacha8-poly1305")]
-impl Decrypt for super::Chacha8Poly1305
+impl super::Chacha8Poly1305
 {
     #[inline(never)]
-    fn decrypt(keystore: &mut impl Keystore, request: &request::Decrypt)
+    pub(crate) fn decrypt_with_usage(keystore: &mut impl Keystore, request: &request::Decrypt, usage: key::Usage)
         -> Result<reply::Decrypt, Error>
     {
         use chacha20poly1305::ChaCha8Poly1305;
         use chacha20poly1305::aead::{AeadInPlace, NewAead};
 
         let serialized_material = keystore
-            .load_key(key::Secrecy::Secret, Some(key::Kind::Symmetric32Nonce(12)), &request.key)?
+            .load_key_for(key::Secrecy::Secret, Some(key::Kind::Symmetric32Nonce(12)), &request.key, request.mechanism, usage)?
             .material;
         let serialized = serialized_material.as_slice();
 
@@ -97,10 +97,20 @@ impl Decrypt for super::Chacha8Poly1305
 }
 
 #[cfg(feature = "chacha8-poly1305")]
-impl Encrypt for super::Chacha8Poly1305
+impl Decrypt for super::Chacha8Poly1305
+{
+    fn decrypt(keystore: &mut impl Keystore, request: &request::Decrypt)
+        -> Result<reply::Decrypt, Error>
+    {
+        Self::decrypt_with_usage(keystore, request, key::Usage::DECRYPT)
+    }
+}
+
+#[cfg(feature = "chacha8-poly1305")]
+impl super::Chacha8Poly1305
 {
     #[inline(never)]
-    fn encrypt(keystore: &mut impl Keystore, request: &request::Encrypt)
+    pub(crate) fn encrypt_with_usage(keystore: &mut impl Keystore, request: &request::Encrypt, usage: key::Usage)
         -> Result<reply::Encrypt, Error>
     {
         use chacha20poly1305::ChaCha8Poly1305;
@@ -112,7 +122,7 @@ impl Encrypt for super::Chacha8Poly1305
         let key_kind = key::Kind::Symmetric32Nonce(12);
         let key_id = &request.key;
         let mut serialized_material = keystore
-            .load_key(secrecy, Some(key_kind), key_id)?
+            .load_key_for(secrecy, Some(key_kind), key_id, request.mechanism, usage)?
             .material;
         let serialized: &mut [u8] = serialized_material.as_mut();
 
@@ -162,6 +172,16 @@ impl Encrypt for super::Chacha8Poly1305
     }
 }
 
+#[cfg(feature = "chacha8-poly1305")]
+impl Encrypt for super::Chacha8Poly1305
+{
+    fn encrypt(keystore: &mut impl Keystore, request: &request::Encrypt)
+        -> Result<reply::Encrypt, Error>
+    {
+        Self::encrypt_with_usage(keystore, request, key::Usage::ENCRYPT)
+    }
+}
+
 #[cfg(feature = "chacha8-poly1305")]
 impl WrapKey for super::Chacha8Poly1305
 {
@@ -173,7 +193,7 @@ impl WrapKey for super::Chacha8Poly1305
 
         // TODO: need to check both secret and private keys
         let serialized_key = keystore
-            .load_key(key::Secrecy::Secret, None, &request.key)?;
+            .load_extractable_key(&request.key)?;
 
         let message = Message::from_slice(&serialized_key.serialize()).unwrap();
 
@@ -184,7 +204,7 @@ impl WrapKey for super::Chacha8Poly1305
             associated_data: ShortData::new(),
             nonce: None,
         };
-        let encryption_reply = <super::Chacha8Poly1305>::encrypt(keystore, &encryption_request)?;
+        let encryption_reply = <super::Chacha8Poly1305>::encrypt_with_usage(keystore, &encryption_request, key::Usage::WRAP)?;
 
         let wrapped_key = crate::postcard_serialize_bytes(&encryption_reply).map_err(|_| Error::CborError)?;
 
@@ -212,21 +232,30 @@ impl UnwrapKey for super::Chacha8Poly1305
         };
 
         let serialized_key = if let Some(serialized_key) =
-            <super::Chacha8Poly1305>::decrypt(keystore, &decryption_request)?.plaintext {
+            <super::Chacha8Poly1305>::decrypt_with_usage(keystore, &decryption_request, key::Usage::UNWRAP)?.plaintext {
             serialized_key
         } else {
             return Ok(reply::UnwrapKey { key: None } );
         };
 
         // TODO: probably change this to returning Option<key> too
-        let key::Key{ flags: _, kind, material } = key::Key::try_deserialize(&serialized_key)?;
+        let key::Key{ flags, kind, policy, limits, metadata, material } = key::Key::try_deserialize(&serialized_key)?;
 
         // TODO: need to check both secret and private keys
+        // the key keeps the usage policy, limits, metadata and extractability it was wrapped with,
+        // but it is neither local nor never extractable anymore
+        let info = key::Info {
+            flags: flags & key::Flags::EXTRACTABLE,
+            kind,
+            policy,
+            limits,
+            metadata,
+        };
         let key_id = keystore.store_key(
             request.attributes.persistence,
             // using for signing keys... we need to know
             key::Secrecy::Secret,
-            kind,
+            info,
             &material,
         )?;
 
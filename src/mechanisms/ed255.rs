@@ -8,11 +8,11 @@ use crate::service::*;
 use crate::types::*;
 
     #[inline(never)]
-fn load_public_key(keystore: &mut impl Keystore, key_id: &KeyId)
+fn load_public_key(keystore: &mut impl Keystore, key_id: &KeyId, mechanism: Mechanism, usage: key::Usage)
     -> Result<salty::PublicKey, Error> {
 
     let public_bytes: [u8; 32] = keystore
-        .load_key(key::Secrecy::Public, Some(key::Kind::Ed255), &key_id)?
+        .load_key_for(key::Secrecy::Public, Some(key::Kind::Ed255), &key_id, mechanism, usage)?
         .material.as_slice()
         .try_into()
         .map_err(|_| Error::InternalError)?;
@@ -23,11 +23,11 @@ fn load_public_key(keystore: &mut impl Keystore, key_id: &KeyId)
 }
 
     #[inline(never)]
-fn load_keypair(keystore: &mut impl Keystore, key_id: &KeyId)
+fn load_keypair(keystore: &mut impl Keystore, key_id: &KeyId, mechanism: Mechanism, usage: key::Usage)
     -> Result<salty::Keypair, Error> {
 
     let seed: [u8; 32] = keystore
-        .load_key(key::Secrecy::Secret, Some(key::Kind::Ed255), &key_id)?
+        .load_key_for(key::Secrecy::Secret, Some(key::Kind::Ed255), &key_id, mechanism, usage)?
         .material.as_slice()
         .try_into()
         .map_err(|_| Error::InternalError)?;
@@ -45,12 +45,23 @@ impl DeriveKey for super::Ed255
         -> Result<reply::DeriveKey, Error>
     {
         let base_id = &request.base_key;
-        let keypair = load_keypair(keystore, base_id)?;
 
+        // the public half is addressed by the private key's `KeyId`
+        if !keystore.exists_key(key::Secrecy::Public, Some(key::Kind::Ed255), base_id) {
+            let keypair = load_keypair(keystore, base_id, request.mechanism, key::Usage::empty())?;
+
+            keystore.store_public_half(
+                request.attributes.persistence,
+                base_id,
+                keypair.public.as_bytes())?;
+        }
+
+        // the caller gets a public key of its own, which it may delete without the keypair
+        let public_key = keystore.load_key(key::Secrecy::Public, Some(key::Kind::Ed255), base_id)?;
         let public_id = keystore.store_key(
             request.attributes.persistence,
             key::Secrecy::Public, key::Kind::Ed255,
-            keypair.public.as_bytes())?;
+            &public_key.material)?;
 
         Ok(reply::DeriveKey {
             key: public_id,
@@ -83,7 +94,8 @@ impl DeserializeKey for super::Ed255
 
         let public_id = keystore.store_key(
             request.attributes.persistence,
-            key::Secrecy::Public, key::Kind::Ed255,
+            key::Secrecy::Public,
+            key::Info::from(key::Kind::Ed255).with_attributes(&request.attributes),
             public_key.as_bytes())?;
 
         Ok(reply::DeserializeKey {
@@ -102,16 +114,16 @@ impl GenerateKey for super::Ed255
         let mut seed = [0u8; 32];
         keystore.rng().fill_bytes(&mut seed);
 
-        // let keypair = salty::signature::Keypair::from(&seed);
+        let keypair = salty::signature::Keypair::from(&seed);
         // #[cfg(all(test, feature = "verbose-tests"))]
         // println!("ed255 keypair with public key = {:?}", &keypair.public);
 
         // store keys
-        let key_id = keystore.store_key(
+        let key_id = keystore.store_keypair(
             request.attributes.persistence,
-            key::Secrecy::Secret,
-            key::Info::from(key::Kind::Ed255).with_local_flag(),
-            &seed)?;
+            key::Info::from(key::Kind::Ed255).with_local_flag().with_attributes(&request.attributes),
+            &seed,
+            keypair.public.as_bytes())?;
 
         // return handle
         Ok(reply::GenerateKey { key: key_id })
@@ -126,7 +138,7 @@ impl SerializeKey for super::Ed255
         -> Result<reply::SerializeKey, Error>
     {
         let key_id = request.key;
-        let public_key = load_public_key(keystore, &key_id)?;
+        let public_key = load_public_key(keystore, &key_id, request.mechanism, key::Usage::empty())?;
 
         let serialized_key = match request.format {
             KeySerialization::Cose => {
@@ -185,7 +197,7 @@ impl Sign for super::Ed255
 
         let key_id = request.key;
 
-        let keypair = load_keypair(keystore, &key_id)?;
+        let keypair = load_keypair(keystore, &key_id, request.mechanism, key::Usage::SIGN)?;
 
         let native_signature = keypair.sign(&request.message);
         let our_signature = Signature::from_slice(&native_signature.to_bytes()).unwrap();
@@ -217,7 +229,7 @@ impl Verify for super::Ed255
         }
 
         let key_id = request.key;
-        let public_key = load_public_key(keystore, &key_id)?;
+        let public_key = load_public_key(keystore, &key_id, request.mechanism, key::Usage::VERIFY)?;
 
         let mut signature_array = [0u8; salty::constants::SIGNATURE_SERIALIZED_LENGTH];
         signature_array.copy_from_slice(request.signature.as_ref());
@@ -229,6 +241,30 @@ impl Verify for super::Ed255
     }
 }
 
+#[cfg(feature = "ed255")]
+impl UnsafeInjectKey for super::Ed255
+{
+    #[inline(never)]
+    fn unsafe_inject_key(keystore: &mut impl Keystore, request: &request::UnsafeInjectKey)
+        -> Result<reply::UnsafeInjectKey, Error>
+    {
+        // any 32 bytes are a valid seed
+        let seed: [u8; 32] = request.raw_key.as_slice().try_into()
+            .map_err(|_| Error::InvalidSerializedKey)?;
+
+        let keypair = salty::signature::Keypair::from(&seed);
+
+        // not `LOCAL`, so attestation refuses this key
+        let key_id = keystore.store_keypair(
+            request.attributes.persistence,
+            key::Info::from(key::Kind::Ed255).with_attributes(&request.attributes),
+            &seed,
+            keypair.public.as_bytes())?;
+
+        Ok(reply::UnsafeInjectKey { key: key_id })
+    }
+}
+
 #[cfg(not(feature = "ed255"))]
 impl DeriveKey for super::Ed255 {}
 #[cfg(not(feature = "ed255"))]
@@ -237,3 +273,5 @@ impl GenerateKey for super::Ed255 {}
 impl Sign for super::Ed255 {}
 #[cfg(not(feature = "ed255"))]
 impl Verify for super::Ed255 {}
+#[cfg(not(feature = "ed255"))]
+impl UnsafeInjectKey for super::Ed255 {}
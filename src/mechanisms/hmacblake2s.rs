@@ -16,7 +16,7 @@ impl DeriveKey for super::HmacBlake2s
         type HmacBlake2s = Hmac<blake2::Blake2s>;
 
         let key_id = request.base_key.object_id;
-        let shared_secret = keystore.load_key(key::Secrecy::Secret, None, &key_id)?.material;
+        let shared_secret = keystore.load_symmetric_key(&key_id, request.mechanism, key::Usage::DERIVE)?.material;
 
         let mut mac = HmacBlake2s::new_from_slice(&shared_secret.as_ref())
             .map_err(|_| Error::InternalError)?;
@@ -27,7 +27,8 @@ impl DeriveKey for super::HmacBlake2s
         let derived_key: [u8; 32] = mac.finalize().into_bytes().try_into().map_err(|_| Error::InternalError)?;
         let key_id = keystore.store_key(
             request.attributes.persistence,
-            key::Secrecy::Secret, key::Kind::Symmetric(32),
+            key::Secrecy::Secret,
+            key::Info::from(key::Kind::Symmetric(32)).with_attributes(&request.attributes),
             &derived_key)?;
 
         Ok(reply::DeriveKey { key: ObjectHandle { object_id: key_id } })
@@ -47,7 +48,7 @@ impl Sign for super::HmacBlake2s
         type HmacBlake2s = Hmac<Blake2s>;
 
         let key_id = request.key.object_id;
-        let shared_secret = keystore.load_key(key::Secrecy::Secret, None, &key_id)?.material;
+        let shared_secret = keystore.load_symmetric_key(&key_id, request.mechanism, key::Usage::SIGN)?.material;
 
         let mut mac = HmacBlake2s::new_from_slice(&shared_secret.as_ref())
             .map_err(|_| Error::InternalError)?;
@@ -61,7 +62,43 @@ impl Sign for super::HmacBlake2s
     }
 }
 
+#[cfg(feature = "hmac-blake2s")]
+impl GenerateKey for super::HmacBlake2s
+{
+    #[inline(never)]
+    fn generate_key(keystore: &mut impl Keystore, request: &request::GenerateKey)
+        -> Result<reply::GenerateKey, Error>
+    {
+        // RFC 2104: keys shorter than the hash output are discouraged
+        let mut symmetric_key = [0u8; 32];
+        keystore.rng().fill_bytes(&mut symmetric_key);
+
+        let key_id = keystore.store_key(
+            request.attributes.persistence,
+            key::Secrecy::Secret,
+            key::Info::from(key::Kind::Symmetric(32)).with_local_flag().with_attributes(&request.attributes),
+            &symmetric_key)?;
+
+        Ok(reply::GenerateKey { key: key_id })
+    }
+}
+
+#[cfg(feature = "hmac-blake2s")]
+impl UnsafeInjectKey for super::HmacBlake2s
+{
+    #[inline(never)]
+    fn unsafe_inject_key(keystore: &mut impl Keystore, request: &request::UnsafeInjectKey)
+        -> Result<reply::UnsafeInjectKey, Error>
+    {
+        super::unsafe_inject_symmetric_key(keystore, request)
+    }
+}
+
 #[cfg(not(feature = "hmac-blake2s"))]
 impl DeriveKey for super::HmacBlake2s {}
 #[cfg(not(feature = "hmac-blake2s"))]
 impl Sign for super::HmacBlake2s {}
+#[cfg(not(feature = "hmac-blake2s"))]
+impl GenerateKey for super::HmacBlake2s {}
+#[cfg(not(feature = "hmac-blake2s"))]
+impl UnsafeInjectKey for super::HmacBlake2s {}
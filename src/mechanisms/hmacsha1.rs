@@ -16,7 +16,7 @@ impl DeriveKey for super::HmacSha1
         type HmacSha1 = Hmac<sha1::Sha1>;
 
         let key_id = request.base_key;
-        let shared_secret = keystore.load_key(key::Secrecy::Secret, None, &key_id)?.material;
+        let shared_secret = keystore.load_symmetric_key(&key_id, request.mechanism, key::Usage::DERIVE)?.material;
 
         let mut mac = HmacSha1::new_from_slice(&shared_secret.as_ref())
             .map_err(|_| Error::InternalError)?;
@@ -27,7 +27,8 @@ impl DeriveKey for super::HmacSha1
         let derived_key: [u8; 20] = mac.finalize().into_bytes().try_into().map_err(|_| Error::InternalError)?;
         let key_id = keystore.store_key(
             request.attributes.persistence,
-            key::Secrecy::Secret, key::Kind::Symmetric(20),
+            key::Secrecy::Secret,
+            key::Info::from(key::Kind::Symmetric(20)).with_attributes(&request.attributes),
             &derived_key)?;
 
         Ok(reply::DeriveKey { key: key_id })
@@ -47,7 +48,7 @@ impl Sign for super::HmacSha1
         type HmacSha1 = Hmac<Sha1>;
 
         let key_id = request.key;
-        let shared_secret = keystore.load_key(key::Secrecy::Secret, None, &key_id)?.material;
+        let shared_secret = keystore.load_symmetric_key(&key_id, request.mechanism, key::Usage::SIGN)?.material;
 
         let mut mac = HmacSha1::new_from_slice(&shared_secret.as_ref())
             .map_err(|_| Error::InternalError)?;
@@ -61,7 +62,43 @@ impl Sign for super::HmacSha1
     }
 }
 
+#[cfg(feature = "hmac-sha1")]
+impl GenerateKey for super::HmacSha1
+{
+    #[inline(never)]
+    fn generate_key(keystore: &mut impl Keystore, request: &request::GenerateKey)
+        -> Result<reply::GenerateKey, Error>
+    {
+        // RFC 2104: keys shorter than the hash output are discouraged
+        let mut symmetric_key = [0u8; 20];
+        keystore.rng().fill_bytes(&mut symmetric_key);
+
+        let key_id = keystore.store_key(
+            request.attributes.persistence,
+            key::Secrecy::Secret,
+            key::Info::from(key::Kind::Symmetric(20)).with_local_flag().with_attributes(&request.attributes),
+            &symmetric_key)?;
+
+        Ok(reply::GenerateKey { key: key_id })
+    }
+}
+
+#[cfg(feature = "hmac-sha1")]
+impl UnsafeInjectKey for super::HmacSha1
+{
+    #[inline(never)]
+    fn unsafe_inject_key(keystore: &mut impl Keystore, request: &request::UnsafeInjectKey)
+        -> Result<reply::UnsafeInjectKey, Error>
+    {
+        super::unsafe_inject_symmetric_key(keystore, request)
+    }
+}
+
 #[cfg(not(feature = "hmac-sha1"))]
 impl DeriveKey for super::HmacSha1 {}
 #[cfg(not(feature = "hmac-sha1"))]
 impl Sign for super::HmacSha1 {}
+#[cfg(not(feature = "hmac-sha1"))]
+impl GenerateKey for super::HmacSha1 {}
+#[cfg(not(feature = "hmac-sha1"))]
+impl UnsafeInjectKey for super::HmacSha1 {}
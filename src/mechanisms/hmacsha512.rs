@@ -14,7 +14,7 @@ impl DeriveKey for super::HmacSha512
         type HmacSha512 = Hmac<sha2::Sha512>;
 
         let key_id = request.base_key.object_id;
-        let shared_secret = keystore.load_key(key::Secrecy::Secret, None, &key_id)?.material;
+        let shared_secret = keystore.load_symmetric_key(&key_id, request.mechanism, key::Usage::DERIVE)?.material;
 
         let mut mac = HmacSha512::new_varkey(&shared_secret.as_ref())
             .map_err(|_| Error::InternalError)?;
@@ -26,7 +26,8 @@ impl DeriveKey for super::HmacSha512
         derived_key.copy_from_slice(&mac.finalize().into_bytes());//.try_into().map_err(|_| Error::InternalError)?;
         let key_id = keystore.store_key(
             request.attributes.persistence,
-            key::Secrecy::Secret, key::Kind::Symmetric(64),
+            key::Secrecy::Secret,
+            key::Info::from(key::Kind::Symmetric(64)).with_attributes(&request.attributes),
             &derived_key)?;
 
         Ok(reply::DeriveKey { key: ObjectHandle { object_id: key_id } })
@@ -45,7 +46,7 @@ impl Sign for super::HmacSha512
         type HmacSha512 = Hmac<Sha512>;
 
         let key_id = request.key.object_id;
-        let shared_secret = keystore.load_key(key::Secrecy::Secret, None, &key_id)?.material;
+        let shared_secret = keystore.load_symmetric_key(&key_id, request.mechanism, key::Usage::SIGN)?.material;
 
         let mut mac = HmacSha512::new_varkey(&shared_secret.as_ref())
             .map_err(|_| Error::InternalError)?;
@@ -60,7 +61,43 @@ impl Sign for super::HmacSha512
 }
 
 
+#[cfg(feature = "hmac-sha512")]
+impl GenerateKey for super::HmacSha512
+{
+    #[inline(never)]
+    fn generate_key(keystore: &mut impl Keystore, request: &request::GenerateKey)
+        -> Result<reply::GenerateKey, Error>
+    {
+        // RFC 2104: keys shorter than the hash output are discouraged
+        let mut symmetric_key = [0u8; 64];
+        keystore.rng().fill_bytes(&mut symmetric_key);
+
+        let key_id = keystore.store_key(
+            request.attributes.persistence,
+            key::Secrecy::Secret,
+            key::Info::from(key::Kind::Symmetric(64)).with_local_flag().with_attributes(&request.attributes),
+            &symmetric_key)?;
+
+        Ok(reply::GenerateKey { key: key_id })
+    }
+}
+
+#[cfg(feature = "hmac-sha512")]
+impl UnsafeInjectKey for super::HmacSha512
+{
+    #[inline(never)]
+    fn unsafe_inject_key(keystore: &mut impl Keystore, request: &request::UnsafeInjectKey)
+        -> Result<reply::UnsafeInjectKey, Error>
+    {
+        super::unsafe_inject_symmetric_key(keystore, request)
+    }
+}
+
 #[cfg(not(feature = "hmac-sha512"))]
 impl DeriveKey for super::HmacSha512 {}
 #[cfg(not(feature = "hmac-sha512"))]
 impl Sign for super::HmacSha512 {}
+#[cfg(not(feature = "hmac-sha512"))]
+impl GenerateKey for super::HmacSha512 {}
+#[cfg(not(feature = "hmac-sha512"))]
+impl UnsafeInjectKey for super::HmacSha512 {}
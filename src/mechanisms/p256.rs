@@ -7,13 +7,13 @@ use crate::service::*;
 use crate::types::*;
 
 #[inline(never)]
-fn load_secret_key(keystore: &mut impl Keystore, key_id: &KeyId)
+fn load_secret_key(keystore: &mut impl Keystore, key_id: &KeyId, mechanism: Mechanism, usage: key::Usage)
     -> Result<p256_cortex_m4::SecretKey, Error>
 {
 
     // info_now!("loading keypair");
     let secret_scalar: [u8; 32] = keystore
-        .load_key(key::Secrecy::Secret, Some(key::Kind::P256), &key_id)?
+        .load_key_for(key::Secrecy::Secret, Some(key::Kind::P256), &key_id, mechanism, usage)?
         .material.as_slice()
         .try_into()
         .map_err(|_| Error::InternalError)?;
@@ -24,11 +24,11 @@ fn load_secret_key(keystore: &mut impl Keystore, key_id: &KeyId)
 }
 
 #[inline(never)]
-fn load_public_key(keystore: &mut impl Keystore, key_id: &KeyId)
+fn load_public_key(keystore: &mut impl Keystore, key_id: &KeyId, mechanism: Mechanism, usage: key::Usage)
     -> Result<p256_cortex_m4::PublicKey, Error>
 {
     let compressed_public_key: [u8; 33] = keystore
-        .load_key(key::Secrecy::Public, Some(key::Kind::P256), &key_id)?
+        .load_key_for(key::Secrecy::Public, Some(key::Kind::P256), &key_id, mechanism, usage)?
         .material.as_slice()
         .try_into()
         .map_err(|_| Error::InternalError)?;
@@ -47,14 +47,15 @@ impl Agree for super::P256
         let private_id = request.private_key;
         let public_id = request.public_key;
 
-        let secret_key = load_secret_key(keystore, &private_id)?;
-        let public_key = load_public_key(keystore, &public_id)?;
+        let secret_key = load_secret_key(keystore, &private_id, request.mechanism, key::Usage::AGREE)?;
+        let public_key = load_public_key(keystore, &public_id, request.mechanism, key::Usage::AGREE)?;
 
         let shared_secret = secret_key.agree(&public_key);
 
         let key_id = keystore.store_key(
             request.attributes.persistence,
-            key::Secrecy::Secret, key::Kind::Shared(32),
+            key::Secrecy::Secret,
+            key::Info::from(key::Kind::Shared(32)).with_attributes(&request.attributes),
             shared_secret.as_bytes())?;
 
         // return handle
@@ -71,13 +72,23 @@ impl DeriveKey for super::P256
     {
         let base_id = request.base_key;
 
-        let secret_key = load_secret_key(keystore, &base_id)?;
-        let public_key = secret_key.public_key();
+        // the public half is addressed by the private key's `KeyId`
+        if !keystore.exists_key(key::Secrecy::Public, Some(key::Kind::P256), &base_id) {
+            let secret_key = load_secret_key(keystore, &base_id, request.mechanism, key::Usage::empty())?;
+            let public_key = secret_key.public_key();
+
+            keystore.store_public_half(
+                request.attributes.persistence,
+                &base_id,
+                &public_key.to_compressed_sec1_bytes())?;
+        }
 
+        // the caller gets a public key of its own, which it may delete without the keypair
+        let public_key = keystore.load_key(key::Secrecy::Public, Some(key::Kind::P256), &base_id)?;
         let public_id = keystore.store_key(
             request.attributes.persistence,
             key::Secrecy::Public, key::Kind::P256,
-            &public_key.to_compressed_sec1_bytes())?;
+            &public_key.material)?;
 
         Ok(reply::DeriveKey {
             key: public_id
@@ -146,7 +157,8 @@ impl DeserializeKey for super::P256
 
         let public_id = keystore.store_key(
             request.attributes.persistence,
-            key::Secrecy::Public, key::Kind::P256,
+            key::Secrecy::Public,
+            key::Info::from(key::Kind::P256).with_attributes(&request.attributes),
             &public_key.to_compressed_sec1_bytes())?;
 
 
@@ -164,11 +176,11 @@ impl GenerateKey for super::P256
         let keypair = p256_cortex_m4::Keypair::random(&mut keystore.rng());
 
         // store keys
-        let key_id = keystore.store_key(
+        let key_id = keystore.store_keypair(
             request.attributes.persistence,
-            key::Secrecy::Secret,
-            key::Info::from(key::Kind::P256).with_local_flag(),
+            key::Info::from(key::Kind::P256).with_local_flag().with_attributes(&request.attributes),
             &unsafe { keypair.secret.to_bytes()  },
+            &keypair.public.to_compressed_sec1_bytes(),
         )?;
 
         // return handle
@@ -187,7 +199,7 @@ impl SerializeKey for super::P256
 
         let key_id = request.key;
 
-        let public_key = load_public_key(keystore, &key_id)?;
+        let public_key = load_public_key(keystore, &key_id, request.mechanism, key::Usage::empty())?;
 
         let serialized_key = match request.format {
             KeySerialization::EcdhEsHkdf256 => {
@@ -243,7 +255,7 @@ impl Sign for super::P256
     {
         let key_id = request.key;
 
-        let secret_key = load_secret_key(keystore, &key_id)?;
+        let secret_key = load_secret_key(keystore, &key_id, request.mechanism, key::Usage::SIGN)?;
         let signature = secret_key.sign(&request.message, keystore.rng());
 
         // debug_now!("making signature");
@@ -273,7 +285,7 @@ impl Sign for super::P256Prehashed
     {
         let key_id = request.key;
 
-        let secret_key = load_secret_key(keystore, &key_id)?;
+        let secret_key = load_secret_key(keystore, &key_id, request.mechanism, key::Usage::SIGN)?;
         let signature = secret_key.sign_prehashed(&request.message, keystore.rng());
 
         // debug_now!("making signature");
@@ -303,7 +315,7 @@ impl Verify for super::P256
     {
         let key_id = request.key;
 
-        let public_key = load_public_key(keystore, &key_id)?;
+        let public_key = load_public_key(keystore, &key_id, request.mechanism, key::Usage::VERIFY)?;
 
         let signature = p256_cortex_m4::Signature::from_untagged_bytes(&request.signature)
             // well... or wrong encoding, need r,s in range 1..=n-1
@@ -320,6 +332,34 @@ impl Verify for super::P256
     }
 }
 
+#[cfg(feature = "p256")]
+impl UnsafeInjectKey for super::P256
+{
+    #[inline(never)]
+    fn unsafe_inject_key(keystore: &mut impl Keystore, request: &request::UnsafeInjectKey)
+        -> Result<reply::UnsafeInjectKey, Error>
+    {
+        let secret_scalar: [u8; 32] = request.raw_key.as_slice().try_into()
+            .map_err(|_| Error::InvalidSerializedKey)?;
+        // scalar must be in range 1..=n-1
+        // (the non-Cortex-M4 fallback lets zero pass)
+        if secret_scalar == [0u8; 32] {
+            return Err(Error::InvalidSerializedKey);
+        }
+        let secret_key = p256_cortex_m4::SecretKey::from_bytes(&secret_scalar)
+            .map_err(|_| Error::InvalidSerializedKey)?;
+
+        // not `LOCAL`, so attestation refuses this key
+        let key_id = keystore.store_keypair(
+            request.attributes.persistence,
+            key::Info::from(key::Kind::P256).with_attributes(&request.attributes),
+            &secret_scalar,
+            &secret_key.public_key().to_compressed_sec1_bytes())?;
+
+        Ok(reply::UnsafeInjectKey { key: key_id })
+    }
+}
+
 #[cfg(not(feature = "p256"))]
 impl Agree for super::P256 {}
 #[cfg(not(feature = "p256"))]
@@ -330,3 +370,5 @@ impl GenerateKey for super::P256 {}
 impl Sign for super::P256 {}
 #[cfg(not(feature = "p256"))]
 impl Verify for super::P256 {}
+#[cfg(not(feature = "p256"))]
+impl UnsafeInjectKey for super::P256 {}
@@ -13,7 +13,7 @@ impl DeriveKey for super::Sha256
         let base_id = &request.base_key;
 
         let shared_secret = keystore
-            .load_key(key::Secrecy::Secret, None, base_id)?
+            .load_symmetric_key(base_id, request.mechanism, key::Usage::DERIVE)?
             .material;
 
         // hash it
@@ -24,7 +24,8 @@ impl DeriveKey for super::Sha256
 
         let key_id = keystore.store_key(
             request.attributes.persistence,
-            key::Secrecy::Secret, key::Kind::Symmetric(32),
+            key::Secrecy::Secret,
+            key::Info::from(key::Kind::Symmetric(32)).with_attributes(&request.attributes),
             &symmetric_key)?;
 
         Ok(reply::DeriveKey { key: key_id })
@@ -2,6 +2,9 @@
 //!
 //! We need to support 3DES to provide compatibility with Yubico's braindead
 //! implementation of key management...
+//!
+//! `Tdes` itself operates on a single block, `TdesEcb`, `TdesCbc` and `TdesCtr`
+//! follow the conventions of `super::block`. All of them use `Kind::Symmetric(24)` keys.
 
 // use cortex_m_semihosting::{dbg, hprintln};
 
@@ -17,7 +20,7 @@ use crate::types::*;
 #[cfg(feature = "tdes")]
 impl Encrypt for super::Tdes
 {
-    /// Encrypts a single block. Let's hope we don't have to support ECB!!
+    /// Encrypts a single block.
     #[inline(never)]
     fn encrypt(keystore: &mut impl Keystore, request: &request::Encrypt)
         -> Result<reply::Encrypt, Error>
@@ -27,7 +30,7 @@ impl Encrypt for super::Tdes
         let key_id = request.key;
 
         let symmetric_key: [u8; 24] = keystore
-            .load_key(key::Secrecy::Secret, None, &key_id)?
+            .load_key_for(key::Secrecy::Secret, Some(key::Kind::Symmetric(24)), &key_id, request.mechanism, key::Usage::ENCRYPT)?
             .material.as_slice().try_into()
             .map_err(|_| Error::InternalError)?;
 
@@ -43,7 +46,7 @@ impl Encrypt for super::Tdes
 #[cfg(feature = "tdes")]
 impl Decrypt for super::Tdes
 {
-    /// Decrypts a single block. Let's hope we don't have to support ECB!!
+    /// Decrypts a single block.
     #[inline(never)]
     fn decrypt(keystore: &mut impl Keystore, request: &request::Decrypt)
         -> Result<reply::Decrypt, Error>
@@ -53,7 +56,7 @@ impl Decrypt for super::Tdes
         let key_id = request.key;
 
         let symmetric_key: [u8; 24] = keystore
-            .load_key(key::Secrecy::Secret, None, &key_id)?
+            .load_key_for(key::Secrecy::Secret, Some(key::Kind::Symmetric(24)), &key_id, request.mechanism, key::Usage::DECRYPT)?
             .material.as_slice().try_into()
             .map_err(|_| Error::InternalError)?;
 
@@ -65,3 +68,38 @@ impl Decrypt for super::Tdes
         Ok(reply::Decrypt { plaintext: Some(message) })
     }
 }
+
+#[cfg(feature = "tdes")]
+impl GenerateKey for super::Tdes
+{
+    #[inline(never)]
+    fn generate_key(keystore: &mut impl Keystore, request: &request::GenerateKey)
+        -> Result<reply::GenerateKey, Error>
+    {
+        super::block::generate_key::<des::TdesEde3>(keystore, request)
+    }
+}
+
+#[cfg(feature = "tdes")]
+impl UnsafeInjectKey for super::Tdes
+{
+    #[inline(never)]
+    fn unsafe_inject_key(keystore: &mut impl Keystore, request: &request::UnsafeInjectKey)
+        -> Result<reply::UnsafeInjectKey, Error>
+    {
+        super::block::unsafe_inject_key::<des::TdesEde3>(keystore, request)
+    }
+}
+
+#[cfg(not(feature = "tdes"))]
+impl Decrypt for super::Tdes {}
+#[cfg(not(feature = "tdes"))]
+impl Encrypt for super::Tdes {}
+#[cfg(not(feature = "tdes"))]
+impl GenerateKey for super::Tdes {}
+#[cfg(not(feature = "tdes"))]
+impl UnsafeInjectKey for super::Tdes {}
+
+impl_block_mode!("tdes", des::TdesEde3, TdesEcb, ecb_encrypt, ecb_decrypt);
+impl_block_mode!("tdes", des::TdesEde3, TdesCbc, cbc_encrypt, cbc_decrypt);
+impl_block_mode!("tdes", des::TdesEde3, TdesCtr, ctr_encrypt, ctr_decrypt);
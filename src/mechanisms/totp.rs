@@ -53,7 +53,7 @@ impl Sign for super::Totp
         let key_id = request.key;
 
         let secret = keystore
-            .load_key(key::Secrecy::Secret, None, &key_id)?
+            .load_symmetric_key(&key_id, request.mechanism, key::Usage::SIGN)?
             .material;
 
         if request.message.len() != 8 {
@@ -83,6 +83,43 @@ impl Exists for super::Totp
 }
 
 
+#[cfg(feature = "totp")]
+impl GenerateKey for super::Totp
+{
+    #[inline(never)]
+    fn generate_key(keystore: &mut impl Keystore, request: &request::GenerateKey)
+        -> Result<reply::GenerateKey, Error>
+    {
+        // RFC 4226 recommends 160 bit secrets
+        let mut symmetric_key = [0u8; 20];
+        keystore.rng().fill_bytes(&mut symmetric_key);
+
+        let key_id = keystore.store_key(
+            request.attributes.persistence,
+            key::Secrecy::Secret,
+            key::Info::from(key::Kind::Symmetric(20)).with_local_flag().with_attributes(&request.attributes),
+            &symmetric_key)?;
+
+        Ok(reply::GenerateKey { key: key_id })
+    }
+}
+
+#[cfg(feature = "totp")]
+impl UnsafeInjectKey for super::Totp
+{
+    #[inline(never)]
+    fn unsafe_inject_key(keystore: &mut impl Keystore, request: &request::UnsafeInjectKey)
+        -> Result<reply::UnsafeInjectKey, Error>
+    {
+        super::unsafe_inject_symmetric_key(keystore, request)
+    }
+}
+
+#[cfg(not(feature = "totp"))]
+impl GenerateKey for super::Totp {}
+#[cfg(not(feature = "totp"))]
+impl UnsafeInjectKey for super::Totp {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -16,10 +16,12 @@ impl GenerateKey for super::Trng
         let key_id = keystore.store_key(
             request.attributes.persistence,
             key::Secrecy::Secret,
-            key::Kind::Symmetric(32),
+            key::Info::from(key::Kind::Symmetric(32)).with_local_flag().with_attributes(&request.attributes),
             &entropy)?;
 
         Ok(reply::GenerateKey { key: key_id })
     }
 }
 
+#[cfg(not(feature = "trng"))]
+impl GenerateKey for super::Trng {}
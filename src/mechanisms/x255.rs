@@ -9,11 +9,11 @@ use crate::types::*;
 
 use salty::agreement;
 
-fn load_public_key(keystore: &mut impl Keystore, key_id: &KeyId)
+fn load_public_key(keystore: &mut impl Keystore, key_id: &KeyId, mechanism: Mechanism, usage: key::Usage)
     -> Result<agreement::PublicKey, Error> {
 
     let public_bytes: [u8; 32] = keystore
-        .load_key(key::Secrecy::Public, Some(key::Kind::X255), &key_id)?
+        .load_key_for(key::Secrecy::Public, Some(key::Kind::X255), &key_id, mechanism, usage)?
         .material.as_slice()
         .try_into()
         .map_err(|_| Error::InternalError)?;
@@ -23,11 +23,11 @@ fn load_public_key(keystore: &mut impl Keystore, key_id: &KeyId)
     Ok(public_key)
 }
 
-fn load_secret_key(keystore: &mut impl Keystore, key_id: &KeyId)
+fn load_secret_key(keystore: &mut impl Keystore, key_id: &KeyId, mechanism: Mechanism, usage: key::Usage)
     -> Result<agreement::SecretKey, Error> {
 
     let seed: [u8; 32] = keystore
-        .load_key(key::Secrecy::Secret, Some(key::Kind::X255), &key_id)?
+        .load_key_for(key::Secrecy::Secret, Some(key::Kind::X255), &key_id, mechanism, usage)?
         .material.as_slice()
         .try_into()
         .map_err(|_| Error::InternalError)?;
@@ -46,18 +46,23 @@ impl Agree for super::X255
         let secret_key = load_secret_key(
             keystore,
             &request.private_key,
+            request.mechanism,
+            key::Usage::AGREE,
         )?;
 
         let public_key = load_public_key(
             keystore,
             &request.public_key,
+            request.mechanism,
+            key::Usage::AGREE,
         )?;
 
         let shared_secret = secret_key.agree(&public_key).to_bytes();
 
         let key_id = keystore.store_key(
             request.attributes.persistence,
-            key::Secrecy::Secret, key::Kind::Shared(32),
+            key::Secrecy::Secret,
+            key::Info::from(key::Kind::Shared(32)).with_attributes(&request.attributes),
             &shared_secret)?;
 
         // return handle
@@ -76,12 +81,14 @@ impl GenerateKey for super::X255
         let mut seed = [0u8; 32];
         keystore.rng().fill_bytes(&mut seed);
 
+        let public_key = agreement::PublicKey::from(&agreement::SecretKey::from_seed(&seed));
+
         // store keys
-        let key_id = keystore.store_key(
+        let key_id = keystore.store_keypair(
             request.attributes.persistence,
-            key::Secrecy::Secret,
-            key::Info::from(key::Kind::X255).with_local_flag(),
-            &seed)?;
+            key::Info::from(key::Kind::X255).with_local_flag().with_attributes(&request.attributes),
+            &seed,
+            &public_key.to_bytes())?;
 
         // return handle
         Ok(reply::GenerateKey { key: key_id })
@@ -110,14 +117,23 @@ impl DeriveKey for super::X255
     {
         let base_id = request.base_key;
 
-        let secret_key = load_secret_key(keystore, &base_id)?;
-        let public_key = agreement::PublicKey::from(&secret_key);
+        // the public half is addressed by the private key's `KeyId`
+        if !keystore.exists_key(key::Secrecy::Public, Some(key::Kind::X255), &base_id) {
+            let secret_key = load_secret_key(keystore, &base_id, request.mechanism, key::Usage::empty())?;
+            let public_key = agreement::PublicKey::from(&secret_key);
 
-        let public_key_bytes = public_key.to_bytes();
+            keystore.store_public_half(
+                request.attributes.persistence,
+                &base_id,
+                &public_key.to_bytes())?;
+        }
+
+        // the caller gets a public key of its own, which it may delete without the keypair
+        let public_key = keystore.load_key(key::Secrecy::Public, Some(key::Kind::X255), &base_id)?;
         let public_id = keystore.store_key(
             request.attributes.persistence,
             key::Secrecy::Public, key::Kind::X255,
-            &public_key_bytes)?;
+            &public_key.material)?;
 
         Ok(reply::DeriveKey { key: public_id })
     }
@@ -131,7 +147,7 @@ impl SerializeKey for super::X255
         -> Result<reply::SerializeKey, Error>
     {
         let key_id = request.key;
-        let public_key = load_public_key(keystore, &key_id)?;
+        let public_key = load_public_key(keystore, &key_id, request.mechanism, key::Usage::empty())?;
 
         let mut serialized_key = Message::new();
         match request.format {
@@ -171,7 +187,8 @@ impl DeserializeKey for super::X255
 
         let public_id = keystore.store_key(
             request.attributes.persistence,
-            key::Secrecy::Public, key::Kind::X255,
+            key::Secrecy::Public,
+            key::Info::from(key::Kind::X255).with_attributes(&request.attributes),
             &public_key.to_bytes())?;
 
         Ok(reply::DeserializeKey { key: public_id })
@@ -179,6 +196,30 @@ impl DeserializeKey for super::X255
 }
 
 
+#[cfg(feature = "x255")]
+impl UnsafeInjectKey for super::X255
+{
+    #[inline(never)]
+    fn unsafe_inject_key(keystore: &mut impl Keystore, request: &request::UnsafeInjectKey)
+        -> Result<reply::UnsafeInjectKey, Error>
+    {
+        // any 32 bytes are a valid seed, clamping happens on use
+        let seed: [u8; 32] = request.raw_key.as_slice().try_into()
+            .map_err(|_| Error::InvalidSerializedKey)?;
+
+        let public_key = agreement::PublicKey::from(&agreement::SecretKey::from_seed(&seed));
+
+        // not `LOCAL`, so attestation refuses this key
+        let key_id = keystore.store_keypair(
+            request.attributes.persistence,
+            key::Info::from(key::Kind::X255).with_attributes(&request.attributes),
+            &seed,
+            &public_key.to_bytes())?;
+
+        Ok(reply::UnsafeInjectKey { key: key_id })
+    }
+}
+
 #[cfg(not(feature = "x255"))]
 impl Agree for super::X255 {}
 #[cfg(not(feature = "x255"))]
@@ -191,3 +232,5 @@ impl Derive for super::X255 {}
 impl SerializeKey for super::X255 {}
 #[cfg(not(feature = "x255"))]
 impl DeserializeKey for super::X255 {}
+#[cfg(not(feature = "x255"))]
+impl UnsafeInjectKey for super::X255 {}
@@ -75,6 +75,10 @@ pub struct ServiceEndpoint {
     // service (trusted) has this, not client (untrusted)
     // used among other things to namespace cryptographic material
     pub client_id: ClientId,
+    // may provision the attestation keys and certificates
+    pub provisioner: bool,
+    // may sign with the DICE Alias key
+    pub dice_signer: bool,
 }
 
 // pub type ClientEndpoint = Requester<TrussedInterchange>;
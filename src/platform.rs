@@ -33,6 +33,11 @@ pub trait UserInterface {
         Default::default()
     }
 
+    /// Return the duration since the UNIX epoch, if the platform has a real-time clock.
+    fn time(&mut self) -> Option<core::time::Duration> {
+        None
+    }
+
     /// Exit / reset the application
     fn reboot (&mut self, to: reboot::To) -> ! {
         let _ = to;
@@ -48,6 +53,18 @@ pub trait UserInterface {
     }
 }
 
+/// What the platform measured at boot, for DICE.
+///
+/// The unique device secret itself must never reach the firmware: the boot ROM, or a lower
+/// layer, measures the firmware and only passes on the compound device identifier.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DiceInputs {
+    /// The layer-0 CDI, the HMAC-SHA256 of the measurement keyed with the unique device secret.
+    pub compound_device_identifier: [u8; 32],
+    /// The SHA-256 hash of the firmware running Trussed.
+    pub measurement: [u8; 32],
+}
+
 // This is the same trick as in "store.rs",
 // replacing generic parameters with associated types
 // and a macro.
@@ -60,6 +77,22 @@ pub unsafe trait Platform {
     fn rng(&mut self) -> &mut Self::R;
     fn store(&self) -> Self::S;
     fn user_interface(&mut self) -> &mut Self::UI;
+
+    /// Return the key under which key material is encrypted at rest, if any.
+    ///
+    /// Should be derived from a hardware unique key or fuses, and must stay the same
+    /// across boots, or stored keys become unreadable. Keys stored before there was one are
+    /// refused, until `Service::encrypt_stored_keys` encrypts them.
+    fn key_encryption_key(&mut self) -> Option<[u8; 32]> {
+        None
+    }
+
+    /// Return the inputs of the DICE identity of the device, if the platform supports DICE.
+    ///
+    /// The same device and firmware must always give the same inputs.
+    fn dice_inputs(&mut self) -> Option<DiceInputs> {
+        None
+    }
 }
 
 #[macro_export]
@@ -77,11 +110,25 @@ macro_rules! platform { (
         rng: $Rng,
         store: $Store,
         user_interface: $UserInterface,
+        key_encryption_key: Option<[u8; 32]>,
+        dice_inputs: Option<$crate::platform::DiceInputs>,
     }
 
     impl $PlatformName {
         pub fn new(rng: $Rng, store: $Store, user_interface: $UserInterface) -> Self {
-            Self { rng, store, user_interface }
+            Self { rng, store, user_interface, key_encryption_key: None, dice_inputs: None }
+        }
+
+        /// Encrypt key material at rest under this key.
+        pub fn with_key_encryption_key(mut self, key_encryption_key: [u8; 32]) -> Self {
+            self.key_encryption_key = Some(key_encryption_key);
+            self
+        }
+
+        /// Derive the DICE identity from these inputs.
+        pub fn with_dice_inputs(mut self, dice_inputs: $crate::platform::DiceInputs) -> Self {
+            self.dice_inputs = Some(dice_inputs);
+            self
         }
     }
 
@@ -101,6 +148,14 @@ macro_rules! platform { (
         fn store(&self) -> Self::S {
             self.store
         }
+
+        fn key_encryption_key(&mut self) -> Option<[u8; 32]> {
+            self.key_encryption_key
+        }
+
+        fn dice_inputs(&mut self) -> Option<$crate::platform::DiceInputs> {
+            self.dice_inputs
+        }
     }
 }}
 
@@ -16,6 +16,7 @@ pub use crate::store::{
     filestore::{ClientFilestore, Filestore, ReadDirState, ReadDirFilesState},
     certstore::{ClientCertstore, Certstore as _},
     counterstore::{ClientCounterstore, Counterstore as _},
+    pinstore::{ClientPinstore, Pinstore as _},
     keystore::{ClientKeystore, Keystore},
 };
 use crate::types::*;
@@ -23,6 +24,10 @@ pub use crate::pipe::ServiceEndpoint;
 
 
 pub mod attest;
+pub mod backup;
+pub mod dice;
+pub mod provision;
+pub mod shamir;
 
 // #[macro_use]
 // mod macros;
@@ -95,11 +100,14 @@ impl<P: Platform> ServiceResources<P> {
         let full_store = self.platform.store();
 
         // prepare keystore, bound to client_id, for cryptographic calls
+        let time = self.platform.user_interface().time().map(|time| time.as_secs());
+        let uptime = self.platform.user_interface().uptime().as_secs();
+        let key_encryption_key = self.platform.key_encryption_key();
         let mut keystore: ClientKeystore<P> = ClientKeystore::new(
             client_id.clone(),
             self.rng().map_err(|_| Error::EntropyMalfunction)?,
             full_store,
-        );
+        ).with_time(time).with_uptime(uptime).with_key_encryption_key(key_encryption_key);
         let keystore = &mut keystore;
 
         // prepare certstore, bound to client_id, for cert calls
@@ -118,9 +126,17 @@ impl<P: Platform> ServiceResources<P> {
         );
         let counterstore = &mut counterstore;
 
+        // prepare pinstore, bound to client_id, for PIN calls
+        let mut pinstore: ClientPinstore<P::S> = ClientPinstore::new(
+            client_id.clone(),
+            self.rng().map_err(|_| Error::EntropyMalfunction)?,
+            full_store,
+        ).with_uptime(uptime).with_key_encryption_key(key_encryption_key);
+        let pinstore = &mut pinstore;
+
         // prepare filestore, bound to client_id, for storage calls
         let mut filestore: ClientFilestore<P::S> = ClientFilestore::new(
-            client_id,
+            client_id.clone(),
             full_store,
         );
         let filestore = &mut filestore;
@@ -141,20 +157,35 @@ impl<P: Platform> ServiceResources<P> {
             },
 
             Request::Attest(request) => {
-                let mut attn_keystore: ClientKeystore<P> = ClientKeystore::new(
-                    PathBuf::from("attn"),
-                    self.rng().map_err(|_| Error::EntropyMalfunction)?,
-                    full_store,
-                );
-                attest::try_attest(&mut attn_keystore, certstore, keystore, request).map(Reply::Attest)
+                let mut attn_keystore = self.attestation_keystore()?;
+                let chain = provision::attestation_chain(full_store, request.signing_mechanism);
+                attest::try_attest(&mut attn_keystore, chain.as_ref(), certstore, keystore, request).map(Reply::Attest)
+            }
+
+            Request::AttestationStatement(request) => {
+                let mut attn_keystore = self.attestation_keystore()?;
+                let chain = provision::attestation_chain(full_store, request.signing_mechanism);
+                attest::try_attestation_statement(&mut attn_keystore, chain.as_ref(), request)
+                    .map(Reply::AttestationStatement)
             }
 
             Request::Decrypt(request) => {
                 match request.mechanism {
 
+                    Mechanism::Aes128Cbc => mechanisms::Aes128Cbc::decrypt(keystore, request),
+                    Mechanism::Aes128Ctr => mechanisms::Aes128Ctr::decrypt(keystore, request),
+                    Mechanism::Aes128Ecb => mechanisms::Aes128Ecb::decrypt(keystore, request),
+                    Mechanism::Aes192Cbc => mechanisms::Aes192Cbc::decrypt(keystore, request),
+                    Mechanism::Aes192Ctr => mechanisms::Aes192Ctr::decrypt(keystore, request),
+                    Mechanism::Aes192Ecb => mechanisms::Aes192Ecb::decrypt(keystore, request),
                     Mechanism::Aes256Cbc => mechanisms::Aes256Cbc::decrypt(keystore, request),
+                    Mechanism::Aes256Ctr => mechanisms::Aes256Ctr::decrypt(keystore, request),
+                    Mechanism::Aes256Ecb => mechanisms::Aes256Ecb::decrypt(keystore, request),
                     Mechanism::Chacha8Poly1305 => mechanisms::Chacha8Poly1305::decrypt(keystore, request),
                     Mechanism::Tdes => mechanisms::Tdes::decrypt(keystore, request),
+                    Mechanism::TdesCbc => mechanisms::TdesCbc::decrypt(keystore, request),
+                    Mechanism::TdesCtr => mechanisms::TdesCtr::decrypt(keystore, request),
+                    Mechanism::TdesEcb => mechanisms::TdesEcb::decrypt(keystore, request),
                     _ => Err(Error::MechanismNotAvailable),
 
                 }.map(Reply::Decrypt)
@@ -190,14 +221,30 @@ impl<P: Platform> ServiceResources<P> {
             Request::Encrypt(request) => {
                 match request.mechanism {
 
+                    Mechanism::Aes128Cbc => mechanisms::Aes128Cbc::encrypt(keystore, request),
+                    Mechanism::Aes128Ctr => mechanisms::Aes128Ctr::encrypt(keystore, request),
+                    Mechanism::Aes128Ecb => mechanisms::Aes128Ecb::encrypt(keystore, request),
+                    Mechanism::Aes192Cbc => mechanisms::Aes192Cbc::encrypt(keystore, request),
+                    Mechanism::Aes192Ctr => mechanisms::Aes192Ctr::encrypt(keystore, request),
+                    Mechanism::Aes192Ecb => mechanisms::Aes192Ecb::encrypt(keystore, request),
                     Mechanism::Aes256Cbc => mechanisms::Aes256Cbc::encrypt(keystore, request),
+                    Mechanism::Aes256Ctr => mechanisms::Aes256Ctr::encrypt(keystore, request),
+                    Mechanism::Aes256Ecb => mechanisms::Aes256Ecb::encrypt(keystore, request),
                     Mechanism::Chacha8Poly1305 => mechanisms::Chacha8Poly1305::encrypt(keystore, request),
                     Mechanism::Tdes => mechanisms::Tdes::encrypt(keystore, request),
+                    Mechanism::TdesCbc => mechanisms::TdesCbc::encrypt(keystore, request),
+                    Mechanism::TdesCtr => mechanisms::TdesCtr::encrypt(keystore, request),
+                    Mechanism::TdesEcb => mechanisms::TdesEcb::encrypt(keystore, request),
                     _ => Err(Error::MechanismNotAvailable),
 
                 }.map(Reply::Encrypt)
             },
 
+            Request::ChangeKeyLocation(request) => {
+                keystore.change_location(&request.key, request.location)?;
+                Ok(Reply::ChangeKeyLocation(reply::ChangeKeyLocation {} ))
+            },
+
             Request::Delete(request) => {
                 let success = keystore.delete_key(&request.key);
                 Ok(Reply::Delete(reply::Delete { success } ))
@@ -208,6 +255,11 @@ impl<P: Platform> ServiceResources<P> {
                 Ok(Reply::DeleteAllKeys(reply::DeleteAllKeys { count } ))
             },
 
+            Request::DeletePublicKey(request) => {
+                let success = keystore.delete_public_key(&request.key);
+                Ok(Reply::DeletePublicKey(reply::DeletePublicKey { success } ))
+            },
+
             Request::Exists(request) => {
                 match request.mechanism {
 
@@ -222,14 +274,68 @@ impl<P: Platform> ServiceResources<P> {
 
             Request::GenerateKey(request) => {
                 match request.mechanism {
+                    Mechanism::Aes128Cbc => mechanisms::Aes128Cbc::generate_key(keystore, request),
+                    Mechanism::Aes128Ctr => mechanisms::Aes128Ctr::generate_key(keystore, request),
+                    Mechanism::Aes128Ecb => mechanisms::Aes128Ecb::generate_key(keystore, request),
+                    Mechanism::Aes192Cbc => mechanisms::Aes192Cbc::generate_key(keystore, request),
+                    Mechanism::Aes192Ctr => mechanisms::Aes192Ctr::generate_key(keystore, request),
+                    Mechanism::Aes192Ecb => mechanisms::Aes192Ecb::generate_key(keystore, request),
+                    Mechanism::Aes256Cbc => mechanisms::Aes256Cbc::generate_key(keystore, request),
+                    Mechanism::Aes256Ctr => mechanisms::Aes256Ctr::generate_key(keystore, request),
+                    Mechanism::Aes256Ecb => mechanisms::Aes256Ecb::generate_key(keystore, request),
                     Mechanism::Chacha8Poly1305 => mechanisms::Chacha8Poly1305::generate_key(keystore, request),
                     Mechanism::Ed255 => mechanisms::Ed255::generate_key(keystore, request),
+                    Mechanism::HmacBlake2s => mechanisms::HmacBlake2s::generate_key(keystore, request),
+                    Mechanism::HmacSha1 => mechanisms::HmacSha1::generate_key(keystore, request),
+                    Mechanism::HmacSha256 => mechanisms::HmacSha256::generate_key(keystore, request),
+                    Mechanism::HmacSha512 => mechanisms::HmacSha512::generate_key(keystore, request),
                     Mechanism::P256 => mechanisms::P256::generate_key(keystore, request),
+                    Mechanism::Tdes => mechanisms::Tdes::generate_key(keystore, request),
+                    Mechanism::TdesCbc => mechanisms::TdesCbc::generate_key(keystore, request),
+                    Mechanism::TdesCtr => mechanisms::TdesCtr::generate_key(keystore, request),
+                    Mechanism::TdesEcb => mechanisms::TdesEcb::generate_key(keystore, request),
+                    Mechanism::Totp => mechanisms::Totp::generate_key(keystore, request),
+                    Mechanism::Trng => mechanisms::Trng::generate_key(keystore, request),
                     Mechanism::X255 => mechanisms::X255::generate_key(keystore, request),
                     _ => Err(Error::MechanismNotAvailable),
                 }.map(Reply::GenerateKey)
             },
 
+            Request::FindObjects(request) => {
+                let (objects, num_objects) = keystore.find_keys(
+                    request.secrecy,
+                    request.kind,
+                    request.location,
+                    request.label.as_ref().map(|label| &label[..]),
+                    request.after.as_ref(),
+                )?;
+                Ok(Reply::FindObjects(reply::FindObjects { objects, num_objects }))
+            },
+
+            Request::ListKeys(request) => {
+                let (keys, num_keys) = keystore.list_keys(request.kind, request.location, request.after.as_ref())?;
+                Ok(Reply::ListKeys(reply::ListKeys { keys, num_keys }))
+            },
+
+            Request::GetKeyInfo(request) => {
+                // for keypairs, this describes the private half
+                let secrecy = [key::Secrecy::Secret, key::Secrecy::Public].iter().copied()
+                    .find(|secrecy| keystore.location(*secrecy, &request.key).is_some())
+                    .ok_or(Error::NoSuchKey)?;
+                let location = keystore.location(secrecy, &request.key).ok_or(Error::NoSuchKey)?;
+                let info = keystore.key_info(secrecy, &request.key).ok_or(Error::NoSuchKey)?;
+                Ok(Reply::GetKeyInfo(reply::GetKeyInfo {
+                    secrecy,
+                    kind: info.kind,
+                    flags: info.flags,
+                    location,
+                    size: info.kind.size(),
+                    policy: info.policy,
+                    limits: info.limits,
+                    metadata: info.metadata,
+                }))
+            },
+
             Request::GenerateSecretKey(request) => {
                 let mut secret_key = MediumData::new();
                 let size = request.size;
@@ -238,15 +344,29 @@ impl<P: Platform> ServiceResources<P> {
                 let key_id = keystore.store_key(
                     request.attributes.persistence,
                     key::Secrecy::Secret,
-                    key::Kind::Symmetric(size),
+                    key::Info::from(key::Kind::Symmetric(size)).with_attributes(&request.attributes),
                     &secret_key[..size],
                 )?;
                 Ok(Reply::GenerateSecretKey(reply::GenerateSecretKey { key: key_id }))
             },
 
-            // deprecated
-            Request::UnsafeInjectKey(_request) => {
-                Err(Error::MechanismNotAvailable)
+            Request::UnsafeInjectKey(request) => {
+                unsafe_inject_key(keystore, request).map(Reply::UnsafeInjectKey)
+            },
+
+            Request::SplitKey(request) => {
+                shamir::split_key(keystore, request).map(Reply::SplitKey)
+            },
+
+            Request::CombineKey(request) => {
+                let secret = shamir::combine_shares(request)?;
+                let request = request::UnsafeInjectKey {
+                    mechanism: request.mechanism,
+                    raw_key: ShortData::from_slice(&secret).unwrap(),
+                    attributes: request.attributes.clone(),
+                };
+                unsafe_inject_key(keystore, &request)
+                    .map(|reply| Reply::CombineKey(reply::CombineKey { key: reply.key }))
             },
 
             Request::UnsafeInjectSharedKey(request) => {
@@ -536,21 +656,139 @@ impl<P: Platform> ServiceResources<P> {
                     .map(|id| Reply::CreateCounter(reply::CreateCounter { id } ))
             }
 
+            Request::DeleteCounter(request) => {
+                counterstore.delete(request.id)
+                    .map(|_| Reply::DeleteCounter(reply::DeleteCounter {} ))
+            }
+
             Request::IncrementCounter(request) => {
-                counterstore.increment(request.id)
+                counterstore.increment_by(request.id, request.step)
                     .map(|counter| Reply::IncrementCounter(reply::IncrementCounter { counter } ))
             }
 
+            Request::ListCounters(request) => {
+                counterstore.list(request.location, request.after)
+                    .map(|(counters, num_counters)| Reply::ListCounters(reply::ListCounters {
+                        counters, num_counters,
+                    }))
+            }
+
+            Request::ReadCounter(request) => {
+                counterstore.read(request.id)
+                    .map(|counter| Reply::ReadCounter(reply::ReadCounter { counter } ))
+            }
+
+            Request::SetCounterAtLeast(request) => {
+                counterstore.set_at_least(request.id, request.value)
+                    .map(|counter| Reply::SetCounterAtLeast(reply::SetCounterAtLeast { counter } ))
+            }
+
+            Request::SetPin(request) => {
+                let puk = request.puk.as_ref().map(|(puk, retries)| (&puk[..], *retries));
+                pinstore.set_pin(request.pin, &request.value, request.retries, puk)
+                    .map(|_| Reply::SetPin(reply::SetPin {} ))
+            }
+
+            Request::VerifyPin(request) => {
+                pinstore.verify_pin(request.pin, &request.value)
+                    .map(|check| Reply::VerifyPin(reply::VerifyPin { success: check.success, retries_left: check.retries_left } ))
+            }
+
+            Request::ChangePin(request) => {
+                pinstore.change_pin(request.pin, &request.old_value, &request.new_value)
+                    .map(|check| Reply::ChangePin(reply::ChangePin { success: check.success, retries_left: check.retries_left } ))
+            }
+
+            Request::ResetPin(request) => {
+                pinstore.reset_pin(request.pin, &request.puk, &request.new_value)
+                    .map(|check| Reply::ResetPin(reply::ResetPin { success: check.success, retries_left: check.retries_left } ))
+            }
+
+            Request::PinRetries(request) => {
+                pinstore.pin_retries(request.pin)
+                    .map(|(retries_left, puk_retries_left)| Reply::PinRetries(reply::PinRetries { retries_left, puk_retries_left } ))
+            }
+
+            Request::DeletePin(request) => {
+                // the keys go first, so no new PIN can be set for them
+                if pinstore.pin_blocked_for_good(request.pin)? {
+                    keystore.delete_keys_bound_to(request.pin)?;
+                }
+                pinstore.delete_pin(request.pin, &request.value)
+                    .map(|check| Reply::DeletePin(reply::DeletePin { success: check.success, retries_left: check.retries_left } ))
+            }
+
+            Request::Backup(request) => {
+                backup::backup(keystore, full_store, &client_id, request).map(Reply::Backup)
+            }
+
+            Request::Restore(request) => {
+                backup::restore(keystore, full_store, &client_id, request).map(Reply::Restore)
+            }
+
+            Request::InstallAttestationCertificates(request) => {
+                let mut attn_keystore = self.attestation_keystore()?;
+                provision::install_attestation_certificates(&mut attn_keystore, full_store, request)
+                    .map(Reply::InstallAttestationCertificates)
+            }
+
+            Request::LockProvisioning(_) => {
+                provision::lock_provisioning(full_store).map(Reply::LockProvisioning)
+            }
+
+            Request::ProvisionAttestationKey(request) => {
+                let mut attn_keystore = self.attestation_keystore()?;
+                provision::provision_attestation_key(&mut attn_keystore, full_store, request)
+                    .map(Reply::ProvisionAttestationKey)
+            }
+
+            Request::DiceCertificates(_) => {
+                let inputs = self.platform.dice_inputs().ok_or(Error::RequestNotAvailable)?;
+                let mut dice_keystore = self.dice_keystore()?;
+                dice::certificates(&mut dice_keystore, &inputs).map(Reply::DiceCertificates)
+            }
+
+            Request::DiceSign(request) => {
+                let inputs = self.platform.dice_inputs().ok_or(Error::RequestNotAvailable)?;
+                let mut dice_keystore = self.dice_keystore()?;
+                dice::sign(&mut dice_keystore, &inputs, request).map(Reply::DiceSign)
+            }
+
             Request::DeleteCertificate(request) => {
                 certstore.delete_certificate(request.id)
                     .map(|_| Reply::DeleteCertificate(reply::DeleteCertificate {} ))
 
             }
 
+            Request::GenerateCsr(request) => {
+                attest::try_generate_csr(keystore, request).map(Reply::GenerateCsr)
+            }
+
+            Request::IssueCertificate(request) => {
+                attest::try_issue_certificate(keystore, certstore, request).map(Reply::IssueCertificate)
+            }
+
+            Request::ListCertificates(request) => {
+                certstore.list_certificates(&request.filter, request.after)
+                    .map(|(certificates, num_certificates)| Reply::ListCertificates(reply::ListCertificates {
+                        certificates, num_certificates,
+                    }))
+            }
+
             Request::ReadCertificate(request) => {
-                certstore.read_certificate(request.id)
-                    .map(|der| Reply::ReadCertificate(reply::ReadCertificate { der } ))
+                let der = certstore.read_certificate(request.id)?;
+                let attributes = certstore.certificate_attributes(request.id)?;
+                Ok(Reply::ReadCertificate(reply::ReadCertificate { der, attributes } ))
+            }
 
+            Request::SetCertificateAttributes(request) => {
+                certstore.set_certificate_attributes(request.id, &request.attributes)
+                    .map(|_| Reply::SetCertificateAttributes(reply::SetCertificateAttributes {} ))
+            }
+
+            Request::VerifyCertificateChain(request) => {
+                let time = request.time.or_else(|| time.map(DateTime::from_unix_time));
+                attest::try_verify_certificate_chain(certstore, request, time).map(Reply::VerifyCertificateChain)
             }
 
             Request::WriteCertificate(request) => {
@@ -566,6 +804,26 @@ impl<P: Platform> ServiceResources<P> {
         }
     }
 
+    /// The keystore of the attestation keys, which are shared by all clients.
+    fn attestation_keystore(&mut self) -> Result<ClientKeystore<P>, Error> {
+        let key_encryption_key = self.platform.key_encryption_key();
+        Ok(ClientKeystore::new(
+            PathBuf::from(provision::ATTESTATION_CLIENT_ID),
+            self.rng().map_err(|_| Error::EntropyMalfunction)?,
+            self.platform.store(),
+        ).with_key_encryption_key(key_encryption_key))
+    }
+
+    /// The keystore of the DICE keys, which are derived from the platform's DICE inputs.
+    fn dice_keystore(&mut self) -> Result<ClientKeystore<P>, Error> {
+        let key_encryption_key = self.platform.key_encryption_key();
+        Ok(ClientKeystore::new(
+            PathBuf::from(dice::DICE_CLIENT_ID),
+            self.rng().map_err(|_| Error::EntropyMalfunction)?,
+            self.platform.store(),
+        ).with_key_encryption_key(key_encryption_key))
+    }
+
     /// Applies a splitting aka forking construction to the inner DRBG,
     /// returning an independent DRBG.
     pub fn rng(&mut self) -> Result<ChaCha8Rng, Error> {
@@ -644,6 +902,36 @@ impl<P: Platform> ServiceResources<P> {
 
 }
 
+/// Stores a raw key for the mechanism, also used to store keys combined from shares.
+fn unsafe_inject_key(keystore: &mut impl Keystore, request: &request::UnsafeInjectKey)
+    -> Result<reply::UnsafeInjectKey, Error>
+{
+    match request.mechanism {
+        Mechanism::Aes128Cbc => mechanisms::Aes128Cbc::unsafe_inject_key(keystore, request),
+        Mechanism::Aes128Ctr => mechanisms::Aes128Ctr::unsafe_inject_key(keystore, request),
+        Mechanism::Aes128Ecb => mechanisms::Aes128Ecb::unsafe_inject_key(keystore, request),
+        Mechanism::Aes192Cbc => mechanisms::Aes192Cbc::unsafe_inject_key(keystore, request),
+        Mechanism::Aes192Ctr => mechanisms::Aes192Ctr::unsafe_inject_key(keystore, request),
+        Mechanism::Aes192Ecb => mechanisms::Aes192Ecb::unsafe_inject_key(keystore, request),
+        Mechanism::Aes256Cbc => mechanisms::Aes256Cbc::unsafe_inject_key(keystore, request),
+        Mechanism::Aes256Ctr => mechanisms::Aes256Ctr::unsafe_inject_key(keystore, request),
+        Mechanism::Aes256Ecb => mechanisms::Aes256Ecb::unsafe_inject_key(keystore, request),
+        Mechanism::Ed255 => mechanisms::Ed255::unsafe_inject_key(keystore, request),
+        Mechanism::HmacBlake2s => mechanisms::HmacBlake2s::unsafe_inject_key(keystore, request),
+        Mechanism::HmacSha1 => mechanisms::HmacSha1::unsafe_inject_key(keystore, request),
+        Mechanism::HmacSha256 => mechanisms::HmacSha256::unsafe_inject_key(keystore, request),
+        Mechanism::HmacSha512 => mechanisms::HmacSha512::unsafe_inject_key(keystore, request),
+        Mechanism::P256 => mechanisms::P256::unsafe_inject_key(keystore, request),
+        Mechanism::Tdes => mechanisms::Tdes::unsafe_inject_key(keystore, request),
+        Mechanism::TdesCbc => mechanisms::TdesCbc::unsafe_inject_key(keystore, request),
+        Mechanism::TdesCtr => mechanisms::TdesCtr::unsafe_inject_key(keystore, request),
+        Mechanism::TdesEcb => mechanisms::TdesEcb::unsafe_inject_key(keystore, request),
+        Mechanism::Totp => mechanisms::Totp::unsafe_inject_key(keystore, request),
+        Mechanism::X255 => mechanisms::X255::unsafe_inject_key(keystore, request),
+        _ => Err(Error::MechanismNotAvailable),
+    }
+}
+
 impl<P: Platform> Service<P> {
 
     pub fn new(platform: P) -> Self {
@@ -680,12 +968,94 @@ impl<P: Platform> Service<P> {
         Ok(crate::client::ClientImplementation::new(requester, self))
     }
 
+    /// Like `try_new_client`, for a client that may also provision the attestation keys
+    /// and certificates.
+    #[allow(clippy::result_unit_err)]
+    pub fn try_new_provisioner<S: crate::platform::Syscall>(&mut self, client_id: &str, syscall: S)
+        -> Result<crate::client::ClientImplementation<S>, ()>
+    {
+        use interchange::Interchange;
+        let (requester, responder) = TrussedInterchange::claim().ok_or(())?;
+        let client_id = ClientId::from(client_id.as_bytes());
+        self.add_provisioner_endpoint(responder, client_id).map_err(|_service_endpoint| ())?;
+
+        Ok(crate::client::ClientImplementation::new(requester, syscall))
+    }
+
+    /// Like `try_as_new_client`, for a client that may also provision the attestation keys
+    /// and certificates.
+    #[allow(clippy::result_unit_err)]
+    pub fn try_as_new_provisioner(&mut self, client_id: &str)
+        -> Result<crate::client::ClientImplementation<&mut Service<P>>, ()>
+    {
+        use interchange::Interchange;
+        let (requester, responder) = TrussedInterchange::claim().ok_or(())?;
+        let client_id = ClientId::from(client_id.as_bytes());
+        self.add_provisioner_endpoint(responder, client_id).map_err(|_service_endpoint| ())?;
+
+        Ok(crate::client::ClientImplementation::new(requester, self))
+    }
+
+    /// Like `try_new_client`, for a client that may also sign with the DICE Alias key.
+    #[allow(clippy::result_unit_err)]
+    pub fn try_new_dice_signer<S: crate::platform::Syscall>(&mut self, client_id: &str, syscall: S)
+        -> Result<crate::client::ClientImplementation<S>, ()>
+    {
+        use interchange::Interchange;
+        let (requester, responder) = TrussedInterchange::claim().ok_or(())?;
+        let client_id = ClientId::from(client_id.as_bytes());
+        self.add_dice_signer_endpoint(responder, client_id).map_err(|_service_endpoint| ())?;
+
+        Ok(crate::client::ClientImplementation::new(requester, syscall))
+    }
+
+    /// Like `try_as_new_client`, for a client that may also sign with the DICE Alias key.
+    #[allow(clippy::result_unit_err)]
+    pub fn try_as_new_dice_signer(&mut self, client_id: &str)
+        -> Result<crate::client::ClientImplementation<&mut Service<P>>, ()>
+    {
+        use interchange::Interchange;
+        let (requester, responder) = TrussedInterchange::claim().ok_or(())?;
+        let client_id = ClientId::from(client_id.as_bytes());
+        self.add_dice_signer_endpoint(responder, client_id).map_err(|_service_endpoint| ())?;
+
+        Ok(crate::client::ClientImplementation::new(requester, self))
+    }
+
 
     pub fn add_endpoint(&mut self, interchange: Responder<TrussedInterchange>, client_id: ClientId) -> Result<(), ServiceEndpoint> {
-        if client_id == PathBuf::from("trussed") {
+        Self::check_client_id(&client_id);
+        self.eps.push(ServiceEndpoint { interchange, client_id, provisioner: false, dice_signer: false })
+    }
+
+    /// Adds an endpoint that may also make provisioning requests.
+    ///
+    /// Provisioning requests from other endpoints fail with `RequestNotAvailable`.
+    pub fn add_provisioner_endpoint(&mut self, interchange: Responder<TrussedInterchange>, client_id: ClientId) -> Result<(), ServiceEndpoint> {
+        Self::check_client_id(&client_id);
+        self.eps.push(ServiceEndpoint { interchange, client_id, provisioner: true, dice_signer: false })
+    }
+
+    /// Adds an endpoint that may also sign with the DICE Alias key.
+    ///
+    /// DICE signing requests from other endpoints fail with `RequestNotAvailable`.
+    pub fn add_dice_signer_endpoint(&mut self, interchange: Responder<TrussedInterchange>, client_id: ClientId) -> Result<(), ServiceEndpoint> {
+        Self::check_client_id(&client_id);
+        self.eps.push(ServiceEndpoint { interchange, client_id, provisioner: false, dice_signer: true })
+    }
+
+    fn check_client_id(client_id: &ClientId) {
+        if *client_id == PathBuf::from("trussed") {
             panic!("trussed is a reserved client ID");
         }
-        self.eps.push(ServiceEndpoint { interchange, client_id })
+        // otherwise, the client could use the attestation keys directly
+        if *client_id == PathBuf::from(provision::ATTESTATION_CLIENT_ID) {
+            panic!("attn is a reserved client ID");
+        }
+        // likewise for the DICE keys
+        if *client_id == PathBuf::from(dice::DICE_CLIENT_ID) {
+            panic!("dice is a reserved client ID");
+        }
     }
 
     pub fn set_seed_if_uninitialized(&mut self, seed: &[u8; 32]) {
@@ -703,6 +1073,21 @@ impl<P: Platform> Service<P> {
 
     }
 
+    /// Encrypts the keys the client stored before the platform had a key encryption key.
+    ///
+    /// Keys in the clear are refused once there is a key encryption key, as anyone able to
+    /// write to flash could plant them. So the platform calls this once for each client
+    /// (including the attestation client), when it first introduces the key encryption key,
+    /// and never again.
+    pub fn encrypt_stored_keys(&mut self, client_id: &str) -> Result<(), Error> {
+        let key_encryption_key = self.resources.platform.key_encryption_key();
+        ClientKeystore::<P>::new(
+            PathBuf::from(client_id),
+            self.resources.rng().map_err(|_| Error::EntropyMalfunction)?,
+            self.resources.platform.store(),
+        ).with_key_encryption_key(key_encryption_key).encrypt_stored_keys()
+    }
+
     // currently, this just blinks the green heartbeat LED (former toggle_red in app_rtic.rs)
     //
     // in future, this would
@@ -724,7 +1109,12 @@ impl<P: Platform> Service<P> {
                 // #[cfg(test)] println!("service got request: {:?}", &request);
 
                 // resources.currently_serving = ep.client_id.clone();
-                let reply_result = resources.reply_to(ep.client_id.clone(), &request);
+                let not_permitted = (provision::is_provisioning_request(&request) && !ep.provisioner)
+                    || (dice::is_signing_request(&request) && !ep.dice_signer);
+                let reply_result = match not_permitted {
+                    true => Err(Error::RequestNotAvailable),
+                    false => resources.reply_to(ep.client_id.clone(), &request),
+                };
                 ep.interchange.respond(&reply_result).ok();
 
             }
@@ -8,30 +8,41 @@ use rand_core::RngCore;
 use crate::{
     api::{
         request::Attest as AttestRequest,
+        request::AttestationStatement as AttestationStatementRequest,
+        request::GenerateCsr as GenerateCsrRequest,
+        request::IssueCertificate as IssueCertificateRequest,
         request,
         reply::Attest as AttestReply,
+        reply::AttestationStatement as AttestationStatementReply,
+        reply::GenerateCsr as GenerateCsrReply,
+        reply::IssueCertificate as IssueCertificateReply,
+        reply::VerifyCertificateChain as VerifyCertificateChainReply,
+        request::VerifyCertificateChain as VerifyCertificateChainRequest,
     },
+    config::MAX_CERTIFICATE_CHAIN_LENGTH,
     error::Error,
     key,
     mechanisms,
     service::{DeriveKey, SerializeKey, Sign},
     store::certstore::Certstore,
     store::keystore::Keystore,
-    types::{KeyId, KeySerialization, Location, Mechanism, Message, SignatureSerialization, StorageAttributes},
+    types::{
+        AttestationStatementFormat, BasicConstraints, CertificateAttributes, CertificateTemplate, DateTime, DistinguishedName, GeneralName, KeyId, KeySerialization,
+        KeyUsage, Location, Mechanism, Message, SignatureSerialization, StorageAttributes,
+    },
+    x509,
 };
 
-#[cfg(not(feature = "test-attestation-cert-ids"))]
 pub const ED255_ATTN_KEY: KeyId = KeyId::from_special(1);
-#[cfg(feature = "test-attestation-cert-ids")]
-pub const ED255_ATTN_KEY: KeyId = KeyId(Id(u128::from_be_bytes([0x12,0xd2,0xa7,0xe4,0x03,0x55,0x21,0x42,0x99,0xf1,0x57,0x34,0xc5,0xd7,0xd0,0xe7])));
-#[cfg(not(feature = "test-attestation-cert-ids"))]
 pub const P256_ATTN_KEY: KeyId = KeyId::from_special(2);
-#[cfg(feature = "test-attestation-cert-ids")]
-pub const P256_ATTN_KEY: KeyId = KeyId(Id(u128::from_be_bytes([0xc8,0xd6,0x77,0xa3,0x93,0x46,0xc9,0x8f,0xc8,0x5a,0xb0,0x5d,0x29,0xc5,0x75,0x32])));
+
+// 2021-03-13T12:00:00Z, if the template does not say otherwise
+const DEFAULT_NOT_BEFORE: DateTime = DateTime::new(2021, 3, 13, 12, 0, 0);
 
 #[inline(never)]
 pub fn try_attest(
     attn_keystore: &mut impl Keystore,
+    attestation_chain: Option<&Message>,
     certstore: &mut impl Certstore,
     keystore: &mut impl Keystore,
     request: &AttestRequest,
@@ -42,16 +53,6 @@ pub fn try_attest(
 
     let signature_algorithm = SignatureAlgorithm::try_from(request.signing_mechanism)?;
 
-    // 1. Construct the TBS Certificate
-
-    let mut serial = [0u8; 20];
-    keystore.rng().fill_bytes(&mut serial);
-
-    enum KeyAlgorithm {
-        Ed255,
-        P256,
-    }
-
     let key_algorithm = match keystore.key_info(key::Secrecy::Secret, &request.private_key) {
         None => return Err(Error::NoSuchKey),
         Some(info) => {
@@ -67,15 +68,347 @@ pub fn try_attest(
         }
     };
 
-    let spki = match key_algorithm {
+    let spki = subject_public_key(keystore, key_algorithm, request.private_key)?;
+    let attestation_key_identifier = subject_public_key(
+        attn_keystore, signature_algorithm.into(), signature_algorithm.attestation_key(),
+    )?.key_identifier();
+    // with installed certificates, the attestation certificate is the issuer
+    let attestation_certificate = attestation_chain
+        .map(|chain| x509::parse_chain(chain).map(|chain| chain[0]))
+        .transpose()?;
+    let issuer = match attestation_certificate {
+        Some(certificate) => Issuer {
+            algorithm: signature_algorithm,
+            key: signature_algorithm.attestation_key(),
+            name: IssuerName::Encoded(x509::name_contents(certificate.subject)?),
+            key_identifier: certificate.subject_key_identifier
+                .filter(|identifier| identifier.len() <= MAX_KEY_IDENTIFIER_LENGTH)
+                .unwrap_or(&attestation_key_identifier),
+        },
+        None => Issuer {
+            algorithm: signature_algorithm,
+            key: signature_algorithm.attestation_key(),
+            name: IssuerName::Built(Name::default().with_country(b"CH").with_organization("Trussed").with_state("Zurich")),
+            key_identifier: &attestation_key_identifier,
+        },
+    };
+
+    let certificate = issue_certificate(attn_keystore, &issuer, request.private_key, spki, &request.template)?;
+    let id = certstore.write_certificate(Location::Internal, &certificate)?;
+    certstore.set_certificate_attributes(id, &CertificateAttributes::default().set_key(request.private_key))?;
+
+    Ok(AttestReply { certificate: id })
+}
+
+/// Makes a WebAuthn attestation statement, signed by the attestation key, with its certificates.
+///
+/// For `packed`, the signed data is the authenticator data followed by the client data hash.
+/// For `fido-u2f`, it is rebuilt from them as in U2F registration, and the statement carries only
+/// the attestation certificate.
+#[inline(never)]
+pub fn try_attestation_statement(
+    attn_keystore: &mut impl Keystore,
+    attestation_chain: Option<&Message>,
+    request: &AttestationStatementRequest,
+)
+    -> Result<AttestationStatementReply, Error>
+{
+    let signature_algorithm = SignatureAlgorithm::try_from(request.signing_mechanism)?;
+    if request.format == AttestationStatementFormat::FidoU2f && signature_algorithm != SignatureAlgorithm::P256 {
+        return Err(Error::MechanismNotAvailable);
+    }
+    let client_data_hash: &[u8; 32] = request.client_data_hash.as_slice().try_into()
+        .map_err(|_| Error::WrongMessageLength)?;
+    let chain = x509::parse_chain(attestation_chain.ok_or(Error::NoSuchCertificate)?)?;
+
+    let mut data = Message::new();
+    match request.format {
+        AttestationStatementFormat::Packed => {
+            data.extend_from_slice(&request.authenticator_data).map_err(|_| Error::SignDataTooLarge)?;
+            data.extend_from_slice(client_data_hash).map_err(|_| Error::SignDataTooLarge)?;
+        }
+        AttestationStatementFormat::FidoU2f => {
+            push_u2f_verification_data(&mut data, &request.authenticator_data, client_data_hash)?;
+        }
+    }
+    let signature = sign(attn_keystore, signature_algorithm, signature_algorithm.attestation_key(), data)?;
+    let signature = CborBytes(signature.as_ref());
+
+    let statement = match request.format {
+        AttestationStatementFormat::Packed => crate::cbor_serialize_bytes(&PackedAttestationStatement {
+            alg: match signature_algorithm {
+                SignatureAlgorithm::Ed255 => COSE_ALG_EDDSA,
+                SignatureAlgorithm::P256 => COSE_ALG_ES256,
+            },
+            sig: signature,
+            x5c: chain.iter().map(|certificate| CborBytes(certificate.der)).collect(),
+        }),
+        AttestationStatementFormat::FidoU2f => crate::cbor_serialize_bytes(&FidoU2fAttestationStatement {
+            sig: signature,
+            x5c: [CborBytes(chain[0].der)],
+        }),
+    }.map_err(|_| Error::CborError)?;
+
+    Ok(AttestationStatementReply { statement })
+}
+
+/// Issues a certificate for a public key with one of the client's own keys.
+///
+/// For a self-signed certificate, the subject key is the issuer key.
+#[inline(never)]
+pub fn try_issue_certificate(
+    keystore: &mut impl Keystore,
+    certstore: &mut impl Certstore,
+    request: &IssueCertificateRequest,
+)
+    -> Result<IssueCertificateReply, Error>
+{
+    let certificate = certify(
+        keystore, request.mechanism, request.issuer_key, &request.issuer, request.subject_key, &request.template,
+    )?;
+    let id = certstore.write_certificate(request.location, &certificate)?;
+    certstore.set_certificate_attributes(id, &CertificateAttributes::default().set_key(request.subject_key))?;
+
+    Ok(IssueCertificateReply { certificate: id })
+}
+
+/// Returns the DER encoded certificate for the subject key, issued with another key of the keystore.
+pub(crate) fn certify(
+    keystore: &mut impl Keystore,
+    mechanism: Mechanism,
+    issuer_key: KeyId,
+    issuer: &DistinguishedName,
+    subject_key: KeyId,
+    template: &CertificateTemplate,
+)
+    -> Result<Message, Error>
+{
+    let signature_algorithm = SignatureAlgorithm::try_from(mechanism)?;
+    // the issuer MUST be non-empty (RFC 5280, 4.1.2.4)
+    if issuer.is_empty() {
+        return Err(Error::InvalidCertificateTemplate);
+    }
+
+    // the public half of a keypair, or a public key on its own
+    let subject_key_info = keystore.key_info(key::Secrecy::Public, &subject_key)
+        .or_else(|| keystore.key_info(key::Secrecy::Secret, &subject_key));
+    let key_algorithm = match subject_key_info.map(|info| info.kind) {
+        Some(key::Kind::P256) => KeyAlgorithm::P256,
+        Some(key::Kind::Ed255) => KeyAlgorithm::Ed255,
+        _ => return Err(Error::NoSuchKey),
+    };
+
+    let spki = subject_public_key(keystore, key_algorithm, subject_key)?;
+    let issuer_key_identifier = subject_public_key(keystore, signature_algorithm.into(), issuer_key)?
+        .key_identifier();
+    let issuer = Issuer {
+        algorithm: signature_algorithm,
+        key: issuer_key,
+        name: IssuerName::Built(Name::try_from(issuer)?),
+        key_identifier: &issuer_key_identifier,
+    };
+
+    issue_certificate(keystore, &issuer, subject_key, spki, template)
+}
+
+/// Who signs a certificate.
+struct Issuer<'l> {
+    algorithm: SignatureAlgorithm,
+    key: KeyId,
+    name: IssuerName<'l>,
+    /// Goes into the authority key identifier.
+    key_identifier: &'l [u8],
+}
+
+// longer subject key identifiers of installed attestation certificates are not copied
+const MAX_KEY_IDENTIFIER_LENGTH: usize = 64;
+
+/// Returns the DER encoded certificate for the subject's public key, signed by the issuer's key.
+fn issue_certificate(
+    keystore: &mut impl Keystore,
+    issuer: &Issuer<'_>,
+    subject_key: KeyId,
+    spki: SerializedSubjectPublicKey,
+    template: &CertificateTemplate,
+)
+    -> Result<Message, Error>
+{
+    // a certificate must name its subject, by default after the key's ID
+    let key_name = subject_key.hex();
+    let named_template;
+    let template = match template.subject.is_empty() && template.alternative_names.is_empty() {
+        true => {
+            let common_name = core::str::from_utf8(&key_name).unwrap();
+            named_template = template.clone().set_subject(DistinguishedName::default().set_common_name(common_name));
+            &named_template
+        }
+        false => template,
+    };
+    check_template(template)?;
+
+    // 1. Construct the TBS Certificate
+
+    // positive, and exactly 20 octets long (RFC 5280, 4.1.2.2)
+    let mut serial = [0u8; 20];
+    keystore.rng().fill_bytes(&mut serial);
+    serial[0] = (serial[0] & 0x7F) | 0x40;
+
+    let subject = Name::try_from(&template.subject)?;
+
+    let not_before = encode_datetime(&template.not_before.unwrap_or(DEFAULT_NOT_BEFORE))?;
+    let not_after = template.not_after.as_ref().map(encode_datetime).transpose()?;
+
+    let mut extensions = heapless::Vec::<u8, 768>::new();
+    // an OCTET STRING
+    let mut subject_key_identifier = [0u8; 22];
+    subject_key_identifier[..2].copy_from_slice(&hex!("04 14"));
+    subject_key_identifier[2..].copy_from_slice(&spki.key_identifier());
+    push_extension(&mut extensions, SUBJECT_KEY_IDENTIFIER_OID, false, &subject_key_identifier)?;
+    // a SEQUENCE of just the keyIdentifier, which is [0] IMPLICIT
+    let key_identifier_length = issuer.key_identifier.len() as u8;
+    let mut authority_key_identifier = heapless::Vec::<u8, { MAX_KEY_IDENTIFIER_LENGTH + 4 }>::new();
+    authority_key_identifier.extend_from_slice(&[0x30, key_identifier_length + 2, 0x80, key_identifier_length]).unwrap();
+    authority_key_identifier.extend_from_slice(issuer.key_identifier).unwrap();
+    push_extension(&mut extensions, AUTHORITY_KEY_IDENTIFIER_OID, false, &authority_key_identifier)?;
+    push_template_extensions(&mut extensions, template)?;
+
+    let to_be_signed_certificate = TbsCertificate {
+        version: Version::V3,
+        serial: BigEndianInteger(serial.as_ref()),
+        signature_algorithm: issuer.algorithm,
+        issuer: issuer.name,
+        validity: Validity { start: Datetime(&not_before), end: not_after.as_ref().map(|end| Datetime(end)) },
+        subject,
+        subject_public_key_info: spki,
+        extensions: Extensions(&extensions),
+    };
+
+    // only fails if the template makes the certificate too large
+    let message = Message::from(TaggedValue::new(Tag::SEQUENCE, &to_be_signed_certificate)
+        .to_heapless_vec()
+        .map_err(|_| Error::InvalidCertificateTemplate)?);
+
+    // 2. sign the TBS Cert
+    let signature = sign(keystore, issuer.algorithm, issuer.key, message)?;
+
+    let mut leading_zero_signature = [0u8; 80];
+    let l = signature.as_ref().len();
+    leading_zero_signature[1..][..l].copy_from_slice(signature.as_ref());
+
+    // 3. construct the entire DER-serialized cert
+    Ok(Message::from(Certificate {
+        tbs_certificate: to_be_signed_certificate,
+        signature_algorithm: issuer.algorithm,
+        signature: &leading_zero_signature[..l + 1],
+    }
+        .to_heapless_vec()
+        .map_err(|_| Error::InvalidCertificateTemplate)?))
+}
+
+/// Builds a PKCS #10 certification request for the key, signed by the key itself.
+///
+/// The subject is left as is, so it may only be empty with alternative names, and the extensions
+/// of the template are requested.
+#[inline(never)]
+pub fn try_generate_csr(
+    keystore: &mut impl Keystore,
+    request: &GenerateCsrRequest,
+)
+    -> Result<GenerateCsrReply, Error>
+{
+    let signature_algorithm = SignatureAlgorithm::try_from(request.mechanism)?;
+    let template = &request.template;
+    check_template(template)?;
+
+    let spki = subject_public_key(keystore, signature_algorithm.into(), request.private_key)?;
+    let mut extensions = heapless::Vec::<u8, 768>::new();
+    push_template_extensions(&mut extensions, template)?;
+
+    let certification_request_info = CertificationRequestInfo {
+        version: BigEndianInteger(&[0]),
+        subject: Name::try_from(&template.subject)?,
+        subject_public_key_info: spki,
+        attributes: Attributes(&extensions),
+    };
+
+    // only fails if the template makes the request too large
+    let message = Message::from(TaggedValue::new(Tag::SEQUENCE, &certification_request_info)
+        .to_heapless_vec()
+        .map_err(|_| Error::InvalidCertificateTemplate)?);
+
+    let signature = sign(keystore, signature_algorithm, request.private_key, message)?;
+
+    let mut leading_zero_signature = [0u8; 80];
+    let l = signature.as_ref().len();
+    leading_zero_signature[1..][..l].copy_from_slice(signature.as_ref());
+
+    let csr = Message::from(CertificationRequest {
+        certification_request_info,
+        signature_algorithm,
+        signature: &leading_zero_signature[..l + 1],
+    }
+        .to_heapless_vec()
+        .map_err(|_| Error::InvalidCertificateTemplate)?);
+
+    Ok(GenerateCsrReply { csr })
+}
+
+/// Validates the chain against each of the trust anchors in turn.
+///
+/// If no anchor leads to a valid chain, a failure more specific than `UntrustedCertificate`
+/// is reported, if there is one. Without a `time`, the validity periods can not be checked,
+/// so verification fails with `FunctionNotSupported`.
+#[inline(never)]
+pub fn try_verify_certificate_chain(
+    certstore: &mut impl Certstore,
+    request: &VerifyCertificateChainRequest,
+    time: Option<DateTime>,
+)
+    -> Result<VerifyCertificateChainReply, Error>
+{
+    let time = time.ok_or(Error::FunctionNotSupported)?;
+    let chain = x509::parse_chain(&request.chain)?;
+
+    let mut error = Error::UntrustedCertificate;
+    for &id in request.anchors.iter() {
+        let der = certstore.read_certificate(id)?;
+        let anchor = x509::Certificate::parse(&der)?;
+        match x509::verify_chain(&chain, &anchor, Some(&time)) {
+            Ok(()) => return Ok(VerifyCertificateChainReply { anchor: id }),
+            Err(Error::UntrustedCertificate) => {}
+            Err(other) => error = other,
+        }
+    }
+    Err(error)
+}
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum KeyAlgorithm {
+    Ed255,
+    P256,
+}
+
+impl From<SignatureAlgorithm> for KeyAlgorithm {
+    fn from(algorithm: SignatureAlgorithm) -> Self {
+        match algorithm {
+            SignatureAlgorithm::Ed255 => KeyAlgorithm::Ed255,
+            SignatureAlgorithm::P256 => KeyAlgorithm::P256,
+        }
+    }
+}
+
+fn subject_public_key(keystore: &mut impl Keystore, algorithm: KeyAlgorithm, private_key: KeyId)
+    -> Result<SerializedSubjectPublicKey, Error>
+{
+    Ok(match algorithm {
         KeyAlgorithm::Ed255 => {
             let public_key = mechanisms::Ed255::derive_key(
                 keystore,
                 &request::DeriveKey {
                     mechanism: Mechanism::Ed255,
-                    base_key: request.private_key,
+                    base_key: private_key,
                     additional_data: None,
-                    attributes: StorageAttributes { persistence: Location::Volatile },
+                    attributes: StorageAttributes::new(),
                 },
             )?.key;
             let serialized_key = mechanisms::Ed255::serialize_key(
@@ -85,8 +418,10 @@ pub fn try_attest(
                     key: public_key,
                     format: KeySerialization::Raw,
                 },
-            ).unwrap().serialized_key;
+            );
+            // the derived public key is a copy of its own
             keystore.delete_key(&public_key);
+            let serialized_key = serialized_key?.serialized_key;
 
             SerializedSubjectPublicKey::Ed255(
                 serialized_key.as_ref().try_into().map_err(|_| Error::ImplementationError)?
@@ -98,9 +433,9 @@ pub fn try_attest(
                 keystore,
                 &request::DeriveKey {
                     mechanism: Mechanism::P256,
-                    base_key: request.private_key,
+                    base_key: private_key,
                     additional_data: None,
-                    attributes: StorageAttributes { persistence: Location::Volatile },
+                    attributes: StorageAttributes::new(),
                 },
             )?.key;
             let serialized_key = mechanisms::P256::serialize_key(
@@ -108,76 +443,283 @@ pub fn try_attest(
                 &request::SerializeKey {
                     mechanism: Mechanism::P256,
                     key: public_key,
-                    format: KeySerialization::Sec1,
+                    format: KeySerialization::Raw,
                 },
-            ).unwrap().serialized_key;
+            );
+            // the derived public key is a copy of its own
             keystore.delete_key(&public_key);
+            let serialized_key = serialized_key?.serialized_key;
 
-            SerializedSubjectPublicKey::P256(
-                serialized_key.as_ref().try_into().map_err(|_| Error::ImplementationError)?
-            )
+            // uncompressed, which all verifiers must support (RFC 5480, 2.2)
+            let mut point = [0x04; 65];
+            if serialized_key.len() != 64 {
+                return Err(Error::ImplementationError);
+            }
+            point[1..].copy_from_slice(&serialized_key);
+            SerializedSubjectPublicKey::P256(point)
         }
-    };
-
-    let to_be_signed_certificate = TbsCertificate {
-        version: Version::V3,
-        serial: BigEndianInteger(serial.as_ref()),
-        signature_algorithm,
-        issuer: Name::default().with_country(b"CH").with_organization("Trussed").with_state("Zurich"),
-        // validity: Validity { start: Datetime(b"20210313120000Z"), end: None },
-        validity: Validity { start: Datetime(b"20210313120000Z"), end: None },
-        // subject: Name::default(),
-        subject: Name::default(), //.with_country(b"CH").with_organization("Trussed").with_state("Zurich"),
-        subject_public_key_info: spki,
-    };
-
-    let message = Message::from(TaggedValue::new(Tag::SEQUENCE, &to_be_signed_certificate)
-        .to_heapless_vec()
-        .map_err(|_| Error::InternalError)?);
+    })
+}
 
-    // 2. sign the TBS Cert
-    let signature = match signature_algorithm {
+/// Signs with a P256 or Ed255 key, returning the signature as it goes into the BIT STRING.
+fn sign(keystore: &mut impl Keystore, algorithm: SignatureAlgorithm, key: KeyId, message: Message)
+    -> Result<SerializedSignature, Error>
+{
+    Ok(match algorithm {
         SignatureAlgorithm::Ed255 => {
             let signature = mechanisms::Ed255::sign(
-                attn_keystore,
+                keystore,
                 &request::Sign {
                     mechanism: Mechanism::Ed255,
-                    key: ED255_ATTN_KEY,
+                    key,
                     message,
                     format: SignatureSerialization::Raw,
                 },
-            ).unwrap().signature;
+            )?.signature;
             SerializedSignature::Ed255(signature.as_ref().try_into().unwrap())
         }
         SignatureAlgorithm::P256 => {
             SerializedSignature::P256(heapless_bytes::Bytes::from_slice(&mechanisms::P256::sign(
-                attn_keystore,
+                keystore,
                 &request::Sign {
                     mechanism: Mechanism::P256,
-                    key: P256_ATTN_KEY,
+                    key,
                     message,
                     format: SignatureSerialization::Asn1Der,
                 },
-            ).unwrap().signature.as_ref()).unwrap())
+            )?.signature.as_ref()).unwrap())
         }
-    };
+    })
+}
 
-    let mut leading_zero_signature = [0u8; 80];
-    let l = signature.as_ref().len();
-    leading_zero_signature[1..][..l].copy_from_slice(signature.as_ref());
+// COSE algorithm identifiers
+const COSE_ALG_ES256: i32 = -7;
+const COSE_ALG_EDDSA: i32 = -8;
 
-    // 3. construct the entire DER-serialized cert
-    let certificate = Message::from(Certificate {
-        tbs_certificate: to_be_signed_certificate,
-        signature_algorithm,
-        signature: &leading_zero_signature[..l + 1],
+/// Serialized as a CBOR byte string, not as an array.
+#[derive(Clone, Copy)]
+struct CborBytes<'l>(&'l [u8]);
+
+impl serde::Serialize for CborBytes<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0)
     }
-        .to_heapless_vec()
-        .map_err(|_| Error::ImplementationError)?);
+}
 
-    let id = certstore.write_certificate(Location::Internal, &certificate)?;
+// the fields are in CTAP2 canonical order
+#[derive(serde::Serialize)]
+struct PackedAttestationStatement<'l> {
+    alg: i32,
+    sig: CborBytes<'l>,
+    x5c: heapless::Vec<CborBytes<'l>, MAX_CERTIFICATE_CHAIN_LENGTH>,
+}
 
-    Ok(AttestReply { certificate: id })
+#[derive(serde::Serialize)]
+struct FidoU2fAttestationStatement<'l> {
+    sig: CborBytes<'l>,
+    x5c: [CborBytes<'l>; 1],
+}
+
+/// Pushes 0x00, the RP ID hash, the client data hash, the credential ID and the uncompressed
+/// credential public key, all but the client data hash taken from the authenticator data.
+fn push_u2f_verification_data(data: &mut Message, authenticator_data: &[u8], client_data_hash: &[u8; 32])
+    -> Result<(), Error>
+{
+    // RP ID hash, flags, signature counter, AAGUID and credential ID length
+    const HEADER_LENGTH: usize = 32 + 1 + 4 + 16 + 2;
+    const ATTESTED_CREDENTIAL_DATA: u8 = 0x40;
+
+    if authenticator_data.len() < HEADER_LENGTH || authenticator_data[32] & ATTESTED_CREDENTIAL_DATA == 0 {
+        return Err(Error::InvalidSerializationFormat);
+    }
+    let (header, rest) = authenticator_data.split_at(HEADER_LENGTH);
+    let credential_id_length = u16::from_be_bytes([header[53], header[54]]) as usize;
+    if rest.len() < credential_id_length {
+        return Err(Error::InvalidSerializationFormat);
+    }
+    let (credential_id, rest) = rest.split_at(credential_id_length);
+    // extensions may follow the public key
+    let (public_key, _): (cosey::P256PublicKey, _) = cbor_smol::de::take_from_bytes(rest)
+        .map_err(|_| Error::InvalidSerializationFormat)?;
+    if public_key.x.len() != 32 || public_key.y.len() != 32 {
+        return Err(Error::InvalidSerializationFormat);
+    }
+
+    let parts: [&[u8]; 7] = [&[0], &header[..32], client_data_hash, credential_id, &[0x04], &public_key.x, &public_key.y];
+    for part in parts.iter() {
+        data.extend_from_slice(part).map_err(|_| Error::SignDataTooLarge)?;
+    }
+    Ok(())
+}
+
+// id-ce-subjectKeyIdentifier, id-ce-keyUsage, id-ce-subjectAltName, id-ce-basicConstraints,
+// id-ce-authorityKeyIdentifier
+const SUBJECT_KEY_IDENTIFIER_OID: &[u8] = &hex!("55 1D 0E");
+const KEY_USAGE_OID: &[u8] = &hex!("55 1D 0F");
+const SUBJECT_ALTERNATIVE_NAME_OID: &[u8] = &hex!("55 1D 11");
+const BASIC_CONSTRAINTS_OID: &[u8] = &hex!("55 1D 13");
+const AUTHORITY_KEY_IDENTIFIER_OID: &[u8] = &hex!("55 1D 23");
+const RESERVED_OIDS: [&[u8]; 5] = [
+    SUBJECT_KEY_IDENTIFIER_OID, KEY_USAGE_OID, SUBJECT_ALTERNATIVE_NAME_OID, BASIC_CONSTRAINTS_OID,
+    AUTHORITY_KEY_IDENTIFIER_OID,
+];
+// 1.2.840.113549.1.9.14 pkcs-9-at-extensionRequest
+const EXTENSION_REQUEST_OID: &[u8] = &hex!("2A 86 48 86 F7 0D 01 09 0E");
+
+/// Checks the parts of the template that RFC 5280 constrains, and that are not checked when encoding.
+fn check_template(template: &CertificateTemplate) -> Result<(), Error> {
+    let ca = matches!(template.basic_constraints, Some(BasicConstraints { ca: true, .. }));
+    if let Some(key_usage) = template.key_usage {
+        let valid = [
+            // at least one bit must be set
+            !key_usage.is_empty(),
+            // only CAs sign certificates
+            ca || !key_usage.contains(KeyUsage::KEY_CERT_SIGN),
+            // encipher and decipher only restrict key agreement
+            key_usage.contains(KeyUsage::KEY_AGREEMENT)
+                || !key_usage.intersects(KeyUsage::ENCIPHER_ONLY | KeyUsage::DECIPHER_ONLY),
+        ].iter().all(|b| *b);
+        if !valid {
+            return Err(Error::InvalidCertificateTemplate);
+        }
+    }
+    if let Some(BasicConstraints { path_length: Some(_), .. }) = template.basic_constraints {
+        let signs_certificates = template.key_usage.map_or(ca, |key_usage| ca && key_usage.contains(KeyUsage::KEY_CERT_SIGN));
+        if !signs_certificates {
+            return Err(Error::InvalidCertificateTemplate);
+        }
+    }
+
+    // an empty subject is only allowed with alternative names, which are then critical (RFC 5280, 4.1.2.6)
+    if template.subject.is_empty() && template.alternative_names.is_empty() {
+        return Err(Error::InvalidCertificateTemplate);
+    }
+    for name in &template.alternative_names {
+        let valid = match name {
+            // IA5String
+            GeneralName::Email(name) | GeneralName::Dns(name) | GeneralName::Uri(name) => {
+                !name.is_empty() && name.is_ascii()
+            }
+            GeneralName::IpAddress(address) => matches!(address.len(), 4 | 16),
+        };
+        if !valid {
+            return Err(Error::InvalidCertificateTemplate);
+        }
+    }
+
+    for (i, extension) in template.extensions.iter().enumerate() {
+        // at most one instance of each extension
+        let duplicate = RESERVED_OIDS.contains(&extension.oid.as_slice())
+            || template.extensions[..i].iter().any(|other| other.oid == extension.oid);
+        if duplicate || !is_valid_oid(&extension.oid) {
+            return Err(Error::InvalidCertificateTemplate);
+        }
+    }
+
+    match (template.not_before.unwrap_or(DEFAULT_NOT_BEFORE), template.not_after) {
+        (not_before, Some(not_after)) if not_after < not_before => Err(Error::InvalidCertificateTemplate),
+        _ => Ok(()),
+    }
+}
+
+/// Checks the contents of a DER encoded OID: subidentifiers are minimally encoded, the last one is complete.
+fn is_valid_oid(oid: &[u8]) -> bool {
+    let starts_subidentifier = |i: usize| i == 0 || oid[i - 1] & 0x80 == 0;
+    matches!(oid.last(), Some(last) if last & 0x80 == 0)
+        && (0..oid.len()).all(|i| oid[i] != 0x80 || !starts_subidentifier(i))
+}
+
+fn encode_datetime(datetime: &DateTime) -> Result<[u8; 15], Error> {
+    let DateTime { year, month, day, hour, minute, second } = *datetime;
+    ParsedDatetime::new(year, month, day, hour, minute, second)
+        .map(|datetime| datetime.to_bytes())
+        .ok_or(Error::InvalidCertificateTemplate)
+}
+
+/// The BIT STRING of named bits, with trailing zero bits removed as DER requires.
+fn encode_key_usage(key_usage: KeyUsage) -> heapless::Vec<u8, 5> {
+    let bits = key_usage.bits();
+    // not empty, see `check_template`
+    let highest = 15 - bits.leading_zeros() as usize;
+    let mut named_bits = [0u8; 2];
+    for n in 0..=highest {
+        if bits & (1 << n) != 0 {
+            named_bits[n / 8] |= 0x80 >> (n % 8);
+        }
+    }
+    let length = highest / 8 + 1;
+    let mut encoding = heapless::Vec::from_slice(&[0x03, length as u8 + 1, 7 - (highest % 8) as u8]).unwrap();
+    encoding.extend_from_slice(&named_bits[..length]).unwrap();
+    encoding
+}
+
+/// The SEQUENCE of the optional cA flag, which defaults to false, and path length.
+fn encode_basic_constraints(basic_constraints: &BasicConstraints) -> heapless::Vec<u8, 10> {
+    let mut contents = heapless::Vec::<u8, 8>::new();
+    if basic_constraints.ca {
+        contents.extend_from_slice(&TRUE_ENCODING).unwrap();
+    }
+    if let Some(path_length) = basic_constraints.path_length {
+        let path_length = [path_length];
+        // NB: `Tag::INTEGER` is 0x1 in flexiber
+        TaggedValue::new(Tag::universal(0x2), &BigEndianInteger(&path_length))
+            .encode_to_heapless_vec(&mut contents)
+            .unwrap();
+    }
+    let mut encoding = heapless::Vec::from_slice(&[0x30, contents.len() as u8]).unwrap();
+    encoding.extend_from_slice(&contents).unwrap();
+    encoding
+}
+
+/// The SEQUENCE of GeneralNames.
+fn encode_alternative_names(names: &[GeneralName]) -> Result<heapless::Vec<u8, 320>, Error> {
+    let mut contents = heapless::Vec::<u8, 316>::new();
+    for name in names {
+        // the context specific tag is the name's CHOICE
+        let (number, name) = match name {
+            GeneralName::Email(name) => (1, name.as_bytes()),
+            GeneralName::Dns(name) => (2, name.as_bytes()),
+            GeneralName::Uri(name) => (6, name.as_bytes()),
+            GeneralName::IpAddress(address) => (7, address.as_slice()),
+        };
+        TaggedSlice::from(Tag::context(number), name)
+            .and_then(|name| name.encode_to_heapless_vec(&mut contents))
+            .map_err(|_| Error::InvalidCertificateTemplate)?;
+    }
+    TaggedSlice::from(Tag::SEQUENCE, &contents)
+        .and_then(|names| names.to_heapless_vec())
+        .map_err(|_| Error::InvalidCertificateTemplate)
+}
+
+/// The extensions described by the template, which go both into certificates and requests.
+///
+/// Alternative names are critical if they are all there is to the subject.
+fn push_template_extensions<const N: usize>(extensions: &mut heapless::Vec<u8, N>, template: &CertificateTemplate)
+    -> Result<(), Error>
+{
+    if let Some(key_usage) = template.key_usage {
+        push_extension(extensions, KEY_USAGE_OID, true, &encode_key_usage(key_usage))?;
+    }
+    if !template.alternative_names.is_empty() {
+        let alternative_names = encode_alternative_names(&template.alternative_names)?;
+        push_extension(extensions, SUBJECT_ALTERNATIVE_NAME_OID, template.subject.is_empty(), &alternative_names)?;
+    }
+    if let Some(basic_constraints) = template.basic_constraints {
+        push_extension(extensions, BASIC_CONSTRAINTS_OID, true, &encode_basic_constraints(&basic_constraints))?;
+    }
+    for extension in &template.extensions {
+        push_extension(extensions, &extension.oid, extension.critical, &extension.value)?;
+    }
+    Ok(())
+}
+
+fn push_extension<const N: usize>(extensions: &mut heapless::Vec<u8, N>, oid: &[u8], critical: bool, value: &[u8])
+    -> Result<(), Error>
+{
+    TaggedValue::new(Tag::SEQUENCE, &Extension { oid, critical, value })
+        .encode_to_heapless_vec(extensions)
+        .map(drop)
+        .map_err(|_| Error::InvalidCertificateTemplate)
 }
 
 #[derive(Clone, Copy, Encodable, Eq, PartialEq)]
@@ -189,19 +731,19 @@ pub struct TbsCertificate<'l> {
     serial: BigEndianInteger<'l>,
     #[tlv(constructed, number = "0x10")] // SEQUENCE
     signature_algorithm: SignatureAlgorithm,
-    /// TODO: This MUST be non-empty. Maybe just put O=Trussed
+    /// This MUST be non-empty.
     #[tlv(constructed, number = "0x10")] // SEQUENCE
-    issuer: Name<'l>,
+    issuer: IssuerName<'l>,
     #[tlv(constructed, number = "0x10")] // SEQUENCE
     validity: Validity<'l>,
-    /// This one seems optional
+    /// Only allowed to be empty with a critical subjectAltName (see `push_template_extensions`).
     #[tlv(constructed, number = "0x10")] // SEQUENCE
     subject: Name<'l>,
     #[tlv(constructed, number = "0x10")] // SEQUENCE
     subject_public_key_info: SerializedSubjectPublicKey,
-
-    // optional
-    // extensions: Extensions
+    // "EXPLICIT [3]", only allowed in V3
+    #[tlv(constructed, context, number = "0x3")]
+    extensions: Extensions<'l>,
 }
 
 #[derive(Clone, Encodable, Eq, PartialEq)]
@@ -215,6 +757,67 @@ pub struct Certificate<'l> {
     signature: &'l [u8],//SerializedSignature,
 }
 
+#[derive(Clone, Copy, Encodable, Eq, PartialEq)]
+pub struct CertificationRequestInfo<'l> {
+    #[tlv(number = "0x2")] // INTEGER
+    version: BigEndianInteger<'l>,
+    #[tlv(constructed, number = "0x10")] // SEQUENCE
+    subject: Name<'l>,
+    #[tlv(constructed, number = "0x10")] // SEQUENCE
+    subject_public_key_info: SerializedSubjectPublicKey,
+    // "IMPLICIT [0]" SET OF attributes
+    #[tlv(constructed, context, number = "0x0")]
+    attributes: Attributes<'l>,
+}
+
+#[derive(Clone, Encodable, Eq, PartialEq)]
+#[tlv(constructed, number = "0x10")] // SEQUENCE
+pub struct CertificationRequest<'l> {
+    #[tlv(constructed, number = "0x10")] // SEQUENCE
+    certification_request_info: CertificationRequestInfo<'l>,
+    #[tlv(constructed, number = "0x10")] // SEQUENCE
+    signature_algorithm: SignatureAlgorithm,
+    #[tlv(number = "0x3", slice)] // BIT-STRING
+    signature: &'l [u8],
+}
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+/// The attributes of a certification request: only the extension request, if there are extensions.
+///
+/// Holds the concatenated DER encodings of each extension, like `Extensions`.
+pub struct Attributes<'l>(&'l [u8]);
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+struct ExtensionRequest<'l>(Extensions<'l>);
+
+impl Encodable for ExtensionRequest<'_> {
+    fn encoded_length(&self) -> BerResult<BerLength> {
+        TaggedSlice::from(Tag::OBJECT_IDENTIFIER, EXTENSION_REQUEST_OID)?.encoded_length()?
+            + TaggedValue::new(Tag::SET, &self.0).encoded_length()?
+    }
+
+    fn encode(&self, encoder: &mut Encoder<'_>) -> BerResult<()> {
+        encoder.encode(&TaggedSlice::from(Tag::OBJECT_IDENTIFIER, EXTENSION_REQUEST_OID)?)?;
+        encoder.encode(&TaggedValue::new(Tag::SET, &self.0))
+    }
+}
+
+impl Encodable for Attributes<'_> {
+    fn encoded_length(&self) -> BerResult<BerLength> {
+        match self.0.is_empty() {
+            true => Ok(BerLength::zero()),
+            false => TaggedValue::new(Tag::SEQUENCE, &ExtensionRequest(Extensions(self.0))).encoded_length(),
+        }
+    }
+
+    fn encode(&self, encoder: &mut Encoder<'_>) -> BerResult<()> {
+        match self.0.is_empty() {
+            true => Ok(()),
+            false => encoder.encode(&TaggedValue::new(Tag::SEQUENCE, &ExtensionRequest(Extensions(self.0)))),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum SerializedSignature {
     Ed255([u8; 64]),
@@ -345,6 +948,15 @@ impl TryFrom<Mechanism> for SignatureAlgorithm {
     }
 }
 
+impl SignatureAlgorithm {
+    fn attestation_key(&self) -> KeyId {
+        match self {
+            SignatureAlgorithm::Ed255 => ED255_ATTN_KEY,
+            SignatureAlgorithm::P256 => P256_ATTN_KEY,
+        }
+    }
+}
+
 // 1.2.840.10045.4.3.2 ecdsaWithSHA256 (ANSI X9.62 ECDSA algorithm with SHA256))
 const P256_OID_ENCODING: &[u8] = &hex!("06 08  2A 86 48 CE 3D 04 03 02");
 const P256_PUB_ENCODING: &[u8] = &hex!("06 07 2A 86 48 CE 3D 02 01   06 08 2A 86 48 CE  3D 03 01 07");
@@ -368,13 +980,39 @@ impl Encodable for SignatureAlgorithm {
     }
 }
 
+/// The issuer of a certificate: a name we build, or the subject of an installed certificate.
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum IssuerName<'l> {
+    Built(Name<'l>),
+    /// The relative distinguished names, as DER encoded in the certificate.
+    Encoded(&'l [u8]),
+}
+
+impl Encodable for IssuerName<'_> {
+    fn encoded_length(&self) -> BerResult<BerLength> {
+        match self {
+            IssuerName::Built(name) => name.encoded_length(),
+            IssuerName::Encoded(contents) => contents.encoded_length(),
+        }
+    }
+
+    fn encode(&self, encoder: &mut Encoder<'_>) -> BerResult<()> {
+        match self {
+            IssuerName::Built(name) => encoder.encode(name),
+            IssuerName::Encoded(contents) => encoder.encode(contents),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Default, Eq, PartialEq)]
 pub struct Name<'l> {
     /// this should be an ISO-code (in particular, "printable characters")
-    /// TODO: enforce
     country: Option<[u8; 2]>,
-    organization: Option<&'l str>,
     state: Option<&'l str>,
+    locality: Option<&'l str>,
+    organization: Option<&'l str>,
+    organizational_unit: Option<&'l str>,
+    common_name: Option<&'l str>,
 }
 
 #[derive(Clone, Copy, Encodable, Eq, PartialEq)]
@@ -390,11 +1028,55 @@ impl<'l> Name<'l> {
     pub fn with_country(self, country: &[u8; 2]) -> Self {
         Self { country: Some(*country), ..self }
     }
+    pub fn with_state(self, state: &'l str) -> Self {
+        Self { state: Some(state), ..self }
+    }
+    pub fn with_locality(self, locality: &'l str) -> Self {
+        Self { locality: Some(locality), ..self }
+    }
     pub fn with_organization(self, organization: &'l str) -> Self {
         Self { organization: Some(organization), ..self }
     }
-    pub fn with_state(self, state: &'l str) -> Self {
-        Self { state: Some(state), ..self }
+    pub fn with_organizational_unit(self, organizational_unit: &'l str) -> Self {
+        Self { organizational_unit: Some(organizational_unit), ..self }
+    }
+    pub fn with_common_name(self, common_name: &'l str) -> Self {
+        Self { common_name: Some(common_name), ..self }
+    }
+
+    /// The UTF8String attributes, in the usual order.
+    fn parts(&self) -> [(&'static [u8], Option<&'l str>); 5] {
+        [
+            (&hex!("55 04 08"), self.state),
+            (&hex!("55 04 07"), self.locality),
+            (&hex!("55 04 0A"), self.organization),
+            (&hex!("55 04 0B"), self.organizational_unit),
+            (&hex!("55 04 03"), self.common_name),
+        ]
+    }
+}
+
+impl<'l> TryFrom<&'l DistinguishedName> for Name<'l> {
+    type Error = Error;
+    fn try_from(name: &'l DistinguishedName) -> Result<Self, Error> {
+        let name = Self {
+            country: name.country,
+            state: name.state.as_deref(),
+            locality: name.locality.as_deref(),
+            organization: name.organization.as_deref(),
+            organizational_unit: name.organizational_unit.as_deref(),
+            common_name: name.common_name.as_deref(),
+        };
+        let valid = [
+            name.country.iter().flatten().all(u8::is_ascii_uppercase),
+            name.parts().iter().all(|(_, part)| part.iter().all(|part| !part.is_empty())),
+        ].iter().all(|b| *b);
+
+        if valid {
+            Ok(name)
+        } else {
+            Err(Error::InvalidCertificateTemplate)
+        }
     }
 }
 
@@ -404,52 +1086,74 @@ impl Encodable for Name<'_> {
         if self.country.is_some() {
             l += 0xD;
         }
-        if let Some(organization) = self.organization {
-            l += 11 + organization.as_bytes().len() as u16;
-        }
-        if let Some(state) = self.state {
-            l += 11 + state.as_bytes().len() as u16;
+        // NB: parts are at most 64 bytes, so all lengths are short
+        for (_, part) in self.parts() {
+            if let Some(part) = part {
+                l += 11 + part.len() as u16;
+            }
         }
         Ok(l.into())
     }
 
     fn encode(&self, encoder: &mut Encoder<'_>) -> BerResult<()> {
-        // Order by OID for good measure
         if let Some(country) = self.country {
             // "31 0B 30 09 06 03 55  04 06 13 02 43 48"
             let mut encoding: [u8; 0xB] = hex!("30 09 06 03 55  04 06 13 02 00 00");
             encoding[9..].copy_from_slice(&country);
             encoder.encode(&TaggedSlice::from(Tag::SET, &encoding)?)?;
         }
-        if let Some(state) = self.state {
-            let encoded_state = EncodedPart {
-                oid: &hex!("55 04 08"),
-                part: state.as_bytes(),
-            };
-            encoder.encode(&TaggedValue::new(Tag::SET, &encoded_state))?;
-        }
-        if let Some(organization) = self.organization {
-            let encoded_organization = EncodedPart {
-                oid: &hex!("55 04 0A"),
-                part: organization.as_bytes(),
-            };
-            encoder.encode(&TaggedValue::new(Tag::SET, &encoded_organization))?;
+        for (oid, part) in self.parts() {
+            if let Some(part) = part {
+                let encoded_part = EncodedPart {
+                    oid,
+                    part: part.as_bytes(),
+                };
+                encoder.encode(&TaggedValue::new(Tag::SET, &encoded_part))?;
+            }
         }
         Ok(())
     }
 }
 
+// DER encoding of a BOOLEAN that is true
+const TRUE_ENCODING: [u8; 3] = hex!("01 01 FF");
+
 #[derive(Clone, Copy, Eq, PartialEq)]
-/// Currently unconstructable.
-pub enum Extension {}
+/// The contents of an extension's SEQUENCE, `critical` is only encoded if set.
+pub struct Extension<'l> {
+    oid: &'l [u8],
+    critical: bool,
+    /// DER encoded, goes into an OCTET STRING.
+    value: &'l [u8],
+}
+
+impl Encodable for Extension<'_> {
+    fn encoded_length(&self) -> BerResult<BerLength> {
+        let critical = if self.critical { TRUE_ENCODING.len() as u8 } else { 0 };
+        (TaggedSlice::from(Tag::OBJECT_IDENTIFIER, self.oid)?.encoded_length()? + critical)?
+            + TaggedSlice::from(Tag::OCTET_STRING, self.value)?.encoded_length()?
+    }
+
+    fn encode(&self, encoder: &mut Encoder<'_>) -> BerResult<()> {
+        encoder.encode(&TaggedSlice::from(Tag::OBJECT_IDENTIFIER, self.oid)?)?;
+        if self.critical {
+            encoder.encode(&TRUE_ENCODING)?;
+        }
+        encoder.encode(&TaggedSlice::from(Tag::OCTET_STRING, self.value)?)
+    }
+}
 
 #[derive(Clone, Copy, Eq, PartialEq)]
-/// Only empty slices possible currently.
-pub struct Extensions<'l>(&'l [Extension]);
+/// The concatenated DER encodings of each extension, encoded as their SEQUENCE.
+pub struct Extensions<'l>(&'l [u8]);
 
 impl Encodable for Extensions<'_> {
-    fn encoded_length(&self) -> BerResult<BerLength> { Ok(0u8.into()) }
-    fn encode(&self, _encoder: &mut Encoder<'_>) -> BerResult<()> { Ok(()) }
+    fn encoded_length(&self) -> BerResult<BerLength> {
+        TaggedSlice::from(Tag::SEQUENCE, self.0)?.encoded_length()
+    }
+    fn encode(&self, encoder: &mut Encoder<'_>) -> BerResult<()> {
+        encoder.encode(&TaggedSlice::from(Tag::SEQUENCE, self.0)?)
+    }
 }
 
 pub struct ParsedDatetime {
@@ -463,17 +1167,8 @@ pub struct ParsedDatetime {
 
 impl ParsedDatetime {
     pub fn new(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> Option<Self> {
-        let valid = [
-            year >= 2000,
-            year <= 9999,
-            month >= 1,
-            month <= 12,
-            day >= 1,
-            day <= 31,
-            hour <= 23,
-            minute <= 59,
-            second <= 59,
-        ].iter().all(|b| *b);
+        // earlier times would need a GeneralizedTime, see `Datetime`
+        let valid = year >= 1950 && DateTime::new(year, month, day, hour, minute, second).is_valid();
 
         if valid {
             Some(Self { year, month, day, hour, minute, second })
@@ -484,7 +1179,6 @@ impl ParsedDatetime {
 
     pub fn to_bytes(&self) -> [u8; 15] {
         let mut buffer: heapless::Vec<u8, 15> = Default::default();
-        buffer.resize_default(15).unwrap();
         core::fmt::write(&mut buffer, format_args!(
             "{}{:02}{:02}{:02}{:02}{:02}Z",
             self.year, self.month, self.day, self.hour, self.minute, self.second
@@ -542,15 +1236,32 @@ impl Encodable for Validity<'_> {
 #[derive(Clone, Copy, Eq, PartialEq)]
 pub enum SerializedSubjectPublicKey {
     Ed255([u8; 32]),
-    // This is the DER version with leading '04'
-    P256([u8; 33]),
+    // This is the uncompressed SEC1 point, with leading '04'
+    P256([u8; 65]),
+}
+
+impl SerializedSubjectPublicKey {
+    fn as_bytes(&self) -> &[u8] {
+        match self {
+            SerializedSubjectPublicKey::Ed255(pub_key) => pub_key,
+            SerializedSubjectPublicKey::P256(pub_key) => pub_key,
+        }
+    }
+
+    /// The first 160 bits of the SHA-256 hash of the key, see RFC 7093, 2.
+    fn key_identifier(&self) -> [u8; 20] {
+        use sha2::digest::Digest;
+        let mut identifier = [0u8; 20];
+        identifier.copy_from_slice(&sha2::Sha256::digest(self.as_bytes())[..20]);
+        identifier
+    }
 }
 
 impl Encodable for SerializedSubjectPublicKey {
     fn encoded_length(&self) -> BerResult<BerLength> {
         Ok((match self {
             SerializedSubjectPublicKey::Ed255(_) => 0x2A,
-            SerializedSubjectPublicKey::P256(_) => 0x39,
+            SerializedSubjectPublicKey::P256(_) => 0x59,
         } as u8).into())
     }
 
@@ -577,7 +1288,7 @@ impl Encodable for SerializedSubjectPublicKey {
                     Tag::SEQUENCE,
                     P256_PUB_ENCODING,
                 )?)?;
-                let mut leading_zero = [0u8; 34];
+                let mut leading_zero = [0u8; 66];
                 leading_zero[1..].copy_from_slice(pub_key.as_ref());
                 encoder.encode(&TaggedSlice::from(
                     Tag::BIT_STRING,
@@ -0,0 +1,451 @@
+//! Encrypted backups of everything a client stores.
+//!
+//! A backup is a sequence of chunks, each small enough for one reply. A chunk is the format
+//! version and a header, followed by the postcard serialization of a `Chacha8Poly1305`
+//! encryption reply, made with a key that permits wrapping. The header holds the random ID of
+//! the backup, the chunk's index and whether it is the last one. The associated data binds it,
+//! with the version and a hash of the client ID, to the ciphertext, so chunks can not be
+//! reordered, dropped, replayed or mixed between backups or clients. The encrypted plaintext is
+//! a sequence of entries: a file's location, its path relative to the client's directory, and
+//! its contents from some offset on.
+//!
+//! Restored files are staged, and only moved in place with the last chunk, so an incomplete
+//! backup restores nothing.
+//!
+//! Keys are stored decrypted in the backup, and encrypted again under the key encryption key of
+//! the device they are restored to. Secret keys that are not extractable, and the wrapping key
+//! itself, are left out. Restored keys are not `LOCAL`, as they were imported.
+
+use littlefs2::path::{Path, PathBuf};
+use rand_core::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::digest::Digest as _;
+
+use crate::{
+    api::{reply, request},
+    config::MAX_MESSAGE_LENGTH,
+    error::{Error, Result},
+    key,
+    store::{self, Store},
+    store::keystore::{ClientKeystore, Keystore},
+    types::{BackupCursor, KeyId, Location, LfsStorage, Message, ShortData},
+    Platform,
+};
+
+pub const VERSION: u8 = 2;
+
+const DIRECTORIES: [&str; 6] = ["sec", "pub", "dat", "ctr", "x5c", "x5a"];
+const LOCATIONS: [Location; 3] = [Location::Internal, Location::External, Location::Volatile];
+// within the client's directory, holds the files of an ongoing restore, and its state
+const STAGING: &str = "rst";
+const STAGING_STATE: &str = "rst/state";
+
+// leaves room for the version and header, and for the nonce, tag and lengths around the ciphertext
+const ENTRIES_CAPACITY: usize = MAX_MESSAGE_LENGTH - 80;
+// location, offset and lengths of an entry
+const ENTRY_OVERHEAD: usize = 16;
+// files are only split if at least this much of them fits in the chunk
+const MIN_FRAGMENT: usize = 32;
+
+#[derive(Deserialize, Serialize)]
+struct Header {
+    backup: [u8; 16],
+    index: u32,
+    last: bool,
+}
+
+/// The chunk an ongoing restore expects next.
+#[derive(Deserialize, Serialize, Eq, PartialEq)]
+struct RestoreState {
+    backup: [u8; 16],
+    next: u32,
+}
+
+#[derive(Deserialize, Serialize)]
+struct Entry {
+    location: Location,
+    path: PathBuf,
+    offset: u32,
+    data: Message,
+}
+
+/// Returns the chunk of the client's backup at the cursor, or the first one.
+pub fn backup<P: Platform>(
+    keystore: &mut ClientKeystore<P>,
+    store: impl Store,
+    client_id: &PathBuf,
+    request: &request::Backup,
+) -> Result<reply::Backup> {
+    let cursor = request.cursor.unwrap_or_else(|| {
+        let mut backup = [0u8; 16];
+        keystore.rng().fill_bytes(&mut backup);
+        BackupCursor { backup, ..Default::default() }
+    });
+    let (mut file, mut offset) = (cursor.file, cursor.offset as usize);
+    let mut entries = Message::new();
+    let mut last = false;
+
+    'chunk: loop {
+        // the files from the cursor on, a few at a time
+        let mut paths = heapless::Vec::<(Location, PathBuf), 8>::new();
+        let mut index = 0;
+        for_each_file(store, client_id, |location, path| {
+            if index >= file && paths.push((location, path.clone())).is_err() {
+                return false;
+            }
+            index += 1;
+            true
+        })?;
+        if paths.is_empty() {
+            last = true;
+            break;
+        }
+
+        for (location, path) in paths {
+            if let Some(data) = read_entry(keystore, store, client_id, location, &path, &request.wrapping_key)? {
+                let data = data.get(offset..).ok_or(Error::InternalError)?;
+                let room = ENTRIES_CAPACITY.saturating_sub(entries.len() + ENTRY_OVERHEAD + path.as_ref().len());
+                let fragment = data.len() > room;
+                if fragment && (is_key(&path) || room < MIN_FRAGMENT) {
+                    break 'chunk;
+                }
+
+                let length = data.len().min(room);
+                let entry = Entry {
+                    location,
+                    path,
+                    offset: offset as u32,
+                    data: Message::from_slice(&data[..length]).unwrap(),
+                };
+                let serialized: Message = crate::postcard_serialize_bytes(&entry).map_err(|_| Error::CborError)?;
+                entries.extend_from_slice(&serialized).map_err(|_| Error::InternalError)?;
+
+                if fragment {
+                    offset += length;
+                    break 'chunk;
+                }
+            }
+            file += 1;
+            offset = 0;
+        }
+    }
+
+    let header = Header { backup: cursor.backup, index: cursor.chunk, last };
+    let associated_data = associated_data(client_id, &header)?;
+    let sealed = seal(keystore, &request.wrapping_key, &associated_data, entries)?;
+    let serialized: Message = crate::postcard_serialize_bytes(&sealed).map_err(|_| Error::CborError)?;
+    let mut chunk = Message::new();
+    chunk.push(VERSION).unwrap();
+    let header: Message = crate::postcard_serialize_bytes(&header).map_err(|_| Error::CborError)?;
+    chunk.extend_from_slice(&header).map_err(|_| Error::InternalError)?;
+    chunk.extend_from_slice(&serialized).map_err(|_| Error::InternalError)?;
+
+    let next = match last {
+        true => None,
+        false => Some(BackupCursor { backup: cursor.backup, chunk: cursor.chunk + 1, file, offset: offset as u32 }),
+    };
+    Ok(reply::Backup { chunk, next })
+}
+
+/// Restores one chunk of a backup.
+///
+/// Chunks must be restored in order, starting with the first one, which aborts any ongoing
+/// restore. Chunks out of sequence fail with `BackupChunkOutOfSequence`, and any other failure
+/// aborts the restore. The files are only moved in place with the last chunk. The reply tells
+/// which chunk it was, and whether it was the last one.
+pub fn restore<P: Platform>(
+    keystore: &mut ClientKeystore<P>,
+    store: impl Store,
+    client_id: &PathBuf,
+    request: &request::Restore,
+) -> Result<reply::Restore> {
+    match request.chunk.first() {
+        Some(&VERSION) => {}
+        _ => return Err(Error::InvalidSerializationFormat),
+    }
+    let (header, sealed): (Header, _) = postcard::take_from_bytes(&request.chunk[1..])
+        .map_err(|_| Error::InvalidSerializationFormat)?;
+
+    let expected = RestoreState { backup: header.backup, next: header.index };
+    if header.index == 0 {
+        clear_staging(store, client_id)?;
+    } else if read_restore_state(store, client_id).as_ref() != Some(&expected) {
+        return Err(Error::BackupChunkOutOfSequence);
+    }
+
+    let restored = restore_chunk(keystore, store, client_id, &header, sealed, &request.wrapping_key);
+    if restored.is_err() || header.last {
+        clear_staging(store, client_id)?;
+    }
+    restored?;
+
+    Ok(reply::Restore { index: header.index, last: header.last })
+}
+
+fn restore_chunk<P: Platform>(
+    keystore: &mut ClientKeystore<P>,
+    store: impl Store,
+    client_id: &PathBuf,
+    header: &Header,
+    sealed: &[u8],
+    wrapping_key: &KeyId,
+) -> Result<()> {
+    let sealed: reply::Encrypt = crate::postcard_deserialize(sealed)
+        .map_err(|_| Error::InvalidSerializationFormat)?;
+    let plaintext = open(keystore, wrapping_key, &associated_data(client_id, header)?, sealed)?;
+
+    let staging = staging_directory(client_id);
+    let mut entries = &plaintext[..];
+    while !entries.is_empty() {
+        let (entry, rest): (Entry, _) = postcard::take_from_bytes(entries)
+            .map_err(|_| Error::InvalidSerializationFormat)?;
+        restore_entry(keystore, store, &staging, entry)?;
+        entries = rest;
+    }
+
+    if header.last {
+        return move_staged_files(keystore, store, client_id);
+    }
+    let state = RestoreState { backup: header.backup, next: header.index + 1 };
+    let state: Message = crate::postcard_serialize_bytes(&state).map_err(|_| Error::CborError)?;
+    store::store(store, Location::Volatile, &staging_state_path(client_id), &state)
+}
+
+/// The version, the header, and the SHA-256 hash of the client ID.
+fn associated_data(client_id: &PathBuf, header: &Header) -> Result<ShortData> {
+    let mut associated_data: ShortData = crate::postcard_serialize_bytes(header).map_err(|_| Error::CborError)?;
+    associated_data.insert(0, VERSION).map_err(|_| Error::InternalError)?;
+    let client_id: &str = client_id.as_ref();
+    associated_data.extend_from_slice(&sha2::Sha256::digest(client_id.as_bytes()))
+        .map_err(|_| Error::InternalError)?;
+    Ok(associated_data)
+}
+
+fn staging_directory(client_id: &PathBuf) -> PathBuf {
+    let mut path = client_id.clone();
+    path.push(&PathBuf::from(STAGING));
+    path
+}
+
+fn staging_state_path(client_id: &PathBuf) -> PathBuf {
+    let mut path = client_id.clone();
+    path.push(&PathBuf::from(STAGING_STATE));
+    path
+}
+
+fn read_restore_state(store: impl Store, client_id: &PathBuf) -> Option<RestoreState> {
+    let state: Message = store::read(store, Location::Volatile, &staging_state_path(client_id)).ok()?;
+    crate::postcard_deserialize(&state).ok()
+}
+
+fn clear_staging(store: impl Store, client_id: &PathBuf) -> Result<()> {
+    let staging = staging_directory(client_id);
+    for location in LOCATIONS {
+        store::remove_dir_all_where(store, location, &staging, |_| true)?;
+    }
+    Ok(())
+}
+
+/// Moves the staged files in place, a few at a time.
+fn move_staged_files<P: Platform>(
+    keystore: &mut ClientKeystore<P>,
+    store: impl Store,
+    client_id: &PathBuf,
+) -> Result<()> {
+    let staging = staging_directory(client_id);
+    loop {
+        let mut paths = heapless::Vec::<(Location, PathBuf), 8>::new();
+        for_each_file(store, &staging, |location, path| paths.push((location, path.clone())).is_ok())?;
+        if paths.is_empty() {
+            return Ok(());
+        }
+
+        for (location, path) in paths {
+            let mut staged_path = staging.clone();
+            staged_path.push(&path);
+            let mut full_path = client_id.clone();
+            full_path.push(&path);
+            // keys are encrypted for their path
+            if is_key(&path) {
+                let key = keystore.read_key(location, &staged_path)?;
+                keystore.write_serialized_key(location, &full_path, &key)?;
+            } else {
+                let data: Message = store::read(store, location, &staged_path)?;
+                store::store(store, location, &full_path, &data)?;
+            }
+            store::delete(store, location, &staged_path);
+        }
+    }
+}
+
+/// Calls `f` with the location and relative path of each file to back up, within the client's
+/// (or its staging) directory, in a fixed order, until it returns false.
+fn for_each_file(
+    store: impl Store,
+    root: &PathBuf,
+    mut f: impl FnMut(Location, &PathBuf) -> bool,
+) -> Result<()> {
+    fn walk<S: LfsStorage>(
+        fs: &littlefs2::fs::Filesystem<'static, S>,
+        path: &Path,
+        relative: &PathBuf,
+        location: Location,
+        f: &mut dyn FnMut(Location, &PathBuf) -> bool,
+    ) -> littlefs2::io::Result<bool> {
+        fs.read_dir_and_then(path, |dir| {
+            // skip over `.` and `..`
+            for entry in dir.skip(2) {
+                let entry = entry?;
+                let mut relative = relative.clone();
+                relative.push(entry.file_name());
+                let more = match entry.file_type().is_dir() {
+                    true => walk(fs, entry.path(), &relative, location, f)?,
+                    false => f(location, &relative),
+                };
+                if !more {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        })
+    }
+
+    for location in LOCATIONS {
+        for directory in DIRECTORIES {
+            let relative = PathBuf::from(directory);
+            let mut path = root.clone();
+            path.push(&relative);
+            if !store::exists(store, location, &path) {
+                continue;
+            }
+            let more = match location {
+                Location::Internal => walk(store.ifs(), &path, &relative, location, &mut f),
+                Location::External => walk(store.efs(), &path, &relative, location, &mut f),
+                Location::Volatile => walk(store.vfs(), &path, &relative, location, &mut f),
+            }.map_err(|_| Error::FilesystemReadFailure)?;
+            if !more {
+                return Ok(());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn is_key(path: &PathBuf) -> bool {
+    let path: &str = path.as_ref();
+    path.starts_with("sec/") || path.starts_with("pub/")
+}
+
+/// Checks that the path of a restored file stays within the client's backed up directories.
+fn is_restorable(path: &PathBuf) -> bool {
+    let path: &str = path.as_ref();
+    DIRECTORIES.iter().any(|directory| matches!(path.strip_prefix(directory), Some(rest) if rest.starts_with('/')))
+        && !path.split('/').any(|component| component.is_empty() || component == "." || component == "..")
+}
+
+/// Reads the contents of a file to back up, or `None` if it is left out.
+fn read_entry<P: Platform>(
+    keystore: &mut ClientKeystore<P>,
+    store: impl Store,
+    client_id: &PathBuf,
+    location: Location,
+    path: &PathBuf,
+    wrapping_key: &KeyId,
+) -> Result<Option<Message>> {
+    let mut full_path = client_id.clone();
+    full_path.push(path);
+
+    if !is_key(path) {
+        return store::read(store, location, &full_path).map(Some);
+    }
+
+    let key = keystore.read_key(location, &full_path)?;
+    let secret = path.as_ref().starts_with("sec/");
+    if secret && !key.flags.contains(key::Flags::EXTRACTABLE) {
+        return Ok(None);
+    }
+    if secret && full_path == keystore.key_path(key::Secrecy::Secret, wrapping_key) {
+        return Ok(None);
+    }
+    Ok(Some(Message::from_slice(&key.serialize()).unwrap()))
+}
+
+/// Stages an entry of a backup.
+fn restore_entry<P: Platform>(
+    keystore: &mut ClientKeystore<P>,
+    store: impl Store,
+    staging: &PathBuf,
+    entry: Entry,
+) -> Result<()> {
+    if !is_restorable(&entry.path) {
+        return Err(Error::InvalidSerializationFormat);
+    }
+    let mut full_path = staging.clone();
+    full_path.push(&entry.path);
+
+    if is_key(&entry.path) {
+        if entry.offset != 0 {
+            return Err(Error::InvalidSerializationFormat);
+        }
+        let mut key = key::Key::try_deserialize(&entry.data)?;
+        // like unwrapped keys, restored keys are neither local nor never extractable anymore
+        key.flags &= key::Flags::EXTRACTABLE | key::Flags::SENSITIVE;
+        return keystore.write_serialized_key(entry.location, &full_path, &key);
+    }
+
+    if entry.offset == 0 {
+        return store::store(store, entry.location, &full_path, &entry.data);
+    }
+    // continues a file split across chunks
+    let mut data: Message = store::read(store, entry.location, &full_path)?;
+    if data.len() != entry.offset as usize {
+        return Err(Error::InvalidSerializationFormat);
+    }
+    data.extend_from_slice(&entry.data).map_err(|_| Error::InvalidSerializationFormat)?;
+    store::write(store, entry.location, &full_path, &data)
+}
+
+#[cfg(feature = "chacha8-poly1305")]
+fn seal(keystore: &mut impl Keystore, wrapping_key: &KeyId, associated_data: &ShortData, plaintext: Message)
+    -> Result<reply::Encrypt>
+{
+    let request = request::Encrypt {
+        mechanism: crate::types::Mechanism::Chacha8Poly1305,
+        key: *wrapping_key,
+        message: plaintext,
+        associated_data: associated_data.clone(),
+        nonce: None,
+    };
+    crate::mechanisms::Chacha8Poly1305::encrypt_with_usage(keystore, &request, key::Usage::WRAP)
+}
+
+#[cfg(feature = "chacha8-poly1305")]
+fn open(keystore: &mut impl Keystore, wrapping_key: &KeyId, associated_data: &ShortData, sealed: reply::Encrypt)
+    -> Result<Message>
+{
+    let request = request::Decrypt {
+        mechanism: crate::types::Mechanism::Chacha8Poly1305,
+        key: *wrapping_key,
+        message: sealed.ciphertext,
+        associated_data: Message::from_slice(associated_data).unwrap(),
+        nonce: sealed.nonce,
+        tag: sealed.tag,
+    };
+    crate::mechanisms::Chacha8Poly1305::decrypt_with_usage(keystore, &request, key::Usage::UNWRAP)?
+        .plaintext
+        .ok_or(Error::AeadError)
+}
+
+#[cfg(not(feature = "chacha8-poly1305"))]
+fn seal(_keystore: &mut impl Keystore, _wrapping_key: &KeyId, _associated_data: &ShortData, _plaintext: Message)
+    -> Result<reply::Encrypt>
+{
+    Err(Error::MechanismNotAvailable)
+}
+
+#[cfg(not(feature = "chacha8-poly1305"))]
+fn open(_keystore: &mut impl Keystore, _wrapping_key: &KeyId, _associated_data: &ShortData, _sealed: reply::Encrypt)
+    -> Result<Message>
+{
+    Err(Error::MechanismNotAvailable)
+}
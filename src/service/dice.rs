@@ -0,0 +1,137 @@
+//! A layered DICE identity, following the TCG DICE Layering Architecture.
+//!
+//! The boot ROM computes the layer-0 Compound Device Identifier (CDI), the HMAC-SHA256 of the
+//! firmware measurement keyed with the unique device secret, and passes it on instead of the
+//! secret. Both the DeviceID and the Alias keypair are derived from the CDI, so firmware that
+//! measures differently gets different keys, and cannot speak for the device. A firmware update
+//! therefore changes the DeviceID too, and its certificate has to be vouched for again.
+//!
+//! The DeviceID certificate is self-signed. The Alias certificate is issued by the DeviceID key,
+//! and carries the measurement in a TCG DiceTcbInfo extension. Both keys are Ed255, derived again
+//! for each request, and only stored in volatile storage of the reserved client "dice", under
+//! the key encryption key like all other keys.
+//!
+//! Anyone may read the certificates, but signing with the Alias key speaks for the device, so
+//! only endpoints added as DICE signers may make signing requests.
+
+use core::fmt::Write as _;
+
+use hex_literal::hex;
+use hmac::{Hmac, Mac, NewMac};
+use sha2::digest::Digest as _;
+
+use crate::{
+    api::{reply, request, Request},
+    error::{Error, Result},
+    key,
+    mechanisms,
+    platform::DiceInputs,
+    service::{attest, Sign},
+    store::keystore::ClientKeystore,
+    types::{
+        BasicConstraints, CertificateTemplate, DistinguishedName, KeyId, KeyUsage, Location, Mechanism,
+        Message, NameAttribute, SignatureSerialization,
+    },
+    Platform,
+};
+
+pub const DICE_CLIENT_ID: &str = "dice";
+
+pub const DEVICE_ID_KEY: KeyId = KeyId::from_special(1);
+pub const ALIAS_KEY: KeyId = KeyId::from_special(2);
+
+// 2.23.133.5.4.1 tcg-dice-TcbInfo
+const TCB_INFO_OID: &[u8] = &hex!("67 81 05 05 04 01");
+// a DiceTcbInfo of just the fwids [6], with one SHA-256 (2.16.840.1.101.3.4.2.1) FWID, up to its digest
+const TCB_INFO_PREFIX: &[u8] = &hex!("30 31 A6 2F 30 2D 06 09 60 86 48 01 65 03 04 02 01 04 20");
+
+/// Returns the Alias certificate, followed by the DeviceID certificate.
+pub fn certificates<P: Platform>(dice_keystore: &mut ClientKeystore<P>, inputs: &DiceInputs)
+    -> Result<reply::DiceCertificates>
+{
+    let (device_id_public_key, alias_public_key) = derive_keys(dice_keystore, inputs)?;
+    let device_id_name = name("DeviceID", &device_id_public_key);
+
+    let device_id_template = CertificateTemplate::default()
+        .set_subject(device_id_name.clone())
+        .set_key_usage(KeyUsage::KEY_CERT_SIGN)
+        .set_basic_constraints(BasicConstraints { ca: true, path_length: None });
+    let device_id_certificate = attest::certify(
+        dice_keystore, Mechanism::Ed255, DEVICE_ID_KEY, &device_id_name, DEVICE_ID_KEY, &device_id_template,
+    )?;
+
+    let mut tcb_info = heapless::Vec::<u8, 64>::from_slice(TCB_INFO_PREFIX).unwrap();
+    tcb_info.extend_from_slice(&inputs.measurement).unwrap();
+    // not critical, so verifiers that do not know it still accept the certificate
+    let alias_template = CertificateTemplate::default()
+        .set_subject(name("Alias", &alias_public_key))
+        .set_key_usage(KeyUsage::DIGITAL_SIGNATURE)
+        .add_extension(TCB_INFO_OID, false, &tcb_info);
+    let alias_certificate = attest::certify(
+        dice_keystore, Mechanism::Ed255, DEVICE_ID_KEY, &device_id_name, ALIAS_KEY, &alias_template,
+    )?;
+
+    let mut chain = alias_certificate;
+    chain.extend_from_slice(&device_id_certificate).map_err(|_| Error::InternalError)?;
+    Ok(reply::DiceCertificates { chain })
+}
+
+/// Whether only DICE signer endpoints may make this request.
+pub fn is_signing_request(request: &Request) -> bool {
+    matches!(request, Request::DiceSign(_))
+}
+
+/// Signs with the Alias key.
+pub fn sign<P: Platform>(dice_keystore: &mut ClientKeystore<P>, inputs: &DiceInputs, request: &request::DiceSign)
+    -> Result<reply::DiceSign>
+{
+    derive_keys(dice_keystore, inputs)?;
+    let signature = mechanisms::Ed255::sign(dice_keystore, &request::Sign {
+        mechanism: Mechanism::Ed255,
+        key: ALIAS_KEY,
+        message: Message::from_slice(&request.message).unwrap(),
+        format: SignatureSerialization::Raw,
+    })?.signature;
+    Ok(reply::DiceSign { signature })
+}
+
+/// Stores the DeviceID and Alias keypairs, and returns their public keys.
+fn derive_keys<P: Platform>(dice_keystore: &mut ClientKeystore<P>, inputs: &DiceInputs)
+    -> Result<([u8; 32], [u8; 32])>
+{
+    let cdi = &inputs.compound_device_identifier;
+    let device_id_seed = hmac_sha256(cdi, b"DeviceID");
+    let alias_seed = hmac_sha256(cdi, b"Alias");
+
+    let mut public_keys = [[0u8; 32]; 2];
+    for ((id, seed), public_key) in [(DEVICE_ID_KEY, device_id_seed), (ALIAS_KEY, alias_seed)].iter().zip(public_keys.iter_mut()) {
+        let keypair = salty::signature::Keypair::from(seed);
+        public_key.copy_from_slice(keypair.public.as_bytes());
+        dice_keystore.store_keypair_at(
+            Location::Volatile,
+            id,
+            key::Info::from(key::Kind::Ed255).with_local_flag(),
+            seed,
+            public_key,
+        )?;
+    }
+    Ok((public_keys[0], public_keys[1]))
+}
+
+fn hmac_sha256(key: &[u8; 32], message: &[u8]) -> [u8; 32] {
+    let mut mac = Hmac::<sha2::Sha256>::new_from_slice(key).unwrap();
+    mac.update(message);
+    mac.finalize().into_bytes().into()
+}
+
+/// The common name is the hex encoded key identifier, as in the Open Profile for DICE.
+fn name(unit: &str, public_key: &[u8; 32]) -> DistinguishedName {
+    let mut common_name = NameAttribute::new();
+    for byte in &sha2::Sha256::digest(public_key)[..20] {
+        write!(common_name, "{:02x}", byte).unwrap();
+    }
+    DistinguishedName::default()
+        .set_organization("Trussed")
+        .set_organizational_unit(unit)
+        .set_common_name(&common_name)
+}
@@ -0,0 +1,175 @@
+//! Provisioning of the attestation keys and their certificates.
+//!
+//! The attestation keys live in the keystore of the reserved client "attn", at the special
+//! IDs `ED255_ATTN_KEY` and `P256_ATTN_KEY`. Only endpoints added as provisioners may make
+//! provisioning requests, and once provisioning is locked, they fail forever.
+
+use core::convert::TryInto;
+
+use littlefs2::path::PathBuf;
+use rand_core::RngCore;
+
+use crate::{
+    api::{reply, request, Request},
+    error::{Error, Result},
+    key,
+    service::attest::{ED255_ATTN_KEY, P256_ATTN_KEY},
+    store::{self, Store},
+    store::keystore::{ClientKeystore, Keystore},
+    types::{KeyId, Location, Mechanism, Message, ShortData},
+    x509,
+    Platform,
+};
+
+pub const ATTESTATION_CLIENT_ID: &str = "attn";
+
+/// Whether only provisioner endpoints may make this request.
+pub fn is_provisioning_request(request: &Request) -> bool {
+    matches!(request,
+        Request::InstallAttestationCertificates(_)
+        | Request::LockProvisioning(_)
+        | Request::ProvisionAttestationKey(_)
+    )
+}
+
+/// Generates or injects the attestation key for the mechanism, replacing the previous key and
+/// dropping its certificates.
+pub fn provision_attestation_key<P: Platform>(
+    attn_keystore: &mut ClientKeystore<P>,
+    store: impl Store,
+    request: &request::ProvisionAttestationKey,
+) -> Result<reply::ProvisionAttestationKey> {
+    check_unlocked(store)?;
+    let (id, kind) = attestation_key(request.mechanism)?;
+
+    // generated keys are `LOCAL`, like in `GenerateKey`
+    let mut info = key::Info::from(kind);
+    if request.raw_key.is_none() {
+        info = info.with_local_flag();
+    }
+
+    let raw_key: Option<[u8; 32]> = request.raw_key.as_ref()
+        .map(|raw_key| raw_key.as_slice().try_into().map_err(|_| Error::InvalidSerializedKey))
+        .transpose()?;
+
+    let public_key = match kind {
+        key::Kind::Ed255 => {
+            // any 32 bytes are a valid seed
+            let seed = raw_key.unwrap_or_else(|| {
+                let mut seed = [0u8; 32];
+                attn_keystore.rng().fill_bytes(&mut seed);
+                seed
+            });
+            let keypair = salty::signature::Keypair::from(&seed);
+            let public_key = keypair.public.as_bytes();
+            attn_keystore.store_keypair_at(Location::Internal, &id, info, &seed, public_key)?;
+            ShortData::from_slice(public_key).unwrap()
+        }
+        _ => {
+            let secret_key = match raw_key {
+                // scalar must be in range 1..=n-1
+                // (the non-Cortex-M4 fallback lets zero pass)
+                Some(scalar) if scalar == [0u8; 32] => return Err(Error::InvalidSerializedKey),
+                Some(scalar) => p256_cortex_m4::SecretKey::from_bytes(scalar)
+                    .map_err(|_| Error::InvalidSerializedKey)?,
+                None => p256_cortex_m4::Keypair::random(attn_keystore.rng()).secret,
+            };
+            let public_key = secret_key.public_key();
+            attn_keystore.store_keypair_at(
+                Location::Internal,
+                &id,
+                info,
+                &unsafe { secret_key.to_bytes() },
+                &public_key.to_compressed_sec1_bytes(),
+            )?;
+            // without the leading 0x04
+            ShortData::from_slice(&public_key.to_uncompressed_sec1_bytes()[1..]).unwrap()
+        }
+    };
+
+    // the certificates are for the previous key
+    store::delete(store, Location::Internal, &chain_path(id));
+
+    Ok(reply::ProvisionAttestationKey { public_key })
+}
+
+/// Installs the certificate chain of the attestation key for the mechanism.
+///
+/// The first certificate must be for the attestation key, and each certificate must be issued
+/// and signed by the next one. Whether the chain ends with a trusted root is up to the verifier.
+pub fn install_attestation_certificates<P: Platform>(
+    attn_keystore: &mut ClientKeystore<P>,
+    store: impl Store,
+    request: &request::InstallAttestationCertificates,
+) -> Result<reply::InstallAttestationCertificates> {
+    check_unlocked(store)?;
+    let (id, kind) = attestation_key(request.mechanism)?;
+
+    let public_key = attn_keystore.load_key(key::Secrecy::Public, Some(kind), &id)?;
+    let chain = x509::parse_chain(&request.chain)?;
+    if !matches_key(kind, &public_key.material, &chain[0].public_key) {
+        return Err(Error::InvalidCertificate);
+    }
+    let linked = chain.windows(2).all(|pair| {
+        pair[0].issuer == pair[1].subject && pair[0].is_signed_by(&pair[1].public_key)
+    });
+    if !linked {
+        return Err(Error::UntrustedCertificate);
+    }
+
+    store::store(store, Location::Internal, &chain_path(id), &request.chain)?;
+    Ok(reply::InstallAttestationCertificates {})
+}
+
+/// Permanently refuses any further provisioning.
+pub fn lock_provisioning(store: impl Store) -> Result<reply::LockProvisioning> {
+    store::store(store, Location::Internal, &locked_path(), &[])?;
+    Ok(reply::LockProvisioning {})
+}
+
+/// The installed certificate chain of the attestation key for the mechanism, if any.
+pub fn attestation_chain(store: impl Store, mechanism: Mechanism) -> Option<Message> {
+    let (id, _) = attestation_key(mechanism).ok()?;
+    store::read(store, Location::Internal, &chain_path(id)).ok()
+}
+
+fn check_unlocked(store: impl Store) -> Result<()> {
+    match store::exists(store, Location::Internal, &locked_path()) {
+        true => Err(Error::ProvisioningLocked),
+        false => Ok(()),
+    }
+}
+
+fn attestation_key(mechanism: Mechanism) -> Result<(KeyId, key::Kind)> {
+    match mechanism {
+        Mechanism::Ed255 => Ok((ED255_ATTN_KEY, key::Kind::Ed255)),
+        Mechanism::P256 => Ok((P256_ATTN_KEY, key::Kind::P256)),
+        _ => Err(Error::MechanismNotAvailable),
+    }
+}
+
+/// Public halves are stored like in `GenerateKey`: Ed255 keys raw, P256 keys compressed.
+fn matches_key(kind: key::Kind, material: &[u8], certified: &x509::PublicKey<'_>) -> bool {
+    match (kind, certified) {
+        (key::Kind::Ed255, x509::PublicKey::Ed255(certified)) => certified[..] == *material,
+        (key::Kind::P256, x509::PublicKey::P256(certified)) => {
+            p256_cortex_m4::PublicKey::from_sec1_bytes(certified)
+                .map(|certified| certified.to_compressed_sec1_bytes()[..] == *material)
+                .unwrap_or(false)
+        }
+        _ => false,
+    }
+}
+
+fn locked_path() -> PathBuf {
+    let mut path = PathBuf::from(ATTESTATION_CLIENT_ID);
+    path.push(&PathBuf::from("locked"));
+    path
+}
+
+fn chain_path(id: KeyId) -> PathBuf {
+    let mut path = PathBuf::from(ATTESTATION_CLIENT_ID);
+    path.push(&PathBuf::from("x5c"));
+    path.push(&PathBuf::from(id.hex().as_slice()));
+    path
+}
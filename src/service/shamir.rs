@@ -0,0 +1,598 @@
+//! Splitting keys into shares, any `threshold` of which recombine to the key.
+//!
+//! This is Shamir's secret sharing over GF(256) as specified by SLIP-0039, including the digest
+//! share that lets recombination detect wrong or mismatched shares. Shares come in two formats:
+//!
+//! - `Raw`: the threshold, the share's x-coordinate, and its value.
+//! - `Slip39`: a single-group SLIP-0039 share of the key, encrypted with the empty passphrase,
+//!   as the big-endian `u16` indices of its mnemonic words. The wordlist is left to the host.
+
+use heapless::Vec;
+use hmac::{Hmac, Mac, NewMac};
+use rand_core::RngCore;
+use sha2::Sha256;
+
+use crate::{
+    api::{reply, request},
+    config::MAX_SHARES,
+    error::{Error, Result},
+    key,
+    store::keystore::Keystore,
+    types::{ShareFormat, ShortData},
+};
+
+/// Secrets are between 16 and 64 bytes long, of even length.
+type Secret = Vec<u8, 64>;
+
+const DIGEST_INDEX: u8 = 254;
+const SECRET_INDEX: u8 = 255;
+const DIGEST_LENGTH: usize = 4;
+
+// SLIP-0039 parameters: 15 bit identifier, then the extendable flag and 4 bit iteration exponent
+const BASE_ITERATION_COUNT: u32 = 10000;
+const ROUND_COUNT: u8 = 4;
+const ITERATION_EXPONENT: u8 = 0;
+const CUSTOMIZATION: &[u8] = b"shamir";
+const CUSTOMIZATION_EXTENDABLE: &[u8] = b"shamir_extendable";
+// identifier, flags and share parameters, and the checksum
+const HEADER_WORDS: usize = 4;
+const CHECKSUM_WORDS: usize = 3;
+
+pub fn split_key(keystore: &mut impl Keystore, request: &request::SplitKey) -> Result<reply::SplitKey> {
+    let (threshold, count) = (request.threshold, request.shares);
+    if threshold == 0 || threshold > count || count as usize > MAX_SHARES || (threshold == 1 && count > 1) {
+        return Err(Error::InvalidShareThreshold);
+    }
+
+    let key = keystore.load_extractable_key(&request.key)?;
+    match key.kind {
+        key::Kind::Shared(_) | key::Kind::Symmetric(_) | key::Kind::Ed255 | key::Kind::P256 | key::Kind::X255 => {}
+        _ => return Err(Error::WrongKeyKind),
+    }
+    let length = key.material.len();
+    if !(16..=64).contains(&length) || length % 2 == 1 {
+        return Err(Error::WrongKeyKind);
+    }
+    let secret = Secret::from_slice(&key.material).unwrap();
+
+    let mut shares = Vec::new();
+    match request.format {
+        ShareFormat::Raw => {
+            for (x, value) in split_secret(keystore.rng(), threshold, count, &secret)? {
+                let mut share = ShortData::new();
+                share.extend_from_slice(&[threshold, x]).unwrap();
+                share.extend_from_slice(&value).unwrap();
+                shares.push(share).unwrap();
+            }
+        }
+        ShareFormat::Slip39 => {
+            let identifier = (keystore.rng().next_u32() & 0x7fff) as u16;
+            let parameters = Parameters { identifier, extendable: false, iteration_exponent: ITERATION_EXPONENT, threshold };
+            let encrypted = feistel(&secret, b"", &parameters, true);
+            for (x, value) in split_secret(keystore.rng(), threshold, count, &encrypted)? {
+                shares.push(encode_mnemonic(&parameters, x, &value)).unwrap();
+            }
+        }
+    }
+    Ok(reply::SplitKey { shares })
+}
+
+/// Recombines shares into the raw key, to be injected with the requested mechanism.
+pub fn combine_shares(request: &request::CombineKey) -> Result<Secret> {
+    match request.format {
+        ShareFormat::Raw => {
+            let mut threshold = None;
+            let mut shares = Vec::<(u8, Secret), MAX_SHARES>::new();
+            for share in request.shares.iter() {
+                if share.len() < 2 || *threshold.get_or_insert(share[0]) != share[0] {
+                    return Err(Error::InvalidShares);
+                }
+                let value = Secret::from_slice(&share[2..]).map_err(|_| Error::InvalidShares)?;
+                shares.push((share[1], value)).map_err(|_| Error::InvalidShares)?;
+            }
+            recover_secret(threshold.ok_or(Error::InvalidShares)?, &shares)
+        }
+        ShareFormat::Slip39 => {
+            let (parameters, encrypted) = combine_mnemonics(request.shares.iter().map(|share| &share[..]))?;
+            if parameters.iteration_exponent != ITERATION_EXPONENT {
+                // the cost grows exponentially, do not let shares ask for more
+                return Err(Error::InvalidShares);
+            }
+            Ok(feistel(&encrypted, b"", &parameters, false))
+        }
+    }
+}
+
+/// Recombines SLIP-0039 shares into the encrypted secret.
+fn combine_mnemonics<'a>(shares: impl Iterator<Item = &'a [u8]>) -> Result<(Parameters, Secret)> {
+    let mut parameters = None;
+    let mut values = Vec::<(u8, Secret), MAX_SHARES>::new();
+    for share in shares {
+        let (share_parameters, x, value) = decode_mnemonic(share)?;
+        if *parameters.get_or_insert(share_parameters) != share_parameters {
+            return Err(Error::InvalidShares);
+        }
+        values.push((x, value)).map_err(|_| Error::InvalidShares)?;
+    }
+    let parameters = parameters.ok_or(Error::InvalidShares)?;
+    let encrypted = recover_secret(parameters.threshold, &values)?;
+    Ok((parameters, encrypted))
+}
+
+// GF(256) with the AES polynomial, as log and exp tables for the generator 3
+const fn tables() -> ([u8; 255], [u8; 256]) {
+    let mut exp = [0u8; 255];
+    let mut log = [0u8; 256];
+    let mut x: u16 = 1;
+    let mut i = 0;
+    while i < 255 {
+        exp[i] = x as u8;
+        log[x as usize] = i as u8;
+        x ^= x << 1;
+        if x & 0x100 != 0 {
+            x ^= 0x11b;
+        }
+        i += 1;
+    }
+    (exp, log)
+}
+
+const EXP: [u8; 255] = tables().0;
+const LOG: [u8; 256] = tables().1;
+
+/// Evaluates at `x` the polynomial of lowest degree through the shares.
+fn interpolate(shares: &[(u8, Secret)], x: u8) -> Result<Secret> {
+    if let Some((_, value)) = shares.iter().find(|(share_x, _)| *share_x == x) {
+        return Ok(value.clone());
+    }
+    let length = shares.first().ok_or(Error::InvalidShares)?.1.len();
+    if shares.iter().any(|(_, value)| value.len() != length) {
+        return Err(Error::InvalidShares);
+    }
+
+    let log_product: i32 = shares.iter().map(|(share_x, _)| LOG[(share_x ^ x) as usize] as i32).sum();
+    let mut result = Secret::new();
+    result.resize_default(length).unwrap();
+    for (share_x, value) in shares {
+        let log_basis = log_product
+            - LOG[(share_x ^ x) as usize] as i32
+            - shares.iter().map(|(other_x, _)| LOG[(share_x ^ other_x) as usize] as i32).sum::<i32>();
+        let log_basis = log_basis.rem_euclid(255);
+        for (result, byte) in result.iter_mut().zip(value.iter()) {
+            if *byte != 0 {
+                *result ^= EXP[((LOG[*byte as usize] as i32 + log_basis) % 255) as usize];
+            }
+        }
+    }
+    Ok(result)
+}
+
+fn digest(random: &[u8], secret: &[u8]) -> [u8; DIGEST_LENGTH] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(random).unwrap();
+    mac.update(secret);
+    let mut digest = [0u8; DIGEST_LENGTH];
+    digest.copy_from_slice(&mac.finalize().into_bytes()[..DIGEST_LENGTH]);
+    digest
+}
+
+fn split_secret(rng: &mut impl RngCore, threshold: u8, count: u8, secret: &Secret)
+    -> Result<Vec<(u8, Secret), MAX_SHARES>>
+{
+    let mut shares = Vec::<(u8, Secret), MAX_SHARES>::new();
+    if threshold == 1 {
+        shares.push((0, secret.clone())).map_err(|_| Error::InvalidShareThreshold)?;
+        return Ok(shares);
+    }
+
+    // the polynomial goes through `threshold - 2` random shares, the digest and the secret
+    let mut base = Vec::<(u8, Secret), { MAX_SHARES + 2 }>::new();
+    for x in 0..threshold - 2 {
+        let mut value = Secret::new();
+        value.resize_default(secret.len()).unwrap();
+        rng.fill_bytes(&mut value);
+        base.push((x, value)).unwrap();
+    }
+    let mut digest_share = Secret::new();
+    digest_share.resize_default(secret.len()).unwrap();
+    rng.fill_bytes(&mut digest_share[DIGEST_LENGTH..]);
+    let digest = digest(&digest_share[DIGEST_LENGTH..], secret);
+    digest_share[..DIGEST_LENGTH].copy_from_slice(&digest);
+    base.push((DIGEST_INDEX, digest_share)).unwrap();
+    base.push((SECRET_INDEX, secret.clone())).unwrap();
+
+    for x in 0..count {
+        let value = interpolate(&base, x)?;
+        shares.push((x, value)).map_err(|_| Error::InvalidShareThreshold)?;
+    }
+    Ok(shares)
+}
+
+fn recover_secret(threshold: u8, shares: &[(u8, Secret)]) -> Result<Secret> {
+    if threshold == 0 || shares.len() < threshold as usize {
+        return Err(Error::InvalidShares);
+    }
+    let shares = &shares[..threshold as usize];
+    if shares.iter().enumerate().any(|(i, (x, _))| shares[..i].iter().any(|(other_x, _)| other_x == x)) {
+        return Err(Error::InvalidShares);
+    }
+    if threshold == 1 {
+        return Ok(shares[0].1.clone());
+    }
+
+    let secret = interpolate(shares, SECRET_INDEX)?;
+    let digest_share = interpolate(shares, DIGEST_INDEX)?;
+    if digest(&digest_share[DIGEST_LENGTH..], &secret) != digest_share[..DIGEST_LENGTH] {
+        return Err(Error::InvalidShares);
+    }
+    Ok(secret)
+}
+
+/// Encrypts or decrypts the secret with the passphrase, as SLIP-0039 specifies.
+///
+/// Keys are always shared with the empty passphrase, which may have up to 31 bytes.
+fn feistel(secret: &Secret, passphrase: &[u8], parameters: &Parameters, encrypt: bool) -> Secret {
+    let half = secret.len() / 2;
+    let mut left = Secret::from_slice(&secret[..half]).unwrap();
+    let mut right = Secret::from_slice(&secret[half..]).unwrap();
+
+    let mut salt = Vec::<u8, { 8 + 32 }>::new();
+    if !parameters.extendable {
+        salt.extend_from_slice(CUSTOMIZATION).unwrap();
+        salt.extend_from_slice(&parameters.identifier.to_be_bytes()).unwrap();
+    }
+    let salt_length = salt.len();
+
+    for round in 0..ROUND_COUNT {
+        let round = match encrypt {
+            true => round,
+            false => ROUND_COUNT - 1 - round,
+        };
+        salt.truncate(salt_length);
+        salt.extend_from_slice(&right).unwrap();
+        let mut password = Vec::<u8, 32>::new();
+        password.push(round).unwrap();
+        password.extend_from_slice(passphrase).unwrap();
+        let iterations = (BASE_ITERATION_COUNT << parameters.iteration_exponent) / ROUND_COUNT as u32;
+        let f = pbkdf2_block(&password, &salt, iterations);
+        for (left, f) in left.iter_mut().zip(f.iter()) {
+            *left ^= f;
+        }
+        core::mem::swap(&mut left, &mut right);
+    }
+
+    let mut result = right;
+    result.extend_from_slice(&left).unwrap();
+    result
+}
+
+/// The first block of PBKDF2-HMAC-SHA256, enough for keys of up to 32 bytes.
+fn pbkdf2_block(password: &[u8], salt: &[u8], iterations: u32) -> [u8; 32] {
+    let prf = Hmac::<Sha256>::new_from_slice(password).unwrap();
+    let mut mac = prf.clone();
+    mac.update(salt);
+    mac.update(&1u32.to_be_bytes());
+    let mut u: [u8; 32] = mac.finalize().into_bytes().into();
+    let mut t = u;
+    for _ in 1..iterations {
+        let mut mac = prf.clone();
+        mac.update(&u);
+        u = mac.finalize().into_bytes().into();
+        for (t, u) in t.iter_mut().zip(u.iter()) {
+            *t ^= u;
+        }
+    }
+    t
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+struct Parameters {
+    identifier: u16,
+    extendable: bool,
+    iteration_exponent: u8,
+    threshold: u8,
+}
+
+fn rs1024_polymod(customization: &[u8], words: &[u16]) -> u32 {
+    const GENERATOR: [u32; 10] = [
+        0xe0e040, 0x1c1c080, 0x3838100, 0x7070200, 0xe0e0009,
+        0x1c0c2412, 0x38086c24, 0x3090fc48, 0x21b1f890, 0x3f3f120,
+    ];
+    let mut checksum: u32 = 1;
+    for value in customization.iter().map(|byte| *byte as u32).chain(words.iter().map(|word| *word as u32)) {
+        let top = checksum >> 20;
+        checksum = ((checksum & 0xfffff) << 10) ^ value;
+        for (i, generator) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                checksum ^= generator;
+            }
+        }
+    }
+    checksum
+}
+
+fn customization(extendable: bool) -> &'static [u8] {
+    match extendable {
+        false => CUSTOMIZATION,
+        true => CUSTOMIZATION_EXTENDABLE,
+    }
+}
+
+/// Packs bits into 10 bit mnemonic words.
+struct Words {
+    words: Vec<u16, 64>,
+    accumulator: u32,
+    bits: u32,
+}
+
+impl Words {
+    fn push(&mut self, value: u32, bits: u32) {
+        self.accumulator = (self.accumulator << bits) | value;
+        self.bits += bits;
+        while self.bits >= 10 {
+            self.bits -= 10;
+            self.words.push(((self.accumulator >> self.bits) & 0x3ff) as u16).unwrap();
+        }
+        self.accumulator &= (1 << self.bits) - 1;
+    }
+}
+
+fn encode_mnemonic(parameters: &Parameters, x: u8, value: &Secret) -> ShortData {
+    let mut words = Words { words: Vec::new(), accumulator: 0, bits: 0 };
+    words.push(parameters.identifier as u32, 15);
+    words.push(parameters.extendable as u32, 1);
+    words.push(parameters.iteration_exponent as u32, 4);
+    // group index, group threshold - 1 and group count - 1 of the single group
+    words.push(0, 12);
+    words.push(x as u32, 4);
+    words.push(parameters.threshold as u32 - 1, 4);
+    let bits = 8 * value.len() as u32;
+    words.push(0, (10 - bits % 10) % 10);
+    for byte in value {
+        words.push(*byte as u32, 8);
+    }
+
+    let mut words = words.words;
+    let length = words.len();
+    words.extend_from_slice(&[0; CHECKSUM_WORDS]).unwrap();
+    let checksum = rs1024_polymod(customization(parameters.extendable), &words) ^ 1;
+    for i in 0..CHECKSUM_WORDS {
+        words[length + i] = ((checksum >> (10 * (CHECKSUM_WORDS - 1 - i))) & 0x3ff) as u16;
+    }
+
+    let mut share = ShortData::new();
+    for word in words {
+        share.extend_from_slice(&word.to_be_bytes()).unwrap();
+    }
+    share
+}
+
+fn decode_mnemonic(share: &[u8]) -> Result<(Parameters, u8, Secret)> {
+    if share.len() % 2 == 1 {
+        return Err(Error::InvalidShares);
+    }
+    let mut words = Vec::<u16, 64>::new();
+    for word in share.chunks(2) {
+        let word = u16::from_be_bytes([word[0], word[1]]);
+        if word >= 1024 {
+            return Err(Error::InvalidShares);
+        }
+        words.push(word).map_err(|_| Error::InvalidShares)?;
+    }
+    if words.len() < HEADER_WORDS + CHECKSUM_WORDS + 13 {
+        return Err(Error::InvalidShares);
+    }
+
+    let header = words[..HEADER_WORDS].iter().fold(0u64, |header, word| (header << 10) | *word as u64);
+    let field = |offset: u32, bits: u32| ((header >> (40 - offset - bits)) & ((1 << bits) - 1)) as u16;
+    let extendable = field(15, 1) == 1;
+    if rs1024_polymod(customization(extendable), &words) != 1 {
+        return Err(Error::InvalidShares);
+    }
+    let parameters = Parameters {
+        identifier: field(0, 15),
+        extendable,
+        iteration_exponent: field(16, 4) as u8,
+        threshold: field(36, 4) as u8 + 1,
+    };
+    // only the single group SLIP-0039 shares created by `split_key`
+    if field(20, 12) != 0 {
+        return Err(Error::InvalidShares);
+    }
+    let x = field(32, 4) as u8;
+
+    let value_words = &words[HEADER_WORDS..words.len() - CHECKSUM_WORDS];
+    let bits = 10 * value_words.len();
+    let padding = bits % 8;
+    let mut value = Secret::new();
+    let mut accumulator: u32 = 0;
+    let mut available = 0;
+    let mut skipped = false;
+    for word in value_words {
+        accumulator = (accumulator << 10) | *word as u32;
+        available += 10;
+        if !skipped {
+            if accumulator >> (available - padding) != 0 {
+                return Err(Error::InvalidShares);
+            }
+            available -= padding;
+            accumulator &= (1 << available) - 1;
+            skipped = true;
+        }
+        while available >= 8 {
+            available -= 8;
+            value.push((accumulator >> available) as u8).map_err(|_| Error::InvalidShares)?;
+            accumulator &= (1 << available) - 1;
+        }
+    }
+    if value.len() < 16 || value.len() % 2 == 1 {
+        return Err(Error::InvalidShares);
+    }
+    Ok((parameters, x, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the SLIP-0039 wordlist
+    const WORDLIST: &str = "
+        academic acid acne acquire acrobat activity actress adapt adequate adjust admit adorn adult
+        advance advocate afraid again agency agree aide aircraft airline airport ajar alarm album
+        alcohol alien alive alpha already alto aluminum always amazing ambition amount amuse
+        analysis anatomy ancestor ancient angel angry animal answer antenna anxiety apart aquatic
+        arcade arena argue armed artist artwork aspect auction august aunt average aviation avoid
+        award away axis axle beam beard beaver become bedroom behavior being believe belong benefit
+        best beyond bike biology birthday bishop black blanket blessing blimp blind blue body bolt
+        boring born both boundary bracelet branch brave breathe briefing broken brother browser
+        bucket budget building bulb bulge bumpy bundle burden burning busy buyer cage calcium camera
+        campus canyon capacity capital capture carbon cards careful cargo carpet carve category
+        cause ceiling center ceramic champion change charity check chemical chest chew chubby cinema
+        civil class clay cleanup client climate clinic clock clogs closet clothes club cluster coal
+        coastal coding column company corner costume counter course cover cowboy cradle craft crazy
+        credit cricket criminal crisis critical crowd crucial crunch crush crystal cubic cultural
+        curious curly custody cylinder daisy damage dance darkness database daughter deadline deal
+        debris debut decent decision declare decorate decrease deliver demand density deny depart
+        depend depict deploy describe desert desire desktop destroy detailed detect device devote
+        diagnose dictate diet dilemma diminish dining diploma disaster discuss disease dish dismiss
+        display distance dive divorce document domain domestic dominant dough downtown dragon
+        dramatic dream dress drift drink drove drug dryer duckling duke duration dwarf dynamic early
+        earth easel easy echo eclipse ecology edge editor educate either elbow elder election
+        elegant element elephant elevator elite else email emerald emission emperor emphasis
+        employer empty ending endless endorse enemy energy enforce engage enjoy enlarge entrance
+        envelope envy epidemic episode equation equip eraser erode escape estate estimate evaluate
+        evening evidence evil evoke exact example exceed exchange exclude excuse execute exercise
+        exhaust exotic expand expect explain express extend extra eyebrow facility fact failure
+        faint fake false family famous fancy fangs fantasy fatal fatigue favorite fawn fiber fiction
+        filter finance findings finger firefly firm fiscal fishing fitness flame flash flavor flea
+        flexible flip float floral fluff focus forbid force forecast forget formal fortune forward
+        founder fraction fragment frequent freshman friar fridge friendly frost froth frozen fumes
+        funding furl fused galaxy game garbage garden garlic gasoline gather general genius genre
+        genuine geology gesture glad glance glasses glen glimpse goat golden graduate grant grasp
+        gravity gray greatest grief grill grin grocery gross group grownup grumpy guard guest guilt
+        guitar gums hairy hamster hand hanger harvest have havoc hawk hazard headset health hearing
+        heat helpful herald herd hesitate hobo holiday holy home hormone hospital hour huge human
+        humidity hunting husband hush husky hybrid idea identify idle image impact imply improve
+        impulse include income increase index indicate industry infant inform inherit injury inmate
+        insect inside install intend intimate invasion involve iris island isolate item ivory jacket
+        jerky jewelry join judicial juice jump junction junior junk jury justice kernel keyboard
+        kidney kind kitchen knife knit laden ladle ladybug lair lamp language large laser laundry
+        lawsuit leader leaf learn leaves lecture legal legend legs lend length level liberty library
+        license lift likely lilac lily lips liquid listen literary living lizard loan lobe location
+        losing loud loyalty luck lunar lunch lungs luxury lying lyrics machine magazine maiden
+        mailman main makeup making mama manager mandate mansion manual marathon march market marvel
+        mason material math maximum mayor meaning medal medical member memory mental merchant merit
+        method metric midst mild military mineral minister miracle mixed mixture mobile modern
+        modify moisture moment morning mortgage mother mountain mouse move much mule multiple muscle
+        museum music mustang nail national necklace negative nervous network news nuclear numb
+        numerous nylon oasis obesity object observe obtain ocean often olympic omit oral orange
+        orbit order ordinary organize ounce oven overall owner paces pacific package paid painting
+        pajamas pancake pants papa paper parcel parking party patent patrol payment payroll peaceful
+        peanut peasant pecan penalty pencil percent perfect permit petition phantom pharmacy photo
+        phrase physics pickup picture piece pile pink pipeline pistol pitch plains plan plastic
+        platform playoff pleasure plot plunge practice prayer preach predator pregnant premium
+        prepare presence prevent priest primary priority prisoner privacy prize problem process
+        profile program promise prospect provide prune public pulse pumps punish puny pupal purchase
+        purple python quantity quarter quick quiet race racism radar railroad rainbow raisin random
+        ranked rapids raspy reaction realize rebound rebuild recall receiver recover regret regular
+        reject relate remember remind remove render repair repeat replace require rescue research
+        resident response result retailer retreat reunion revenue review reward rhyme rhythm rich
+        rival river robin rocky romantic romp roster round royal ruin ruler rumor sack safari salary
+        salon salt satisfy satoshi saver says scandal scared scatter scene scholar science scout
+        scramble screw script scroll seafood season secret security segment senior shadow shaft
+        shame shaped sharp shelter sheriff short should shrimp sidewalk silent silver similar simple
+        single sister skin skunk slap slavery sled slice slim slow slush smart smear smell smirk
+        smith smoking smug snake snapshot sniff society software soldier solution soul source space
+        spark speak species spelling spend spew spider spill spine spirit spit spray sprinkle square
+        squeeze stadium staff standard starting station stay steady step stick stilt story strategy
+        strike style subject submit sugar suitable sunlight superior surface surprise survive
+        sweater swimming swing switch symbolic sympathy syndrome system tackle tactics tadpole
+        talent task taste taught taxi teacher teammate teaspoon temple tenant tendency tension
+        terminal testify texture thank that theater theory therapy thorn threaten thumb thunder
+        ticket tidy timber timely ting tofu together tolerate total toxic tracks traffic training
+        transfer trash traveler treat trend trial tricycle trip triumph trouble true trust twice
+        twin type typical ugly ultimate umbrella uncover undergo unfair unfold unhappy union
+        universe unkind unknown unusual unwrap upgrade upstairs username usher usual valid valuable
+        vampire vanish various vegan velvet venture verdict verify very veteran vexed victim video
+        view vintage violence viral visitor visual vitamins vocal voice volume voter voting walnut
+        warmth warn watch wavy wealthy weapon webcam welcome welfare western width wildlife window
+        wine wireless wisdom withdraw wits wolf woman work worthy wrap wrist writing wrote year yelp
+        yield yoga zero
+    ";
+
+    fn share(mnemonic: &str) -> ShortData {
+        let mut share = ShortData::new();
+        for word in mnemonic.split(' ') {
+            let index = WORDLIST.split_whitespace().position(|other| other == word).unwrap() as u16;
+            share.extend_from_slice(&index.to_be_bytes()).unwrap();
+        }
+        share
+    }
+
+    fn combine(mnemonics: &[&str]) -> Result<Secret> {
+        let shares: std::vec::Vec<ShortData> = mnemonics.iter().map(|mnemonic| share(mnemonic)).collect();
+        let (parameters, encrypted) = combine_mnemonics(shares.iter().map(|share| &share[..]))?;
+        Ok(feistel(&encrypted, b"TREZOR", &parameters, false))
+    }
+
+    #[test]
+    fn test_slip39() {
+        assert_eq!(WORDLIST.split_whitespace().count(), 1024);
+
+        // test vectors from SLIP-0039, with the passphrase "TREZOR"
+        let valid: [(&[&str], &[u8]); 3] = [
+            // 1. Valid mnemonic without sharing (128 bits)
+            (&["duckling enlarge academic academic agency result length solution fridge kidney coal piece \
+                deal husband erode duke ajar critical decision keyboard"],
+             &hex_literal::hex!("bb54aac4b89dc868ba37d9cc21b2cece")),
+            // 4. Basic sharing 2-of-3 (128 bits)
+            (&["shadow pistol academic always adequate wildlife fancy gross oasis cylinder mustang wrist \
+                rescue view short owner flip making coding armed",
+               "shadow pistol academic acid actress prayer class unknown daughter sweater depict flip \
+                twice unkind craft early superior advocate guest smoking"],
+             &hex_literal::hex!("b43ceb7e57a0ea8766221624d01b0864")),
+            // 21. Basic sharing 2-of-3 (256 bits)
+            (&["humidity disease academic always aluminum jewelry energy woman receiver strategy amuse \
+                duckling lying evidence network walnut tactics forget hairy rebound impulse brother \
+                survive clothes stadium mailman rival ocean reward venture always armed unwrap",
+               "humidity disease academic agency actress jacket gross physics cylinder solution fake \
+                mortgage benefit public busy prepare sharp friar change work slow purchase ruler again \
+                tricycle involve viral wireless mixture anatomy desert cargo upgrade"],
+             &hex_literal::hex!("c938b319067687e990e05e0da0ecce1278f75ff58d9853f19dcaeed5de104aae")),
+        ];
+        for (mnemonics, secret) in valid.iter() {
+            assert_eq!(&combine(mnemonics).unwrap()[..], *secret);
+        }
+
+        let invalid: [&[&str]; 5] = [
+            // 2. Mnemonic with invalid checksum (128 bits)
+            &["duckling enlarge academic academic agency result length solution fridge kidney coal piece \
+               deal husband erode duke ajar critical decision kidney"],
+            // 3. Mnemonic with invalid padding (128 bits)
+            &["duckling enlarge academic academic email result length solution fridge kidney coal piece \
+               deal husband erode duke ajar music cargo fitness"],
+            // 5. Basic sharing 2-of-3 (128 bits), with one share only
+            &["shadow pistol academic always adequate wildlife fancy gross oasis cylinder mustang wrist \
+               rescue view short owner flip making coding armed"],
+            // 6. Mnemonics with different identifiers (128 bits)
+            &["adequate smoking academic acid debut wine petition glen cluster slow rhyme slow simple \
+               epidemic rumor junk tracks treat olympic tolerate",
+              "adequate stay academic agency agency formal party ting frequent learn upstairs remember \
+               smear leaf damage anatomy ladle market hush corner"],
+            // 19. Mnemonic with invalid checksum (256 bits)
+            &["theory painting academic academic armed sweater year military elder discuss acne wildlife \
+               boring employer fused large satoshi bundle carbon diagnose anatomy hamster leaves tracks \
+               paces beyond phantom capital marvel lips brave detect lunar"],
+        ];
+        for mnemonics in invalid.iter() {
+            assert_eq!(combine(mnemonics), Err(Error::InvalidShares));
+        }
+
+        // 18. Valid mnemonic without sharing (256 bits), which differs from 19. only in the checksum
+        assert_eq!(
+            &combine(&["theory painting academic academic armed sweater year military elder discuss acne \
+                wildlife boring employer fused large satoshi bundle carbon diagnose anatomy hamster leaves \
+                tracks paces beyond phantom capital marvel lips brave detect luck"]).unwrap()[..],
+            &hex_literal::hex!("989baf9dcaad5b10ca33dfd8cc75e42477025dce88ae83e75a230086a0e00e92")[..],
+        );
+    }
+}
@@ -78,6 +78,7 @@ pub mod certstore;
 pub mod counterstore;
 pub mod filestore;
 pub mod keystore;
+pub mod pinstore;
 
 // pub type FileContents = Bytes<MAX_FILE_SIZE>;
 
@@ -523,6 +524,45 @@ where
     outcome.map_err(|_| Error::FilesystemWriteFailure)
 }
 
+/// Inserts `item` in order, keeping the smallest items if `found` is full.
+pub(crate) fn keep_smallest<T, K: PartialOrd, const N: usize>(found: &mut Vec<T, N>, item: T, order: impl Fn(&T) -> K) {
+    let position = found.iter().position(|other| order(&item) < order(other)).unwrap_or(found.len());
+    if position < N {
+        if found.is_full() {
+            found.pop();
+        }
+        found.insert(position, item).ok();
+    }
+}
+
+/// Calls `f` with the name of each file in the directory at path in location of store.
+pub fn read_dir_file_names<F>(store: impl Store, location: Location, path: &Path, f: F) -> Result<(), Error>
+where
+    F: FnMut(&Path),
+{
+    debug_now!("listing files in {}", &path);
+    fn read_file_names<S: LfsStorage>(fs: &Filesystem<'static, S>, path: &Path, mut f: impl FnMut(&Path))
+        -> littlefs2::io::Result<()>
+    {
+        fs.read_dir_and_then(path, |dir| {
+            // skip over `.` and `..`
+            for entry in dir.skip(2) {
+                let entry = entry?;
+                if entry.file_type().is_file() {
+                    f(entry.file_name());
+                }
+            }
+            Ok(())
+        })
+    }
+    let outcome = match location {
+        Location::Internal => read_file_names(store.ifs(), path, f),
+        Location::External => read_file_names(store.efs(), path, f),
+        Location::Volatile => read_file_names(store.vfs(), path, f),
+    };
+    outcome.map_err(|_| Error::FilesystemReadFailure)
+}
+
 // pub fn delete_volatile(store: impl Store, handle: &ObjectHandle) -> bool {
 //     let secrecies = [
 //         Secrecy::Secret,
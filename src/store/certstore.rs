@@ -1,10 +1,15 @@
 use chacha20::ChaCha8Rng;
+use heapless::Vec;
 use littlefs2::path::PathBuf;
+use sha2::digest::Digest as _;
 
 use crate::{
+    Bytes,
+    config,
     error::{Error, Result},
-    store::{self, Store},
-    types::{CertId, ClientId, Location, Message},
+    store::{self, Store, counterstore::ClientCounterstore},
+    types::{CertId, CertificateAttributes, CertificateFilter, CertificateListing, ClientId, Id, Location, Message},
+    x509,
 };
 
 pub struct ClientCertstore<S>
@@ -12,29 +17,37 @@ where
     S: Store,
 {
     client_id: ClientId,
-    rng: ChaCha8Rng,
+    // for counter zero, which numbers the certificates
+    counterstore: ClientCounterstore<S>,
     store: S,
 }
 
 pub trait Certstore {
     fn delete_certificate(&mut self, id: CertId) -> Result<()>;
     fn read_certificate(&mut self, id: CertId) -> Result<Message>;
-    /// TODO: feels a bit heavy-weight to pass in the ClientCounterstore here
-    /// just to ensure the next global counter ("counter zero") is used, and
-    /// not something random.
+    fn certificate_attributes(&mut self, id: CertId) -> Result<CertificateAttributes>;
+    fn set_certificate_attributes(&mut self, id: CertId, attributes: &CertificateAttributes) -> Result<()>;
+    /// IDs count up per client, using its counter zero, so they are deterministic
+    /// and never reused.
     fn write_certificate(&mut self, location: Location, der: &Message) -> Result<CertId>;
+    /// Returns the matching certificates following `after` in ascending order, as many
+    /// as fit, and the total number of matching certificates.
+    fn list_certificates(&mut self, filter: &CertificateFilter, after: Option<CertId>)
+        -> Result<(Vec<CertificateListing, { config::MAX_OBJECT_HANDLES }>, usize)>;
 }
 
 impl<S: Store> Certstore for ClientCertstore<S> {
 
     fn delete_certificate(&mut self, id: CertId) -> Result<()> {
         let path = self.cert_path(id);
+        let attributes_path = self.attributes_path(id);
         let locations = [
             Location::Internal,
             Location::External,
             Location::Volatile,
         ];
         locations.iter().any(|&location| {
+            store::delete(self.store, location, &attributes_path);
             store::delete(self.store, location, &path)
         }).then(|| ()).ok_or(Error::NoSuchKey)
     }
@@ -51,37 +64,138 @@ impl<S: Store> Certstore for ClientCertstore<S> {
         }).ok_or(Error::NoSuchCertificate)
     }
 
+    fn certificate_attributes(&mut self, id: CertId) -> Result<CertificateAttributes> {
+        let location = self.locate(id).ok_or(Error::NoSuchCertificate)?;
+        self.read_attributes(location, id)
+    }
+
+    fn set_certificate_attributes(&mut self, id: CertId, attributes: &CertificateAttributes) -> Result<()> {
+        let location = self.locate(id).ok_or(Error::NoSuchCertificate)?;
+        let path = self.attributes_path(id);
+        if *attributes == CertificateAttributes::default() {
+            store::delete(self.store, location, &path);
+            return Ok(());
+        }
+        let bytes: Bytes<128> = crate::cbor_serialize_bytes(attributes).map_err(|_| Error::CborError)?;
+        store::store(self.store, location, &path, &bytes)
+    }
+
     fn write_certificate(&mut self, location: Location, der: &Message) -> Result<CertId> {
-        let id = CertId::new(&mut self.rng);
+        let id = CertId(Id(self.counterstore.increment_counter_zero()?));
         let path = self.cert_path(id);
         store::store(self.store, location, &path, &der.as_slice())?;
         Ok(id)
     }
+
+    fn list_certificates(&mut self, filter: &CertificateFilter, after: Option<CertId>)
+        -> Result<(Vec<CertificateListing, { config::MAX_OBJECT_HANDLES }>, usize)>
+    {
+        let locations = [Location::Internal, Location::External, Location::Volatile];
+        let dir = self.cert_directory();
+
+        let mut found = Vec::<CertificateListing, { config::MAX_OBJECT_HANDLES }>::new();
+        let mut count = 0;
+        for location in locations.iter().filter(|l| filter.location.is_none() || filter.location == Some(**l)) {
+            if !store::exists(self.store, *location, &dir) {
+                continue;
+            }
+            store::read_dir_file_names(self.store, *location, &dir, |file_name| {
+                // skip anything that is not a certificate
+                let id = match u128::from_str_radix(file_name.as_ref(), 16) {
+                    Ok(id) => CertId(Id(id)),
+                    Err(_) => return,
+                };
+                let attributes = match self.read_attributes(*location, id) {
+                    Ok(attributes) => attributes,
+                    Err(_) => return,
+                };
+                let matches = [
+                    filter.label.is_none() || filter.label == attributes.label,
+                    filter.key.is_none() || filter.key == attributes.key,
+                    self.matches_hashes(*location, id, filter),
+                ].iter().all(|b| *b);
+                if !matches {
+                    return;
+                }
+
+                count += 1;
+                if matches!(after, Some(after) if id <= after) {
+                    return;
+                }
+                store::keep_smallest(&mut found, (id, *location, attributes), |(id, _, _)| *id);
+            })?;
+        }
+
+        Ok((found, count))
+    }
 }
 
 impl<S: Store> ClientCertstore<S> {
     pub fn new(client_id: ClientId, rng: ChaCha8Rng, store: S) -> Self {
-        Self { client_id, rng, store }
+        let counterstore = ClientCounterstore::new(client_id.clone(), rng, store);
+        Self { client_id, counterstore, store }
     }
 
-    fn cert_path(&self, id: CertId) -> PathBuf {
+    fn cert_directory(&self) -> PathBuf {
         let mut path = PathBuf::new();
         path.push(&self.client_id);
         path.push(&PathBuf::from("x5c"));
+        path
+    }
+
+    fn cert_path(&self, id: CertId) -> PathBuf {
+        let mut path = self.cert_directory();
+        path.push(&PathBuf::from(id.hex().as_slice()));
+        path
+    }
+
+    fn attributes_path(&self, id: CertId) -> PathBuf {
+        let mut path = PathBuf::new();
+        path.push(&self.client_id);
+        path.push(&PathBuf::from("x5a"));
         path.push(&PathBuf::from(id.hex().as_slice()));
         path
     }
 
-    // fn read_cert(&mut self, location: Location, id: u128) -> Result<Message> {
-    //     let path = self.cert_path(id);
-    //     let mut data: Message = store::read(self.store, location, &path)?;
-    //     Ok(data)
-    // }
+    fn locate(&self, id: CertId) -> Option<Location> {
+        let path = self.cert_path(id);
+        let locations = [
+            Location::Internal,
+            Location::External,
+            Location::Volatile,
+        ];
+        locations.iter().copied().find(|&location| store::exists(self.store, location, &path))
+    }
 
-    // fn write_cert(&mut self, location: Location, id: u128, data: Message) -> Result<()> {
-    //     let path = self.cert_path(id);
-    //     store::store(self.store, location, &path, &data)
-    // }
+    /// Certificates without attributes have the default ones.
+    fn read_attributes(&self, location: Location, id: CertId) -> Result<CertificateAttributes> {
+        let path = self.attributes_path(id);
+        if !store::exists(self.store, location, &path) {
+            return Ok(CertificateAttributes::default());
+        }
+        let bytes: Bytes<128> = store::read(self.store, location, &path)?;
+        crate::cbor_deserialize(&bytes).map_err(|_| Error::CborError)
+    }
 
+    /// Certificates that do not parse only match if no hash is asked for.
+    fn matches_hashes(&self, location: Location, id: CertId, filter: &CertificateFilter) -> bool {
+        if filter.subject.is_none() && filter.issuer.is_none() && filter.public_key.is_none() {
+            return true;
+        }
+        let der: Message = match store::read(self.store, location, &self.cert_path(id)) {
+            Ok(der) => der,
+            Err(_) => return false,
+        };
+        let certificate = match x509::Certificate::parse(&der) {
+            Ok(certificate) => certificate,
+            Err(_) => return false,
+        };
+        [
+            (filter.subject, certificate.subject),
+            (filter.issuer, certificate.issuer),
+            (filter.public_key, certificate.public_key.as_bytes()),
+        ].iter().all(|(hash, value)| {
+            hash.map(|hash| hash[..] == sha2::Sha256::digest(value)[..]).unwrap_or(true)
+        })
+    }
 }
-
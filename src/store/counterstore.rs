@@ -1,12 +1,14 @@
 use core::convert::TryInto;
 
 use chacha20::ChaCha8Rng;
+use heapless::Vec;
 use littlefs2::path::PathBuf;
 
 use crate::{
+    config,
     error::{Error, Result},
     store::{self, Store},
-    types::{ClientId, CounterId, Location as Location},
+    types::{ClientId, CounterId, CounterListing, Id, Location as Location},
 };
 
 
@@ -21,20 +23,38 @@ where
 
 pub type Counter = u128;
 
+/// Never handed out by `Counterstore::create`, numbers the certificates of a client.
+pub const COUNTER_ZERO: CounterId = CounterId::from_special(0);
+
 impl<S: Store> ClientCounterstore<S> {
     pub fn new(client_id: ClientId, rng: ChaCha8Rng, store: S) -> Self {
         Self { client_id, rng, store }
     }
 
-    fn counter_path(&self, id: CounterId) -> PathBuf {
+    fn counter_directory(&self) -> PathBuf {
         let mut path = PathBuf::new();
         path.push(&self.client_id);
         path.push(&PathBuf::from("ctr"));
+        path
+    }
+
+    fn counter_path(&self, id: CounterId) -> PathBuf {
+        let mut path = self.counter_directory();
         path.push(&PathBuf::from(id.hex().as_slice()));
         path
     }
 
-    fn read_counter(&mut self, location: Location, id: CounterId) -> Result<Counter> {
+    fn locate(&self, id: CounterId) -> Option<Location> {
+        let path = self.counter_path(id);
+        let locations = [
+            Location::Internal,
+            Location::External,
+            Location::Volatile,
+        ];
+        locations.iter().copied().find(|&location| store::exists(self.store, location, &path))
+    }
+
+    fn read_counter(&self, location: Location, id: CounterId) -> Result<Counter> {
         let path = self.counter_path(id);
         let mut bytes: crate::Bytes<16> = store::read(self.store, location, &path)?;
         bytes.resize_default(16).ok();
@@ -46,41 +66,134 @@ impl<S: Store> ClientCounterstore<S> {
         store::store(self.store, location, &path, &value.to_le_bytes())
     }
 
-    fn increment_location(&mut self, location: Location, id: CounterId) -> Result<Counter> {
+    fn increment_location(&mut self, location: Location, id: CounterId, step: Counter) -> Result<Counter> {
         let counter: u128 = self.read_counter(location, id)?;
-        let next_counter = counter + 1;
+        let next_counter = counter.checked_add(step).ok_or(Error::CounterOverflow)?;
         self.write_counter(location, id, next_counter)?;
         Ok(counter)
     }
+
+    /// Increments counter zero, which starts at one in internal storage.
+    pub fn increment_counter_zero(&mut self) -> Result<Counter> {
+        if !store::exists(self.store, Location::Internal, &self.counter_path(COUNTER_ZERO)) {
+            self.write_counter(Location::Internal, COUNTER_ZERO, 1)?;
+        }
+        self.increment_location(Location::Internal, COUNTER_ZERO, 1)
+    }
 }
 
 /// Trait intended for use by mechanism implementations.
+///
+/// Counters never decrease, unless deleted: they can only be incremented, or raised.
 pub trait Counterstore {
     const DEFAULT_START_AT: u128 = 0;
     fn create_starting_at(&mut self, location: Location, starting_at: impl Into<Counter>) -> Result<CounterId>;
     fn create(&mut self, location: Location) -> Result<CounterId> {
         self.create_starting_at(location, Self::DEFAULT_START_AT)
     }
-    fn increment(&mut self, id: CounterId) -> Result<u128>;
+    /// Returns the value before incrementing.
+    fn increment(&mut self, id: CounterId) -> Result<Counter> {
+        self.increment_by(id, 1)
+    }
+    /// Returns the value before incrementing, and fails with `CounterOverflow` if the
+    /// counter would wrap.
+    fn increment_by(&mut self, id: CounterId, step: Counter) -> Result<Counter>;
+    fn read(&mut self, id: CounterId) -> Result<Counter>;
+    /// Raises the counter to `value` if it is lower, and returns the resulting value.
+    fn set_at_least(&mut self, id: CounterId, value: Counter) -> Result<Counter>;
+    /// Counter zero can not be deleted, as certificate IDs would be reused.
+    fn delete(&mut self, id: CounterId) -> Result<()>;
+    /// Returns the counters following `after` in ascending order, as many as fit, and the
+    /// total number of counters. Counter zero is not listed.
+    fn list(&mut self, location: Option<Location>, after: Option<CounterId>)
+        -> Result<(Vec<CounterListing, { config::MAX_OBJECT_HANDLES }>, usize)>;
 }
 
 impl<S: Store> Counterstore for ClientCounterstore<S> {
     fn create_starting_at(&mut self, location: Location, starting_at: impl Into<Counter>) -> Result<CounterId> {
-        let id = CounterId::new(&mut self.rng);
+        let id = loop {
+            let id = CounterId::new(&mut self.rng);
+            if id != COUNTER_ZERO {
+                break id;
+            }
+        };
         self.write_counter(location, id, starting_at.into())?;
         Ok(id)
     }
 
-    fn increment(&mut self, id: CounterId) -> Result<u128> {
+    fn increment_by(&mut self, id: CounterId, step: Counter) -> Result<Counter> {
+        let location = self.locate(id).ok_or(Error::NoSuchKey)?;
+        self.increment_location(location, id, step)
+    }
+
+    fn read(&mut self, id: CounterId) -> Result<Counter> {
+        let location = self.locate(id).ok_or(Error::NoSuchKey)?;
+        self.read_counter(location, id)
+    }
+
+    fn set_at_least(&mut self, id: CounterId, value: Counter) -> Result<Counter> {
+        let location = self.locate(id).ok_or(Error::NoSuchKey)?;
+        let counter = self.read_counter(location, id)?;
+        if counter >= value {
+            return Ok(counter);
+        }
+        self.write_counter(location, id, value)?;
+        Ok(value)
+    }
+
+    fn delete(&mut self, id: CounterId) -> Result<()> {
+        if id == COUNTER_ZERO {
+            return Err(Error::ObjectHandleInvalid);
+        }
+        let path = self.counter_path(id);
         let locations = [
             Location::Internal,
             Location::External,
             Location::Volatile,
         ];
+        let deleted = locations.iter().any(|&location| {
+            store::delete(self.store, location, &path)
+        });
+        match deleted {
+            true => Ok(()),
+            false => Err(Error::NoSuchKey),
+        }
+    }
+
+    fn list(&mut self, location: Option<Location>, after: Option<CounterId>)
+        -> Result<(Vec<CounterListing, { config::MAX_OBJECT_HANDLES }>, usize)>
+    {
+        let locations = [Location::Internal, Location::External, Location::Volatile];
+        let dir = self.counter_directory();
 
-        locations.iter().filter_map(|&location| {
-            self.increment_location(location, id).ok()
-        }).next().ok_or(Error::NoSuchKey)
+        let mut found = Vec::<CounterListing, { config::MAX_OBJECT_HANDLES }>::new();
+        let mut count = 0;
+        for location in locations.iter().filter(|l| location.is_none() || location == Some(**l)) {
+            if !store::exists(self.store, *location, &dir) {
+                continue;
+            }
+            store::read_dir_file_names(self.store, *location, &dir, |file_name| {
+                let id = match u128::from_str_radix(file_name.as_ref(), 16) {
+                    Ok(id) => CounterId(Id(id)),
+                    Err(_) => return,
+                };
+                // counter zero belongs to the certificates
+                if id == COUNTER_ZERO {
+                    return;
+                }
+                let counter = match self.read_counter(*location, id) {
+                    Ok(counter) => counter,
+                    Err(_) => return,
+                };
+
+                count += 1;
+                if matches!(after, Some(after) if id <= after) {
+                    return;
+                }
+                store::keep_smallest(&mut found, (id, *location, counter), |(id, _, _)| *id);
+            })?;
+        }
+
+        Ok((found, count))
     }
 }
-
@@ -1,4 +1,8 @@
 use chacha20::ChaCha8Rng;
+use generic_array::GenericArray;
+use rand_core::RngCore as _;
+use zeroize::Zeroize as _;
+use heapless::Vec;
 use littlefs2::path::PathBuf;
 
 use crate::{
@@ -7,7 +11,8 @@ use crate::{
     key,
     Platform,
     store::{self, Store as _},
-    types::{KeyId, Location},
+    config::{self, MAX_STORED_KEY_LENGTH},
+    types::{Id, KeyId, KeyListing, Location, Mechanism, PinId},
 };
 
 
@@ -20,15 +25,36 @@ where
     client_id: ClientId,
     rng: ChaCha8Rng,
     store: P::S,
+    time: Option<u64>,
+    uptime: u64,
+    key_encryption_key: Option<[u8; 32]>,
 }
 
 impl<'a, P: Platform> ClientKeystore<P> {
     pub fn new(client_id: ClientId, rng: ChaCha8Rng, store: P::S) -> Self {
-        Self { client_id, rng, store }
+        Self { client_id, rng, store, time: None, uptime: 0, key_encryption_key: None }
+    }
+
+    /// Sets the current time in seconds since the UNIX epoch, recorded as creation time of stored keys.
+    pub fn with_time(mut self, time: Option<u64>) -> Self {
+        self.time = time;
+        self
+    }
+
+    /// Sets the current uptime in seconds, against which `Deadline::Uptime` is checked.
+    pub fn with_uptime(mut self, uptime: u64) -> Self {
+        self.uptime = uptime;
+        self
+    }
+
+    /// Sets the key under which key material is encrypted at rest, see `Platform::key_encryption_key`.
+    pub fn with_key_encryption_key(mut self, key_encryption_key: Option<[u8; 32]>) -> Self {
+        self.key_encryption_key = key_encryption_key;
+        self
     }
 }
 
-pub const SERIALIZATION_VERSION: u8 = 0;
+pub use crate::key::SERIALIZATION_VERSION;
 
 /// Trait intended for use by mechanism implementations.
 pub trait Keystore {
@@ -36,15 +62,68 @@ pub trait Keystore {
     // fn load(&self, key: KeyId) -> Result<Key>;
     // fn exists(&self, key: KeyId) -> bool;
     fn store_key(&mut self, location: Location, secrecy: key::Secrecy, info: impl Into<key::Info>, material: &[u8]) -> Result<KeyId>;
+    /// Store a keypair, both halves under the same `KeyId`.
+    fn store_keypair(&mut self, location: Location, info: impl Into<key::Info>, secret_material: &[u8], public_material: &[u8]) -> Result<KeyId>;
+    /// Store the public half of the keypair `id`, for private keys stored on their own.
+    fn store_public_half(&mut self, location: Location, id: &KeyId, material: &[u8]) -> Result<()>;
     fn exists_key(&self, secrecy: key::Secrecy, kind: Option<key::Kind>, id: &KeyId) -> bool;
     /// Return Header of key, if it exists
     fn key_info(&self, secrecy: key::Secrecy, id: &KeyId) -> Option<key::Info>;
+    /// Delete the key, or both halves of a keypair.
     fn delete_key(&self, id: &KeyId) -> bool;
+    /// Delete only the public half of a keypair, or a public key.
+    fn delete_public_key(&self, id: &KeyId) -> bool;
+    fn change_location(&self, id: &KeyId, location: Location) -> Result<()>;
     fn delete_all(&self, location: Location) -> Result<usize>;
     fn load_key(&self, secrecy: key::Secrecy, kind: Option<key::Kind>, id: &KeyId) -> Result<key::Key>;
-    fn overwrite_key(&self, location: Location, secrecy: key::Secrecy, kind: key::Kind, id: &KeyId, material: &[u8]) -> Result<()>;
+    fn overwrite_key(&mut self, location: Location, secrecy: key::Secrecy, kind: key::Kind, id: &KeyId, material: &[u8]) -> Result<()>;
     fn rng(&mut self) -> &mut ChaCha8Rng;
     fn location(&self, secrecy: key::Secrecy, id: &KeyId) -> Option<Location>;
+    /// Whether the PIN was verified recently enough for the requirement.
+    fn pin_verified(&self, requirement: &key::PinRequirement) -> bool;
+    /// Account for one use of a loaded key, refusing it if its limits are reached.
+    ///
+    /// Keys stored in an earlier format are upgraded to the current one.
+    fn use_key(&mut self, secrecy: key::Secrecy, id: &KeyId, key: &key::Key) -> Result<()>;
+
+    /// Load a key for use with `mechanism`, if its usage policy permits `usage`.
+    ///
+    /// Mechanisms must load the keys they operate with through this method.
+    fn load_key_for(&mut self, secrecy: key::Secrecy, kind: Option<key::Kind>, id: &KeyId, mechanism: Mechanism, usage: key::Usage) -> Result<key::Key> {
+        let key = self.load_key(secrecy, kind, id)?;
+        if !key.policy.permits(mechanism, usage) {
+            return Err(Error::KeyFunctionNotPermitted);
+        }
+        if let Some(requirement) = key.policy.pin.as_ref() {
+            if !self.pin_verified(requirement) {
+                return Err(Error::PinNotVerified);
+            }
+        }
+        self.use_key(secrecy, id, &key)?;
+        Ok(key)
+    }
+
+    /// Load a secret key to export it wrapped.
+    ///
+    /// Only keys created extractable may leave the keystore.
+    fn load_extractable_key(&self, id: &KeyId) -> Result<key::Key> {
+        let key = self.load_key(key::Secrecy::Secret, None, id)?;
+        if !key.flags.contains(key::Flags::EXTRACTABLE) {
+            return Err(Error::KeyUnextractable);
+        }
+        Ok(key)
+    }
+
+    /// Load a secret key that is plain key material, as needed for MACs and KDFs.
+    ///
+    /// These are `Symmetric` keys (generated or derived) and `Shared` secrets (from key agreement).
+    fn load_symmetric_key(&mut self, id: &KeyId, mechanism: Mechanism, usage: key::Usage) -> Result<key::Key> {
+        let key = self.load_key_for(key::Secrecy::Secret, None, id, mechanism, usage)?;
+        match key.kind {
+            key::Kind::Shared(_) | key::Kind::Symmetric(_) => Ok(key),
+            _ => Err(Error::WrongKeyKind),
+        }
+    }
 }
 
 impl<P: Platform> ClientKeystore<P> {
@@ -69,41 +148,350 @@ impl<P: Platform> ClientKeystore<P> {
         path
     }
 
-}
-
-impl<P: Platform> Keystore for ClientKeystore<P> {
-
-    fn rng(&mut self) -> &mut ChaCha8Rng {
-        &mut self.rng
+    /// Stores a keypair under a well-known ID instead of a random one, replacing any key there.
+    pub(crate) fn store_keypair_at(&mut self, location: Location, id: &KeyId, info: key::Info, secret_material: &[u8], public_material: &[u8]) -> Result<()> {
+        self.delete_key(id);
+        self.write_key(location, key::Secrecy::Secret, id, info, secret_material)?;
+        if let Err(error) = self.store_public_half(location, id, public_material) {
+            self.delete_key(id);
+            return Err(error);
+        }
+        Ok(())
     }
 
-    #[inline(never)]
-    fn store_key(&mut self, location: Location, secrecy: key::Secrecy, info: impl Into<key::Info>, material: &[u8]) -> Result<KeyId> {
-        // info_now!("storing {:?} -> {:?}", &key_kind, location);
-
-        let mut info: key::Info = info.into();
+    fn write_key(&mut self, location: Location, secrecy: key::Secrecy, id: &KeyId, mut info: key::Info, material: &[u8]) -> Result<()> {
         if secrecy == key::Secrecy::Secret {
             info.flags |= key::Flags::SENSITIVE;
         }
         let key = key::Key {
             flags: info.flags,
             kind: info.kind,
+            policy: info.policy,
+            limits: info.limits,
+            metadata: key::Metadata { created: self.time, ..info.metadata },
             material: key::Material::from_slice(material).unwrap(),
         };
 
+        let path = self.key_path(secrecy, id);
+        self.write_serialized_key(location, &path, &key)
+    }
+
+    /// Writes the key, encrypted if there is a key encryption key.
+    ///
+    /// Encrypted files consist of the `ENCRYPTED` and `VERSIONED` flags, the format version,
+    /// a nonce, the encrypted serialized key and its tag. The path is authenticated too,
+    /// so a file moved to another client or key ID fails to decrypt.
+    pub(crate) fn write_serialized_key(&mut self, location: Location, path: &PathBuf, key: &key::Key) -> Result<()> {
+        use chacha20poly1305::ChaCha8Poly1305;
+        use chacha20poly1305::aead::{AeadInPlace, NewAead};
+
+        self.check_storable(location, key)?;
+        let serialized = key.serialize();
+        let key_encryption_key = match self.key_encryption_key.as_ref() {
+            Some(key_encryption_key) => key_encryption_key,
+            None => return store::store(self.store, location, path, &serialized),
+        };
+
+        let mut header = [0u8; 3];
+        header[..2].copy_from_slice(&(key::Flags::ENCRYPTED | key::Flags::VERSIONED).bits().to_be_bytes());
+        header[2] = key::SERIALIZATION_VERSION;
+        let mut nonce = [0u8; 12];
+        self.rng.fill_bytes(&mut nonce);
+
+        let mut file = Bytes::<MAX_STORED_KEY_LENGTH>::new();
+        file.extend_from_slice(&header).unwrap();
+        file.extend_from_slice(&nonce).unwrap();
+        file.extend_from_slice(&serialized).unwrap();
+
+        let aead = ChaCha8Poly1305::new(GenericArray::from_slice(key_encryption_key));
+        let tag = aead.encrypt_in_place_detached(
+            GenericArray::from_slice(&nonce),
+            &associated_data(path, &header),
+            &mut file[header.len() + 12..],
+        ).map_err(|_| Error::AeadError)?;
+        file.extend_from_slice(&tag).unwrap();
+
+        store::store(self.store, location, path, &file)
+    }
+
+    /// Checks that the key may be stored at the location, for every way keys are stored.
+    fn check_storable(&self, location: Location, key: &key::Key) -> Result<()> {
+        match key.limits.deadline {
+            // the uptime restarts at boot, which would extend the life of a persistent key
+            Some(key::Deadline::Uptime(_)) if location != Location::Volatile => Err(Error::KeyLocationNotPermitted),
+            // without a clock, the deadline could not be enforced
+            Some(key::Deadline::Time(_)) if self.time.is_none() => Err(Error::FunctionNotSupported),
+            _ => Ok(()),
+        }
+    }
+
+    /// Reads a key written by `write_serialized_key`.
+    ///
+    /// Once there is a key encryption key, keys in the clear are refused, as anyone able to write
+    /// to flash could plant them; keys stored before it was introduced have to be encrypted with
+    /// `encrypt_stored_keys` first. Keys encrypted before the file header carried the format
+    /// version stay readable until their first use upgrades them (see `upgrade_key`).
+    pub(crate) fn read_key(&self, location: Location, path: &PathBuf) -> Result<key::Key> {
+        use chacha20poly1305::ChaCha8Poly1305;
+        use chacha20poly1305::aead::{AeadInPlace, NewAead};
+
+        let mut file: Bytes<MAX_STORED_KEY_LENGTH> = store::read(self.store, location, path)?;
+        if !is_encrypted(&file) {
+            if self.key_encryption_key.is_some() {
+                return Err(Error::AeadError);
+            }
+            return key::Key::try_deserialize(&file);
+        }
+
+        let key_encryption_key = self.key_encryption_key.as_ref().ok_or(Error::AeadError)?;
+        let header_length = match key::serialized_version(&file)? {
+            0 => 2,
+            _ => 3,
+        };
+        if file.len() < header_length + 12 + 16 {
+            return Err(Error::InvalidSerializedKey);
+        }
+        let (header, rest) = file.split_at_mut(header_length);
+        let (nonce, rest) = rest.split_at_mut(12);
+        let (serialized, tag) = rest.split_at_mut(rest.len() - 16);
+
+        let aead = ChaCha8Poly1305::new(GenericArray::from_slice(key_encryption_key));
+        aead.decrypt_in_place_detached(
+            GenericArray::from_slice(nonce),
+            &associated_data(path, header),
+            serialized,
+            GenericArray::from_slice(tag),
+        ).map_err(|_| Error::AeadError)?;
+
+        let key = key::Key::try_deserialize(serialized);
+        serialized.zeroize();
+        key
+    }
+
+    /// Rewrites the key in the current format if it is stored in an earlier one.
+    fn upgrade_key(&mut self, location: Location, path: &PathBuf, key: &key::Key) -> Result<()> {
+        let file: Bytes<MAX_STORED_KEY_LENGTH> = store::read(self.store, location, path)?;
+        if key::serialized_version(&file)? < key::SERIALIZATION_VERSION {
+            self.write_serialized_key(location, path, key)?;
+        }
+        Ok(())
+    }
+
+    /// Deletes the keys whose policy requires the PIN, a few at a time.
+    pub(crate) fn delete_keys_bound_to(&self, pin: PinId) -> Result<()> {
+        loop {
+            let mut ids = Vec::<KeyId, 8>::new();
+            self.for_each_key(None, None, |_, _, id, key| {
+                if matches!(key.policy.pin, Some(requirement) if requirement.pin == pin) {
+                    ids.push(id).ok();
+                }
+            })?;
+            if ids.is_empty() {
+                return Ok(());
+            }
+            for id in ids.iter() {
+                self.delete_key(id);
+            }
+        }
+    }
+
+    /// Encrypts the keys stored in the clear, before the platform had a key encryption key.
+    ///
+    /// This is the only way to keep keys stored in the clear, so it must only run once, when
+    /// the key encryption key is introduced; see `Service::encrypt_stored_keys`.
+    pub fn encrypt_stored_keys(&mut self) -> Result<()> {
+        if self.key_encryption_key.is_none() {
+            return Ok(());
+        }
+        let secrecies = [key::Secrecy::Secret, key::Secrecy::Public];
+        let locations = [Location::Internal, Location::External, Location::Volatile];
+        for secrecy in secrecies.iter() {
+            let dir = self.key_directory(*secrecy);
+            for location in locations.iter() {
+                if !store::exists(self.store, *location, &dir) {
+                    continue;
+                }
+                // a few at a time, rather than writing while the directory is being read
+                loop {
+                    let mut paths = heapless::Vec::<PathBuf, 8>::new();
+                    store::read_dir_file_names(self.store, *location, &dir, |file_name| {
+                        let mut path = dir.clone();
+                        path.push(file_name);
+                        let file: Result<Bytes<MAX_STORED_KEY_LENGTH>> = store::read(self.store, *location, &path);
+                        if matches!(file, Ok(file) if !is_encrypted(&file)) {
+                            paths.push(path).ok();
+                        }
+                    })?;
+                    if paths.is_empty() {
+                        break;
+                    }
+                    for path in paths.iter() {
+                        let file: Bytes<MAX_STORED_KEY_LENGTH> = store::read(self.store, *location, path)?;
+                        let key = key::Key::try_deserialize(&file)?;
+                        self.write_serialized_key(*location, path, &key)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Calls `f` with the secrecy, location, ID and contents of each readable key,
+    /// where a `None` filter matches any.
+    fn for_each_key(
+        &self,
+        secrecy: Option<key::Secrecy>,
+        location: Option<Location>,
+        mut f: impl FnMut(key::Secrecy, Location, KeyId, key::Key),
+    ) -> Result<()> {
+        let secrecies = [key::Secrecy::Secret, key::Secrecy::Public];
+        let locations = [Location::Internal, Location::External, Location::Volatile];
+
+        let (secrecy_filter, location_filter) = (secrecy, location);
+        for secrecy in secrecies.iter().filter(|s| secrecy_filter.is_none() || secrecy_filter == Some(**s)) {
+            let dir = self.key_directory(*secrecy);
+            for location in locations.iter().filter(|l| location_filter.is_none() || location_filter == Some(**l)) {
+                if !store::exists(self.store, *location, &dir) {
+                    continue;
+                }
+                store::read_dir_file_names(self.store, *location, &dir, |file_name| {
+                    // skip anything that is not a key file
+                    let id = match u128::from_str_radix(file_name.as_ref(), 16) {
+                        Ok(id) => KeyId(Id(id)),
+                        Err(_) => return,
+                    };
+                    let mut path = dir.clone();
+                    path.push(file_name);
+                    if let Ok(key) = self.read_key(*location, &path) {
+                        f(*secrecy, *location, id, key);
+                    }
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Find keys by secrecy, kind, location and label, where `None` matches any.
+    ///
+    /// Returns the matching keys following `after` in ascending order, as many as fit,
+    /// and the total number of matching keys. To page through them, pass the last
+    /// key returned as `after` of the next call.
+    pub fn find_keys(
+        &self,
+        secrecy: Option<key::Secrecy>,
+        kind: Option<key::Kind>,
+        location: Option<Location>,
+        label: Option<&[u8]>,
+        after: Option<&KeyId>,
+    ) -> Result<(Vec<KeyId, { config::MAX_OBJECT_HANDLES }>, usize)> {
+        let mut found = Vec::<KeyId, { config::MAX_OBJECT_HANDLES }>::new();
+        let mut count = 0;
+
+        self.for_each_key(secrecy, location, |key_secrecy, _, id, key| {
+            if kind.is_some() && kind != Some(key.kind) {
+                return;
+            }
+            if label.is_some() && key.metadata.label.as_ref().map(|l| &l[..]) != label {
+                return;
+            }
+
+            // the public half of a keypair is found as part of the private half
+            if secrecy.is_none() && key_secrecy == key::Secrecy::Public && matches!(
+                self.location(key::Secrecy::Secret, &id),
+                Some(l) if location.is_none() || location == Some(l)
+            ) {
+                return;
+            }
+
+            count += 1;
+            if matches!(after, Some(after) if id <= *after) {
+                return;
+            }
+            store::keep_smallest(&mut found, id, |id| *id);
+        })?;
+
+        Ok((found, count))
+    }
+
+    /// List keys by kind and location, where `None` matches any.
+    ///
+    /// Unlike `find_keys`, the two halves of a keypair are listed separately, so either
+    /// half left over on its own shows up too. Returns the keys following `after` in ascending
+    /// order of ID, secret before public half, as many as fit, and the total number of matching keys.
+    pub fn list_keys(
+        &self,
+        kind: Option<key::Kind>,
+        location: Option<Location>,
+        after: Option<&(KeyId, key::Secrecy)>,
+    ) -> Result<(Vec<KeyListing, { config::MAX_OBJECT_HANDLES }>, usize)> {
+        fn order(id: KeyId, secrecy: key::Secrecy) -> (KeyId, bool) {
+            (id, secrecy == key::Secrecy::Public)
+        }
+
+        let mut found = Vec::<KeyListing, { config::MAX_OBJECT_HANDLES }>::new();
+        let mut count = 0;
+
+        self.for_each_key(None, location, |secrecy, location, id, key| {
+            if kind.is_some() && kind != Some(key.kind) {
+                return;
+            }
+
+            count += 1;
+            if matches!(after, Some((after_id, after_secrecy)) if order(id, secrecy) <= order(*after_id, *after_secrecy)) {
+                return;
+            }
+            store::keep_smallest(&mut found, (id, key.kind, secrecy, location), |(id, _, secrecy, _)| order(*id, *secrecy));
+        })?;
+
+        Ok((found, count))
+    }
+
+}
+
+impl<P: Platform> Keystore for ClientKeystore<P> {
+
+    fn rng(&mut self) -> &mut ChaCha8Rng {
+        &mut self.rng
+    }
+
+    #[inline(never)]
+    fn store_key(&mut self, location: Location, secrecy: key::Secrecy, info: impl Into<key::Info>, material: &[u8]) -> Result<KeyId> {
+        // info_now!("storing {:?} -> {:?}", &key_kind, location);
         let id = self.generate_key_id();
-        let path = self.key_path(secrecy, &id);
-        store::store(self.store, location, &path, &key.serialize())?;
+        self.write_key(location, secrecy, &id, info.into(), material)?;
+        Ok(id)
+    }
 
+    fn store_keypair(&mut self, location: Location, info: impl Into<key::Info>, secret_material: &[u8], public_material: &[u8]) -> Result<KeyId> {
+        let id = self.store_key(location, key::Secrecy::Secret, info, secret_material)?;
+        if let Err(error) = self.store_public_half(location, &id, public_material) {
+            self.delete_key(&id);
+            return Err(error);
+        }
         Ok(id)
     }
 
+    fn store_public_half(&mut self, location: Location, id: &KeyId, material: &[u8]) -> Result<()> {
+        // the public half is labeled and tagged like the private half, nothing more
+        let secret = self.key_info(key::Secrecy::Secret, id).ok_or(Error::NoSuchKey)?;
+        let mut info = key::Info::from(secret.kind);
+        info.metadata.label = secret.metadata.label;
+        info.metadata.tag = secret.metadata.tag;
+        self.write_key(location, key::Secrecy::Public, id, info, material)
+    }
+
     fn exists_key(&self, secrecy: key::Secrecy, kind: Option<key::Kind>, id: &KeyId) -> bool {
         self.load_key(secrecy, kind, id).is_ok()
     }
 
     fn key_info(&self, secrecy: key::Secrecy, id: &KeyId) -> Option<key::Info> {
-        self.load_key(secrecy, None, id).map(|key| key::Info { flags: key.flags, kind: key.kind }).ok()
+        self.load_key(secrecy, None, id).map(|key| key::Info {
+            flags: key.flags,
+            kind: key.kind,
+            policy: key.policy,
+            limits: key.limits,
+            metadata: key.metadata,
+        }).ok()
     }
 
     // TODO: is this an Oracle?
@@ -119,12 +507,63 @@ impl<P: Platform> Keystore for ClientKeystore<P> {
             Location::Volatile,
         ];
 
-        secrecies.iter().any(|secrecy| {
+        // both halves of a keypair, and any copies left behind by an interrupted move
+        let mut deleted = false;
+        for secrecy in secrecies.iter() {
             let path = self.key_path(*secrecy, &id);
-            locations.iter().any(|location| {
-                store::delete(self.store, *location, &path)
-            })
-        })
+            for location in locations.iter() {
+                deleted |= store::delete(self.store, *location, &path);
+            }
+        }
+        deleted
+    }
+
+    fn delete_public_key(&self, id: &KeyId) -> bool {
+        let path = self.key_path(key::Secrecy::Public, id);
+        let mut deleted = false;
+        for location in [Location::Internal, Location::External, Location::Volatile] {
+            deleted |= store::delete(self.store, location, &path);
+        }
+        deleted
+    }
+
+    /// Moves the key, or both halves of a keypair, to another filesystem, keeping its `KeyId`.
+    ///
+    /// The new file is written before the old one is removed, so the key stays
+    /// loadable throughout; an interrupted move leaves an identical copy behind.
+    /// Only extractable secret keys may be moved to external flash.
+    fn change_location(&self, id: &KeyId, location: Location) -> Result<()> {
+        let secret = self.location(key::Secrecy::Secret, id);
+        let public = self.location(key::Secrecy::Public, id);
+        if secret.is_none() && public.is_none() {
+            return Err(Error::NoSuchKey);
+        }
+
+        // non-extractable secrets may be created in external flash, but are not moved there
+        if secret.is_some() && location == Location::External {
+            let key = self.load_key(key::Secrecy::Secret, None, id)?;
+            if !key.flags.contains(key::Flags::EXTRACTABLE) {
+                return Err(Error::KeyLocationNotPermitted);
+            }
+        }
+        for secrecy in [key::Secrecy::Secret, key::Secrecy::Public] {
+            if self.location(secrecy, id).is_some() {
+                self.check_storable(location, &self.load_key(secrecy, None, id)?)?;
+            }
+        }
+
+        for (secrecy, current) in [(key::Secrecy::Secret, secret), (key::Secrecy::Public, public)] {
+            let current = match current {
+                Some(current) if current != location => current,
+                _ => continue,
+            };
+            let path = self.key_path(secrecy, id);
+            // encrypted keys can be moved as they are, as their path stays the same
+            let bytes: Bytes<MAX_STORED_KEY_LENGTH> = store::read(self.store, current, &path)?;
+            store::store(self.store, location, &path, &bytes)?;
+            store::delete(self.store, current, &path);
+        }
+        Ok(())
     }
 
     /// TODO: This uses the predicate "filename.len() >= 4"
@@ -146,9 +585,7 @@ impl<P: Platform> Keystore for ClientKeystore<P> {
 
         let location = self.location(secrecy, id).ok_or(Error::NoSuchKey)?;
 
-        let bytes: Bytes<128> = store::read(self.store, location, &path)?;
-
-        let key = key::Key::try_deserialize(&bytes)?;
+        let key = self.read_key(location, &path)?;
 
         if let Some(kind) = kind {
             if key.kind != kind {
@@ -158,23 +595,65 @@ impl<P: Platform> Keystore for ClientKeystore<P> {
         Ok(key)
     }
 
-    fn overwrite_key(&self, location: Location, secrecy: key::Secrecy, kind: key::Kind, id: &KeyId, material: &[u8]) -> Result<()> {
-        let mut flags = key::Flags::default();
-        if secrecy == key::Secrecy::Secret {
-            flags |= key::Flags::SENSITIVE;
-        }
+    fn overwrite_key(&mut self, location: Location, secrecy: key::Secrecy, kind: key::Kind, id: &KeyId, material: &[u8]) -> Result<()> {
+        // keep the flags, usage policy, limits and metadata of the key
+        let existing = self.load_key(secrecy, Some(kind), id)?;
         let key = key::Key {
-            flags: Default::default(),
+            flags: existing.flags,
             kind,
+            policy: existing.policy,
+            limits: existing.limits,
+            metadata: existing.metadata,
             material: key::Material::from_slice(material).unwrap(),
         };
 
         let path = self.key_path(secrecy, id);
-        store::store(self.store, location, &path, &key.serialize())?;
+        self.write_serialized_key(location, &path, &key)
+    }
 
-        Ok(())
+    fn pin_verified(&self, requirement: &key::PinRequirement) -> bool {
+        match store::pinstore::verified_at(self.store, &self.client_id, requirement.pin) {
+            None => false,
+            Some(verified_at) => match requirement.validity {
+                None => true,
+                Some(validity) => self.uptime <= verified_at + validity as u64,
+            },
+        }
     }
 
+    fn use_key(&mut self, secrecy: key::Secrecy, id: &KeyId, key: &key::Key) -> Result<()> {
+        let expired = match key.limits.deadline {
+            None => false,
+            Some(key::Deadline::Uptime(deadline)) => self.uptime >= deadline,
+            Some(key::Deadline::Time(deadline)) => match self.time {
+                Some(time) => time >= deadline,
+                // without a clock, there is no way to tell, e.g. for a key restored from
+                // another device, but the key is kept for when there is one
+                None => return Err(Error::FunctionNotSupported),
+            },
+        };
+        if expired || key.limits.remaining_uses == Some(0) {
+            self.delete_key(id);
+            return Err(Error::KeyExpired);
+        }
+
+        let location = self.location(secrecy, id).ok_or(Error::NoSuchKey)?;
+        let path = self.key_path(secrecy, id);
+        match key.limits.remaining_uses {
+            // count the use before the key is used, so an interrupted operation
+            // can not be retried for free
+            Some(1) => {
+                self.delete_key(id);
+                Ok(())
+            }
+            Some(remaining_uses) => {
+                let mut key = key.clone();
+                key.limits.remaining_uses = Some(remaining_uses - 1);
+                self.write_serialized_key(location, &path, &key)
+            }
+            None => self.upgrade_key(location, &path, key),
+        }
+    }
 
     fn location(&self, secrecy: key::Secrecy, id: &KeyId) -> Option<Location> {
         let path = self.key_path(secrecy, id);
@@ -195,3 +674,14 @@ impl<P: Platform> Keystore for ClientKeystore<P> {
     }
 
 }
+
+fn is_encrypted(file: &[u8]) -> bool {
+    file.len() >= 2 && key::Flags::from_bits_truncate(u16::from_be_bytes([file[0], file[1]])).contains(key::Flags::ENCRYPTED)
+}
+
+// The path of an encrypted key file, followed by the header in the clear.
+fn associated_data(path: &PathBuf, header: &[u8]) -> Bytes<{littlefs2::consts::PATH_MAX_PLUS_ONE + 3}> {
+    let mut associated_data = Bytes::from_slice(AsRef::<str>::as_ref(&**path).as_bytes()).unwrap();
+    associated_data.extend_from_slice(header).unwrap();
+    associated_data
+}
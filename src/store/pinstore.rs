@@ -0,0 +1,259 @@
+use chacha20::ChaCha8Rng;
+use hmac::{Hmac, Mac, NewMac};
+use littlefs2::path::PathBuf;
+use rand_core::RngCore as _;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Bytes,
+    error::{Error, Result},
+    store::{self, Store},
+    types::{ClientId, Location, PinId},
+};
+
+pub struct ClientPinstore<S>
+where
+    S: Store,
+{
+    client_id: ClientId,
+    rng: ChaCha8Rng,
+    store: S,
+    uptime: u64,
+    // derived from the key encryption key, if any
+    hash_key: Option<[u8; 32]>,
+}
+
+/// A salted, keyed hash of a PIN or PUK, with its retry counter.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct Secret {
+    salt: Bytes<16>,
+    hash: Bytes<32>,
+    retries_left: u8,
+    max_retries: u8,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct Record {
+    pin: Secret,
+    puk: Option<Secret>,
+}
+
+/// Outcome of checking a PIN or PUK.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Check {
+    pub success: bool,
+    pub retries_left: u8,
+}
+
+/// PINs with retry counters, stored in internal flash.
+///
+/// Each check decrements the retry counter before comparing, so cutting power during
+/// the comparison costs a retry. A PIN with no retries left is blocked until it is reset
+/// with its PUK. Without a PUK, or with a blocked PUK, it is blocked for good, and can only
+/// be deleted, along with the keys bound to it.
+///
+/// The hashes are keyed with the platform's key encryption key, if it has one, so a dump of the
+/// flash alone does not allow a brute force of the PIN that bypasses the retry counter.
+///
+/// Successful verification is recorded in volatile storage, so it does not survive a reboot.
+pub trait Pinstore {
+    fn set_pin(&mut self, id: PinId, pin: &[u8], retries: u8, puk: Option<(&[u8], u8)>) -> Result<()>;
+    fn verify_pin(&mut self, id: PinId, pin: &[u8]) -> Result<Check>;
+    fn change_pin(&mut self, id: PinId, old_pin: &[u8], new_pin: &[u8]) -> Result<Check>;
+    fn reset_pin(&mut self, id: PinId, puk: &[u8], new_pin: &[u8]) -> Result<Check>;
+    /// Returns the retries left for the PIN and for its PUK, if any.
+    fn pin_retries(&mut self, id: PinId) -> Result<(u8, Option<u8>)>;
+    /// Whether the PIN is blocked, and has no PUK left to reset it with.
+    fn pin_blocked_for_good(&mut self, id: PinId) -> Result<bool> {
+        let (retries_left, puk_retries_left) = self.pin_retries(id)?;
+        Ok(retries_left == 0 && puk_retries_left.unwrap_or(0) == 0)
+    }
+    /// Deletes the PIN if it is correct, as a new PIN could otherwise be set in its place,
+    /// for the keys bound to it. A blocked PIN must be reset with its PUK first.
+    ///
+    /// A PIN blocked for good is deleted without checking, so its `PinId` can be used again.
+    /// The keys bound to it must be deleted first (see `ClientKeystore::delete_keys_bound_to`).
+    fn delete_pin(&mut self, id: PinId, pin: &[u8]) -> Result<Check>;
+}
+
+impl<S: Store> ClientPinstore<S> {
+    pub fn new(client_id: ClientId, rng: ChaCha8Rng, store: S) -> Self {
+        Self { client_id, rng, store, uptime: 0, hash_key: None }
+    }
+
+    /// Keys the PIN hashes with a key derived from the key encryption key, see
+    /// `Platform::key_encryption_key`.
+    pub fn with_key_encryption_key(mut self, key_encryption_key: Option<[u8; 32]>) -> Self {
+        self.hash_key = key_encryption_key.map(|key_encryption_key| hmac_sha256(&key_encryption_key, &[b"PIN"]));
+        self
+    }
+
+    /// Sets the current uptime in seconds, recorded on successful verification.
+    pub fn with_uptime(mut self, uptime: u64) -> Self {
+        self.uptime = uptime;
+        self
+    }
+
+    fn read_record(&self, id: PinId) -> Result<Record> {
+        let path = pin_path(&self.client_id, id);
+        if !store::exists(self.store, Location::Internal, &path) {
+            return Err(Error::NoSuchPin);
+        }
+        let bytes: Bytes<256> = store::read(self.store, Location::Internal, &path)?;
+        crate::cbor_deserialize(&bytes).map_err(|_| Error::CborError)
+    }
+
+    fn write_record(&self, id: PinId, record: &Record) -> Result<()> {
+        let path = pin_path(&self.client_id, id);
+        let bytes: Bytes<256> = crate::cbor_serialize_bytes(record).map_err(|_| Error::CborError)?;
+        store::store(self.store, Location::Internal, &path, &bytes)
+    }
+
+    fn new_secret(&mut self, value: &[u8], retries: u8) -> Secret {
+        let mut salt = [0u8; 16];
+        self.rng.fill_bytes(&mut salt);
+        Secret {
+            salt: Bytes::from_slice(&salt).unwrap(),
+            hash: self.hash(&salt, value),
+            retries_left: retries,
+            max_retries: retries,
+        }
+    }
+
+    fn set_verified(&self, id: PinId) -> Result<()> {
+        let path = pin_path(&self.client_id, id);
+        store::store(self.store, Location::Volatile, &path, &self.uptime.to_be_bytes())
+    }
+
+    fn clear_verified(&self, id: PinId) {
+        let path = pin_path(&self.client_id, id);
+        store::delete(self.store, Location::Volatile, &path);
+    }
+
+    /// Checks the PIN, or the PUK if `puk` is set, against the stored record.
+    ///
+    /// The decremented retry counter is written before comparing; on success, it is reset
+    /// and the updated record is returned for further changes.
+    fn check(&mut self, id: PinId, value: &[u8], puk: bool) -> Result<(Check, Record)> {
+        let mut record = self.read_record(id)?;
+        let secret = match puk {
+            false => &mut record.pin,
+            true => record.puk.as_mut().ok_or(Error::NoSuchPin)?,
+        };
+        if secret.retries_left == 0 {
+            return Err(Error::PinBlocked);
+        }
+        secret.retries_left -= 1;
+        let salt = secret.salt.clone();
+        let stored_hash = secret.hash.clone();
+        self.write_record(id, &record)?;
+        self.clear_verified(id);
+
+        let success = constant_time_eq(&self.hash(&salt, value), &stored_hash);
+        let secret = match puk {
+            false => &mut record.pin,
+            true => record.puk.as_mut().unwrap(),
+        };
+        if success {
+            secret.retries_left = secret.max_retries;
+        }
+        let check = Check { success, retries_left: secret.retries_left };
+        Ok((check, record))
+    }
+
+    fn hash(&self, salt: &[u8], value: &[u8]) -> Bytes<32> {
+        let hash = hmac_sha256(self.hash_key.as_ref().unwrap_or(&[0u8; 32]), &[salt, value]);
+        Bytes::from_slice(&hash).unwrap()
+    }
+}
+
+/// When the PIN `id` of the client was last verified, in seconds of uptime.
+pub fn verified_at(store: impl Store, client_id: &ClientId, id: PinId) -> Option<u64> {
+    let path = pin_path(client_id, id);
+    let bytes: Bytes<8> = store::read(store, Location::Volatile, &path).ok()?;
+    let mut uptime = [0u8; 8];
+    uptime.copy_from_slice(bytes.get(..8)?);
+    Some(u64::from_be_bytes(uptime))
+}
+
+// Records live in internal flash, verification times in volatile storage, under the same path.
+fn pin_path(client_id: &ClientId, id: PinId) -> PathBuf {
+    let mut path = PathBuf::new();
+    path.push(client_id);
+    path.push(&PathBuf::from("pin"));
+    path.push(&PathBuf::from(id.hex().as_slice()));
+    path
+}
+
+fn hmac_sha256(key: &[u8; 32], parts: &[&[u8]]) -> [u8; 32] {
+    let mut mac = Hmac::<sha2::Sha256>::new_from_slice(key).unwrap();
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b.iter()).fold(0u8, |difference, (x, y)| difference | (x ^ y)) == 0
+}
+
+impl<S: Store> Pinstore for ClientPinstore<S> {
+    fn set_pin(&mut self, id: PinId, pin: &[u8], retries: u8, puk: Option<(&[u8], u8)>) -> Result<()> {
+        // changing a PIN requires the old one, or the PUK
+        if self.read_record(id).is_ok() {
+            return Err(Error::PinAlreadySet);
+        }
+        if retries == 0 || matches!(puk, Some((_, 0))) {
+            return Err(Error::InvalidPinRetries);
+        }
+        let record = Record {
+            pin: self.new_secret(pin, retries),
+            puk: puk.map(|(puk, retries)| self.new_secret(puk, retries)),
+        };
+        self.write_record(id, &record)
+    }
+
+    fn verify_pin(&mut self, id: PinId, pin: &[u8]) -> Result<Check> {
+        let (check, record) = self.check(id, pin, false)?;
+        if check.success {
+            self.write_record(id, &record)?;
+            self.set_verified(id)?;
+        }
+        Ok(check)
+    }
+
+    fn change_pin(&mut self, id: PinId, old_pin: &[u8], new_pin: &[u8]) -> Result<Check> {
+        let (check, mut record) = self.check(id, old_pin, false)?;
+        if check.success {
+            record.pin = self.new_secret(new_pin, record.pin.max_retries);
+            self.write_record(id, &record)?;
+        }
+        Ok(check)
+    }
+
+    fn reset_pin(&mut self, id: PinId, puk: &[u8], new_pin: &[u8]) -> Result<Check> {
+        let (check, mut record) = self.check(id, puk, true)?;
+        if check.success {
+            record.pin = self.new_secret(new_pin, record.pin.max_retries);
+            self.write_record(id, &record)?;
+        }
+        Ok(check)
+    }
+
+    fn pin_retries(&mut self, id: PinId) -> Result<(u8, Option<u8>)> {
+        let record = self.read_record(id)?;
+        Ok((record.pin.retries_left, record.puk.map(|puk| puk.retries_left)))
+    }
+
+    fn delete_pin(&mut self, id: PinId, pin: &[u8]) -> Result<Check> {
+        let check = match self.pin_blocked_for_good(id)? {
+            true => Check { success: true, retries_left: 0 },
+            false => self.check(id, pin, false)?.0,
+        };
+        if check.success {
+            let path = pin_path(&self.client_id, id);
+            store::delete(self.store, Location::Internal, &path);
+        }
+        Ok(check)
+    }
+}
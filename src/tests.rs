@@ -70,6 +70,10 @@ impl crate::platform::UserInterface for UserInterface
         core::time::Duration::from_millis(1000)
     }
 
+    fn time(&mut self) -> Option<core::time::Duration> {
+        Some(core::time::Duration::from_secs(1_600_000_000))
+    }
+
     fn reboot(&mut self, to: reboot::To) -> ! {
         println!("Restart!  ({:?})", to);
         std::process::exit(25);
@@ -165,6 +169,18 @@ macro_rules! setup {
         setup!($client, Store, Platform, memory, [0u8; 32], true);
     };
     ($client:ident, $store:ident, $platform: ident, $memory:expr, $seed:expr, $reformat: expr) => {
+        setup!($client, $store, $platform, $memory, $seed, $reformat, None);
+    };
+    ($client:ident, $store:ident, $platform: ident, $memory:expr, $seed:expr, $reformat: expr, $key_encryption_key: expr) => {
+        setup!($client, $store, $platform, $memory, $seed, $reformat, $key_encryption_key, false);
+    };
+    ($client:ident, $store:ident, $platform: ident, $memory:expr, $seed:expr, $reformat: expr, $key_encryption_key: expr, $provisioner: expr) => {
+        setup!($client, $store, $platform, $memory, $seed, $reformat, $key_encryption_key, $provisioner, None);
+    };
+    ($client:ident, $store:ident, $platform: ident, $memory:expr, $seed:expr, $reformat: expr, $key_encryption_key: expr, $provisioner: expr, $dice_inputs: expr) => {
+        setup!($client, $store, $platform, $memory, $seed, $reformat, $key_encryption_key, $provisioner, $dice_inputs, false);
+    };
+    ($client:ident, $store:ident, $platform: ident, $memory:expr, $seed:expr, $reformat: expr, $key_encryption_key: expr, $provisioner: expr, $dice_inputs: expr, $encrypt_stored_keys: expr) => {
 
 
             store!($store,
@@ -194,7 +210,15 @@ macro_rules! setup {
             let rng = MockRng::new();
             let pc_interface: UserInterface = Default::default();
 
-            let platform = $platform::new(rng, store, pc_interface);
+            let mut platform = $platform::new(rng, store, pc_interface);
+            let key_encryption_key: Option<[u8; 32]> = $key_encryption_key;
+            if let Some(key_encryption_key) = key_encryption_key {
+                platform = platform.with_key_encryption_key(key_encryption_key);
+            }
+            let dice_inputs: Option<crate::platform::DiceInputs> = $dice_inputs;
+            if let Some(dice_inputs) = dice_inputs {
+                platform = platform.with_dice_inputs(dice_inputs);
+            }
             let mut trussed: crate::Service<$platform> = crate::service::Service::new(platform);
 
             unsafe { crate::pipe::TrussedInterchange::reset_claims(); }
@@ -202,9 +226,15 @@ macro_rules! setup {
                 .expect("could not setup TEST TrussedInterchange");
             let test_client_id = "TEST".into();
 
-            assert!(trussed.add_endpoint(test_trussed_responder, test_client_id).is_ok());
+            match $provisioner {
+                true => assert!(trussed.add_provisioner_endpoint(test_trussed_responder, test_client_id).is_ok()),
+                false => assert!(trussed.add_endpoint(test_trussed_responder, test_client_id).is_ok()),
+            }
 
             trussed.set_seed_if_uninitialized(&$seed);
+            if $encrypt_stored_keys {
+                trussed.encrypt_stored_keys("TEST").expect("no errors");
+            }
             let mut $client = {
                 pub type TestClient<'a> = crate::ClientImplementation<&'a mut crate::Service<$platform>>;
                 TestClient::new(
@@ -460,3 +490,1662 @@ fn rng() {
 
 }
 
+
+#[test]
+#[serial]
+fn generate_symmetric_keys() {
+    use crate::client::mechanisms::{Aes256Cbc, HmacSha1, HmacSha256, P256, Tdes, Totp};
+    setup!(client);
+
+    let hmac_key = block!(client.generate_hmacsha256_key(Location::Volatile).expect("no client error"))
+        .expect("no errors").key;
+    let signature = block!(client.sign_hmacsha256(hmac_key, b"message").expect("no client error"))
+        .expect("no errors").signature;
+    assert_eq!(signature.len(), 32);
+
+    let hmac_key = block!(client.generate_hmacsha1_key(Location::Volatile).expect("no client error"))
+        .expect("no errors").key;
+    assert!(block!(client.sign_hmacsha1(hmac_key, b"message").expect("no client error")).is_ok());
+
+    let totp_key = block!(client.generate_totp_key(Location::Volatile).expect("no client error"))
+        .expect("no errors").key;
+    assert!(block!(client.exists(Mechanism::Totp, totp_key).expect("no client error"))
+        .expect("no errors").exists);
+    assert!(block!(client.sign_totp(totp_key, 59).expect("no client error")).is_ok());
+
+    let trng_key = block!(client.generate_key(Mechanism::Trng, StorageAttributes::new()).expect("no client error"))
+        .expect("no errors").key;
+    assert!(block!(client.sign_hmacsha256(trng_key, b"message").expect("no client error")).is_ok());
+
+    // mechanisms check the kind of the keys they are given
+    let aes_key = block!(client.generate_aes256cbc_key(Location::Volatile).expect("no client error"))
+        .expect("no errors").key;
+    let tdes_key = block!(client.generate_tdes_key(Location::Volatile).expect("no client error"))
+        .expect("no errors").key;
+    let p256_key = block!(client.generate_p256_private_key(Location::Volatile).expect("no client error"))
+        .expect("no errors").key;
+
+    assert!(block!(client.encrypt_tdes(tdes_key, &[0u8; 8]).expect("no client error")).is_ok());
+    assert_eq!(
+        block!(client.encrypt_tdes(aes_key, &[0u8; 8]).expect("no client error")).map(drop),
+        Err(Error::WrongKeyKind),
+    );
+    assert_eq!(
+        block!(client.decrypt_aes256cbc(tdes_key, &[0u8; 16]).expect("no client error")).map(drop),
+        Err(Error::WrongKeyKind),
+    );
+    assert_eq!(
+        block!(client.sign_hmacsha256(p256_key, b"message").expect("no client error")).map(drop),
+        Err(Error::WrongKeyKind),
+    );
+}
+
+#[test]
+#[serial]
+fn unsafe_inject_key() {
+    use crate::client::mechanisms::{Ed255, P256, Totp};
+    setup!(client);
+
+    // P256 secret scalar one has the base point as public key
+    let mut scalar = [0u8; 32];
+    scalar[31] = 1;
+    let private_key = block!(client.unsafe_inject_key(Mechanism::P256, &scalar, Location::Volatile).expect("no client error"))
+        .expect("no errors").key;
+    let public_key = block!(client.derive_p256_public_key(private_key, Location::Volatile).expect("no client error"))
+        .expect("no errors").key;
+    let serialized_key = block!(client.serialize_p256_key(public_key, KeySerialization::Raw).expect("no client error"))
+        .expect("no errors").serialized_key;
+    assert_eq!(&serialized_key[..], &hex_literal::hex!("
+        6B17D1F2E12C4247F8BCE6E563A440F277037D812DEB33A0F4A13945D898C296
+        4FE342E2FE1A7F9B8EE7EB4A7C0F9E162BCE33576B315ECECBB6406837BF51F5
+    ")[..]);
+
+    // invalid keys are rejected
+    assert_eq!(
+        block!(client.unsafe_inject_key(Mechanism::P256, &[0u8; 32], Location::Volatile).expect("no client error")).map(drop),
+        Err(Error::InvalidSerializedKey),
+    );
+    assert_eq!(
+        block!(client.unsafe_inject_key(Mechanism::P256, &hex_literal::hex!(
+            "FFFFFFFF00000000FFFFFFFFFFFFFFFFBCE6FAADA7179E84F3B9CAC2FC632551"
+        ), Location::Volatile).expect("no client error")).map(drop),
+        Err(Error::InvalidSerializedKey),
+    );
+    assert_eq!(
+        block!(client.unsafe_inject_key(Mechanism::Ed255, &[1u8; 31], Location::Volatile).expect("no client error")).map(drop),
+        Err(Error::InvalidSerializedKey),
+    );
+    assert_eq!(
+        block!(client.unsafe_inject_key(Mechanism::Aes128Ecb, &[1u8; 32], Location::Volatile).expect("no client error")).map(drop),
+        Err(Error::InvalidSerializedKey),
+    );
+
+    // RFC 8032, section 7.1, test 1
+    let private_key = block!(client.unsafe_inject_key(Mechanism::Ed255, &hex_literal::hex!(
+        "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60"
+    ), Location::Volatile).expect("no client error")).expect("no errors").key;
+    let public_key = block!(client.derive_ed255_public_key(private_key, Location::Volatile).expect("no client error"))
+        .expect("no errors").key;
+    let serialized_key = block!(client.serialize_ed255_key(public_key, KeySerialization::Raw).expect("no client error"))
+        .expect("no errors").serialized_key;
+    assert_eq!(&serialized_key[..], &hex_literal::hex!(
+        "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a"
+    )[..]);
+
+    // RFC 6238, appendix B
+    let totp_key = block!(client.unsafe_inject_key(Mechanism::Totp, b"12345678901234567890", Location::Volatile).expect("no client error"))
+        .expect("no errors").key;
+    let otp = block!(client.sign_totp(totp_key, 59 / 30).expect("no client error"))
+        .expect("no errors").signature;
+    assert_eq!(&otp[..], &287082u64.to_le_bytes()[..]);
+}
+
+#[test]
+#[serial]
+fn key_usage_policy() {
+    use crate::client::mechanisms::{Aes128, P256};
+    use crate::key::{Policy, Usage};
+    setup!(client);
+
+    // signing only, persisted to check the policy survives storage
+    let attributes = StorageAttributes::new()
+        .set_persistence(Location::Internal)
+        .set_policy(Policy::new().set_usage(Usage::SIGN));
+    let private_key = block!(client.generate_key(Mechanism::P256, attributes).expect("no client error"))
+        .expect("no errors").key;
+    block!(client.sign(Mechanism::P256, private_key, &[0u8; 32], SignatureSerialization::Raw).expect("no client error"))
+        .expect("no errors");
+    let public_key = block!(client.derive_p256_public_key(private_key, Location::Volatile).expect("no client error"))
+        .expect("no errors").key;
+    assert_eq!(
+        block!(client.agree(Mechanism::P256, private_key, public_key, StorageAttributes::new())
+            .expect("no client error")).map(drop),
+        Err(Error::KeyFunctionNotPermitted),
+    );
+
+    // deriving only, and only with HMAC-SHA256
+    let attributes = StorageAttributes::new()
+        .set_policy(Policy::new().set_usage(Usage::DERIVE).set_mechanisms(&[Mechanism::HmacSha256]).unwrap());
+    let secret = block!(client.generate_key(Mechanism::HmacSha256, attributes).expect("no client error"))
+        .expect("no errors").key;
+    block!(client.derive_key(Mechanism::HmacSha256, secret, None, StorageAttributes::new()).expect("no client error"))
+        .expect("no errors");
+    assert_eq!(
+        block!(client.sign(Mechanism::HmacSha256, secret, &[], SignatureSerialization::Raw)
+            .expect("no client error")).map(drop),
+        Err(Error::KeyFunctionNotPermitted),
+    );
+    assert_eq!(
+        block!(client.derive_key(Mechanism::Sha256, secret, None, StorageAttributes::new())
+            .expect("no client error")).map(drop),
+        Err(Error::KeyFunctionNotPermitted),
+    );
+
+    assert_eq!(
+        Policy::new().set_mechanisms(&[Mechanism::Aes128Cbc; crate::config::MAX_ALLOWED_MECHANISMS + 1]),
+        Err(Error::TooManyMechanisms),
+    );
+
+    // encryption only, and only in CBC mode
+    let attributes = StorageAttributes::new()
+        .set_policy(Policy::new().set_usage(Usage::ENCRYPT).set_mechanisms(&[Mechanism::Aes128Cbc]).unwrap());
+    let key = block!(client.generate_key(Mechanism::Aes128Cbc, attributes).expect("no client error"))
+        .expect("no errors").key;
+    let ciphertext = block!(client.encrypt_aes128cbc(key, &[0u8; 16], None).expect("no client error"))
+        .expect("no errors").ciphertext;
+    assert_eq!(
+        block!(client.decrypt_aes128cbc(key, &ciphertext, &[0u8; 16]).expect("no client error")).map(drop),
+        Err(Error::KeyFunctionNotPermitted),
+    );
+    assert_eq!(
+        block!(client.encrypt_aes128ecb(key, &[0u8; 16]).expect("no client error")).map(drop),
+        Err(Error::KeyFunctionNotPermitted),
+    );
+}
+
+#[test]
+#[serial]
+fn extractable_keys() {
+    setup!(client);
+
+    let wrapping_key = block!(client.generate_key(Mechanism::Chacha8Poly1305, StorageAttributes::new()).expect("no client error"))
+        .expect("no errors").key;
+
+    // keys are not extractable by default
+    let key = block!(client.generate_key(Mechanism::P256, StorageAttributes::new()).expect("no client error"))
+        .expect("no errors").key;
+    assert_eq!(
+        block!(client.wrap_key(Mechanism::Chacha8Poly1305, wrapping_key, key, &[]).expect("no client error")).map(drop),
+        Err(Error::KeyUnextractable),
+    );
+
+    let attributes = StorageAttributes::new().set_extractable(true);
+    let key = block!(client.generate_key(Mechanism::P256, attributes).expect("no client error"))
+        .expect("no errors").key;
+    let wrapped_key = block!(client.wrap_key(Mechanism::Chacha8Poly1305, wrapping_key, key, &[]).expect("no client error"))
+        .expect("no errors").wrapped_key;
+    let unwrapped_key = block!(client.unwrap_key(Mechanism::Chacha8Poly1305, wrapping_key, wrapped_key, &[], StorageAttributes::new())
+        .expect("no client error")).expect("no errors").key.expect("unwrapped");
+    block!(client.sign(Mechanism::P256, unwrapped_key, &[0u8; 32], SignatureSerialization::Raw).expect("no client error"))
+        .expect("no errors");
+    // ...and stay extractable
+    block!(client.wrap_key(Mechanism::Chacha8Poly1305, wrapping_key, unwrapped_key, &[]).expect("no client error"))
+        .expect("no errors");
+}
+
+#[test]
+#[serial]
+fn find_objects() {
+    const MAX_OBJECT_HANDLES: usize = crate::config::MAX_OBJECT_HANDLES;
+    use crate::key::{Kind, Secrecy};
+    setup!(client);
+
+    let attributes = StorageAttributes::new().set_persistence(Location::Internal).set_label(b"signing");
+    let signing_key = block!(client.generate_key(Mechanism::Ed255, attributes).expect("no client error"))
+        .expect("no errors").key;
+    let found = block!(client.find_objects(None, None, None, Some(b"signing"), None).expect("no client error"))
+        .expect("no errors");
+    assert_eq!(found.num_objects, 1);
+    assert_eq!(&found.objects[..], &[signing_key]);
+    // the public half shares the label, and is found on its own
+    let found = block!(client.find_objects(Some(Secrecy::Public), None, None, Some(b"signing"), None).expect("no client error"))
+        .expect("no errors");
+    assert_eq!(&found.objects[..], &[signing_key]);
+    let found = block!(client.find_objects(None, None, Some(Location::Volatile), Some(b"signing"), None).expect("no client error"))
+        .expect("no errors");
+    assert_eq!(found.num_objects, 0);
+
+    // more keys than fit in one reply
+    let total = MAX_OBJECT_HANDLES + 4;
+    for _ in 0..total {
+        let attributes = StorageAttributes::new().set_label(b"session").set_tag(&[1, 2, 3]);
+        block!(client.generate_key(Mechanism::HmacSha256, attributes).expect("no client error"))
+            .expect("no errors");
+    }
+    let first = block!(client.find_objects(None, Some(Kind::Symmetric(32)), None, Some(b"session"), None).expect("no client error"))
+        .expect("no errors");
+    assert_eq!(first.num_objects, total);
+    assert_eq!(first.objects.len(), MAX_OBJECT_HANDLES);
+    let last = first.objects.last().copied();
+    let second = block!(client.find_objects(None, Some(Kind::Symmetric(32)), None, Some(b"session"), last).expect("no client error"))
+        .expect("no errors");
+    assert_eq!(second.num_objects, total);
+    assert_eq!(second.objects.len(), 4);
+    assert!(first.objects.iter().all(|id| !second.objects.contains(id)));
+
+    let found = block!(client.find_objects(None, Some(Kind::Ed255), None, None, None).expect("no client error"))
+        .expect("no errors");
+    assert_eq!(&found.objects[..], &[signing_key]);
+}
+
+#[test]
+#[serial]
+fn list_keys() {
+    const MAX_OBJECT_HANDLES: usize = crate::config::MAX_OBJECT_HANDLES;
+    use crate::key::{Kind, Secrecy};
+    setup!(client);
+
+    let attributes = StorageAttributes::new().set_persistence(Location::Internal);
+    let keypair = block!(client.generate_key(Mechanism::P256, attributes).expect("no client error"))
+        .expect("no errors").key;
+    let attributes = StorageAttributes::new().set_persistence(Location::Volatile);
+    let volatile_key = block!(client.generate_key(Mechanism::HmacSha256, attributes).expect("no client error"))
+        .expect("no errors").key;
+
+    let listed = block!(client.list_keys(None, None, None).expect("no client error")).expect("no errors");
+    assert_eq!(listed.num_keys, 3);
+    // both halves of the keypair are listed, secret first
+    let position = listed.keys.iter().position(|(id, ..)| *id == keypair).unwrap();
+    assert_eq!(&listed.keys[position..position + 2], &[
+        (keypair, Kind::P256, Secrecy::Secret, Location::Internal),
+        (keypair, Kind::P256, Secrecy::Public, Location::Internal),
+    ]);
+    let listed = block!(client.list_keys(None, Some(Location::Volatile), None).expect("no client error"))
+        .expect("no errors");
+    assert_eq!(&listed.keys[..], &[(volatile_key, Kind::Symmetric(32), Secrecy::Secret, Location::Volatile)]);
+
+    // more keys than fit in one reply
+    for _ in 0..MAX_OBJECT_HANDLES {
+        block!(client.generate_key(Mechanism::HmacSha256, StorageAttributes::new()).expect("no client error"))
+            .expect("no errors");
+    }
+    let total = MAX_OBJECT_HANDLES + 3;
+    let first = block!(client.list_keys(None, None, None).expect("no client error")).expect("no errors");
+    assert_eq!(first.num_keys, total);
+    assert_eq!(first.keys.len(), MAX_OBJECT_HANDLES);
+    let last = first.keys.last().map(|(id, _, secrecy, _)| (*id, *secrecy));
+    let second = block!(client.list_keys(None, None, last).expect("no client error")).expect("no errors");
+    assert_eq!(second.keys.len(), 3);
+    assert!(first.keys.iter().all(|key| !second.keys.contains(key)));
+
+    let listed = block!(client.list_keys(Some(Kind::P256), None, None).expect("no client error")).expect("no errors");
+    assert_eq!(listed.num_keys, 2);
+}
+
+#[test]
+#[serial]
+fn get_key_info() {
+    use crate::client::mechanisms::P256;
+    use crate::key::{Flags, Kind, Policy, Secrecy, Usage};
+    setup!(client);
+
+    let policy = Policy::new().set_usage(Usage::SIGN | Usage::VERIFY);
+    let attributes = StorageAttributes::new()
+        .set_persistence(Location::Internal)
+        .set_policy(policy.clone())
+        .set_label(b"identity");
+    let private_key = block!(client.generate_key(Mechanism::P256, attributes).expect("no client error"))
+        .expect("no errors").key;
+    let info = block!(client.get_key_info(private_key).expect("no client error")).expect("no errors");
+    assert_eq!(info.secrecy, Secrecy::Secret);
+    assert_eq!(info.kind, Kind::P256);
+    assert_eq!(info.flags, Flags::LOCAL | Flags::SENSITIVE | Flags::NEVER_EXTRACTABLE);
+    assert_eq!(info.location, Location::Internal);
+    assert_eq!(info.size, 32);
+    assert_eq!(info.policy, policy);
+    assert_eq!(info.metadata.label.as_ref().map(|label| &label[..]), Some(&b"identity"[..]));
+    assert_eq!(info.metadata.created, Some(1_600_000_000));
+    assert_eq!(info.metadata.tag, None);
+
+    let serialized_key = block!(client.serialize_p256_key(private_key, KeySerialization::Raw).expect("no client error"))
+        .expect("no errors").serialized_key;
+    let public_key = block!(client.deserialize_p256_key(
+        &serialized_key,
+        KeySerialization::Raw,
+        StorageAttributes::new().set_persistence(Location::Volatile),
+    ).expect("no client error")).expect("no errors").key;
+    let info = block!(client.get_key_info(public_key).expect("no client error")).expect("no errors");
+    assert_eq!(info.secrecy, Secrecy::Public);
+    assert_eq!(info.kind, Kind::P256);
+    assert!(!info.flags.contains(Flags::LOCAL));
+    assert_eq!(info.location, Location::Volatile);
+
+    block!(client.delete(public_key).expect("no client error")).expect("no errors");
+    assert_eq!(
+        block!(client.get_key_info(public_key).expect("no client error")).map(drop),
+        Err(Error::NoSuchKey),
+    );
+}
+
+#[test]
+#[serial]
+fn change_key_location() {
+    use crate::client::mechanisms::P256;
+    use crate::key::Secrecy;
+    setup!(client);
+
+    let private_key = block!(client.generate_p256_private_key(Location::Volatile).expect("no client error"))
+        .expect("no errors").key;
+    block!(client.change_key_location(private_key, Location::Internal).expect("no client error"))
+        .expect("no errors");
+    let info = block!(client.get_key_info(private_key).expect("no client error")).expect("no errors");
+    assert_eq!(info.location, Location::Internal);
+    // the public half moves along
+    let found = block!(client.find_objects(Some(Secrecy::Public), None, Some(Location::Internal), None, None)
+        .expect("no client error")).expect("no errors");
+    assert_eq!(&found.objects[..], &[private_key]);
+
+    // key is still usable under the same handle
+    let message = [1u8, 2u8, 3u8];
+    block!(client.sign_p256(private_key, &message, SignatureSerialization::Raw).expect("no client error"))
+        .expect("no errors");
+
+    // non-extractable secrets stay out of external flash
+    assert_eq!(
+        block!(client.change_key_location(private_key, Location::External).expect("no client error")).map(drop),
+        Err(Error::KeyLocationNotPermitted),
+    );
+    // only moving them there is refused, creating keys there works as it always did
+    block!(client.generate_key(Mechanism::P256, StorageAttributes::new().set_persistence(Location::External))
+        .expect("no client error")).expect("no errors");
+
+    let serialized_key = block!(client.serialize_p256_key(private_key, KeySerialization::Raw).expect("no client error"))
+        .expect("no errors").serialized_key;
+    let public_key = block!(client.deserialize_p256_key(
+        &serialized_key,
+        KeySerialization::Raw,
+        StorageAttributes::new().set_persistence(Location::Volatile),
+    ).expect("no client error")).expect("no errors").key;
+    block!(client.change_key_location(public_key, Location::External).expect("no client error"))
+        .expect("no errors");
+    let info = block!(client.get_key_info(public_key).expect("no client error")).expect("no errors");
+    assert_eq!(info.location, Location::External);
+
+    let attributes = StorageAttributes::new().set_persistence(Location::Volatile).set_extractable(true);
+    let shared_key = block!(client.generate_key(Mechanism::Chacha8Poly1305, attributes).expect("no client error"))
+        .expect("no errors").key;
+    block!(client.change_key_location(shared_key, Location::External).expect("no client error"))
+        .expect("no errors");
+
+    block!(client.delete(private_key).expect("no client error")).expect("no errors");
+    assert_eq!(
+        block!(client.change_key_location(private_key, Location::Internal).expect("no client error")).map(drop),
+        Err(Error::NoSuchKey),
+    );
+}
+
+#[test]
+#[serial]
+fn key_usage_limits() {
+    use crate::client::mechanisms::P256;
+    use crate::key::{Deadline, Limits};
+    setup!(client);
+    let message = [1u8, 2u8, 3u8];
+
+    let attributes = StorageAttributes::new().set_limits(Limits::new().set_remaining_uses(2));
+    let private_key = block!(client.generate_key(Mechanism::P256, attributes).expect("no client error"))
+        .expect("no errors").key;
+    // looking at the key is not a use
+    let info = block!(client.get_key_info(private_key).expect("no client error")).expect("no errors");
+    assert_eq!(info.limits.remaining_uses, Some(2));
+    for _ in 0..2 {
+        block!(client.sign_p256(private_key, &message, SignatureSerialization::Raw).expect("no client error"))
+            .expect("no errors");
+    }
+    assert_eq!(
+        block!(client.sign_p256(private_key, &message, SignatureSerialization::Raw).expect("no client error")).map(drop),
+        Err(Error::NoSuchKey),
+    );
+
+    // the test platform has been up for a second, at 1_600_000_000 seconds after the epoch
+    for (deadline, valid) in [
+        (Deadline::Uptime(1), false),
+        (Deadline::Uptime(60), true),
+        (Deadline::Time(1_600_000_000), false),
+        (Deadline::Time(1_600_000_060), true),
+    ] {
+        let attributes = StorageAttributes::new().set_limits(Limits::new().set_deadline(deadline));
+        let private_key = block!(client.generate_key(Mechanism::P256, attributes).expect("no client error"))
+            .expect("no errors").key;
+        let result = block!(client.sign_p256(private_key, &message, SignatureSerialization::Raw).expect("no client error"));
+        if valid {
+            result.expect("no errors");
+        } else {
+            assert_eq!(result.map(drop), Err(Error::KeyExpired));
+            // expired keys are gone
+            assert_eq!(
+                block!(client.get_key_info(private_key).expect("no client error")).map(drop),
+                Err(Error::NoSuchKey),
+            );
+        }
+    }
+
+    // the uptime restarts at boot
+    let attributes = StorageAttributes::new()
+        .set_persistence(Location::Internal)
+        .set_limits(Limits::new().set_deadline(Deadline::Uptime(60)));
+    assert_eq!(
+        block!(client.generate_key(Mechanism::P256, attributes).expect("no client error")).map(drop),
+        Err(Error::KeyLocationNotPermitted),
+    );
+    let attributes = StorageAttributes::new().set_limits(Limits::new().set_deadline(Deadline::Uptime(60)));
+    let private_key = block!(client.generate_key(Mechanism::P256, attributes).expect("no client error"))
+        .expect("no errors").key;
+    assert_eq!(
+        block!(client.change_key_location(private_key, Location::Internal).expect("no client error")).map(drop),
+        Err(Error::KeyLocationNotPermitted),
+    );
+
+    // copies would not share the limits
+    let attributes = StorageAttributes::new()
+        .set_extractable(true)
+        .set_limits(Limits::new().set_remaining_uses(2));
+    let private_key = block!(client.generate_key(Mechanism::P256, attributes).expect("no client error"))
+        .expect("no errors").key;
+    let flags = block!(client.get_key_info(private_key).expect("no client error")).expect("no errors").flags;
+    assert!(!flags.contains(crate::key::Flags::EXTRACTABLE));
+}
+
+#[test]
+#[serial]
+fn linked_keypairs() {
+    use crate::client::mechanisms::{Ed255, P256};
+    use crate::key::Secrecy;
+    setup!(client);
+    let message = [1u8, 2u8, 3u8];
+
+    let private_key = block!(client.generate_ed255_private_key(Location::Internal).expect("no client error"))
+        .expect("no errors").key;
+    // the public half exists from the start, under the same handle
+    let found = block!(client.find_objects(Some(Secrecy::Public), None, None, None, None).expect("no client error"))
+        .expect("no errors");
+    assert_eq!(&found.objects[..], &[private_key]);
+    let signature = block!(client.sign_ed255(private_key, &message).expect("no client error"))
+        .expect("no errors").signature;
+    assert!(block!(client.verify_ed255(private_key, &message, &signature).expect("no client error"))
+        .expect("no errors").valid);
+    // listed once
+    let found = block!(client.find_objects(None, None, None, None, None).expect("no client error"))
+        .expect("no errors");
+    assert_eq!(found.num_objects, 1);
+
+    // a derived public key is a copy, so deriving, serializing and deleting it
+    // leaves the keypair alone
+    let public_key = block!(client.derive_ed255_public_key(private_key, Location::Volatile).expect("no client error"))
+        .expect("no errors").key;
+    assert_ne!(public_key, private_key);
+    block!(client.serialize_ed255_key(public_key, KeySerialization::Raw).expect("no client error"))
+        .expect("no errors");
+    block!(client.delete(public_key).expect("no client error")).expect("no errors");
+    block!(client.sign_ed255(private_key, &message).expect("no client error")).expect("no errors");
+    assert!(block!(client.verify_ed255(private_key, &message, &signature).expect("no client error"))
+        .expect("no errors").valid);
+
+    // the public half can be dropped without the private key, and derived again
+    assert!(block!(client.delete_public_key(private_key).expect("no client error")).expect("no errors").success);
+    let found = block!(client.find_objects(Some(Secrecy::Public), None, None, None, None).expect("no client error"))
+        .expect("no errors");
+    assert_eq!(found.num_objects, 0);
+    block!(client.sign_ed255(private_key, &message).expect("no client error")).expect("no errors");
+    let public_key = block!(client.derive_ed255_public_key(private_key, Location::Volatile).expect("no client error"))
+        .expect("no errors").key;
+    assert!(block!(client.verify_ed255(private_key, &message, &signature).expect("no client error"))
+        .expect("no errors").valid);
+
+    // deleting the handle deletes both halves, but not copies of the public key
+    block!(client.delete(private_key).expect("no client error")).expect("no errors");
+    let found = block!(client.find_objects(None, None, None, None, None).expect("no client error"))
+        .expect("no errors");
+    assert_eq!(&found.objects[..], &[public_key]);
+
+    // injected keys are keypairs too
+    let private_key = block!(client.unsafe_inject_key(Mechanism::P256, &[1u8; 32], Location::Volatile).expect("no client error"))
+        .expect("no errors").key;
+    let signature = block!(client.sign_p256(private_key, &message, SignatureSerialization::Raw).expect("no client error"))
+        .expect("no errors").signature;
+    assert!(block!(client.verify_p256(private_key, &message, &signature).expect("no client error"))
+        .expect("no errors").valid);
+}
+
+#[test]
+#[serial]
+fn keys_encrypted_at_rest() {
+    use crate::client::{PinClient as _, mechanisms::Ed255};
+    let message = [1u8, 2u8, 3u8];
+    let pin = PinId::from_special(1);
+
+    let mem = create_memory!();
+    let mem_same_key = create_memory!(mem);
+    let mem_other_key = create_memory!(mem);
+    let mem_no_key = create_memory!(mem);
+    let mem_plaintext = create_memory!();
+    let mem_planted = create_memory!(mem_plaintext);
+    let mem_migrated = create_memory!(mem_plaintext);
+    let mem_migrated_other_key = create_memory!(mem_migrated);
+
+    setup!(client, StoreEncrypted, PlatformEncrypted, mem, [0u8; 32], true, Some([0x42u8; 32]));
+    let private_key = block!(client.generate_ed255_private_key(Location::Internal).expect("no client error"))
+        .expect("no errors").key;
+    let signature = block!(client.sign_ed255(private_key, &message).expect("no client error"))
+        .expect("no errors").signature;
+    block!(client.set_pin(pin, b"123456", 3, None).expect("no client error")).expect("no errors");
+
+    // after a reboot, the same key encryption key opens the key again
+    setup!(client, StoreSameKey, PlatformSameKey, mem_same_key, [0u8; 32], false, Some([0x42u8; 32]));
+    assert!(block!(client.verify_ed255(private_key, &message, &signature).expect("no client error"))
+        .expect("no errors").valid);
+    assert!(block!(client.verify_pin(pin, b"123456").expect("no client error")).expect("no errors").success);
+
+    setup!(client, StoreOtherKey, PlatformOtherKey, mem_other_key, [0u8; 32], false, Some([0x43u8; 32]));
+    assert_eq!(
+        block!(client.sign_ed255(private_key, &message).expect("no client error")).map(drop),
+        Err(Error::AeadError),
+    );
+    // PINs are hashed with a key derived from the key encryption key
+    assert!(!block!(client.verify_pin(pin, b"123456").expect("no client error")).expect("no errors").success);
+
+    setup!(client, StoreNoKey, PlatformNoKey, mem_no_key, [0u8; 32], false, None);
+    assert_eq!(
+        block!(client.sign_ed255(private_key, &message).expect("no client error")).map(drop),
+        Err(Error::AeadError),
+    );
+
+    // keys in the clear could have been planted, so they are refused once there is a key
+    // encryption key, unless they were encrypted when it was introduced
+    setup!(client, StorePlaintext, PlatformPlaintext, mem_plaintext, [0u8; 32], true, None);
+    let private_key = block!(client.generate_ed255_private_key(Location::Internal).expect("no client error"))
+        .expect("no errors").key;
+    setup!(client, StorePlanted, PlatformPlanted, mem_planted, [0u8; 32], false, Some([0x42u8; 32]));
+    assert_eq!(
+        block!(client.sign_ed255(private_key, &message).expect("no client error")).map(drop),
+        Err(Error::AeadError),
+    );
+    setup!(client, StoreMigrated, PlatformMigrated, mem_migrated, [0u8; 32], false, Some([0x42u8; 32]), false, None, true);
+    block!(client.sign_ed255(private_key, &message).expect("no client error")).expect("no errors");
+    setup!(client, StoreMigratedOtherKey, PlatformMigratedOtherKey, mem_migrated_other_key, [0u8; 32], false, Some([0x43u8; 32]));
+    assert_eq!(
+        block!(client.sign_ed255(private_key, &message).expect("no client error")).map(drop),
+        Err(Error::AeadError),
+    );
+}
+
+#[test]
+#[serial]
+fn pins() {
+    use crate::client::{PinClient as _, mechanisms::Ed255};
+    use crate::key::Policy;
+    setup!(client);
+    let pin = PinId::from_special(1);
+    let message = [1u8, 2u8, 3u8];
+
+    block!(client.set_pin(pin, b"123456", 3, Some((b"12345678", 2))).expect("no client error"))
+        .expect("no errors");
+    assert_eq!(
+        block!(client.set_pin(pin, b"654321", 3, None).expect("no client error")).map(drop),
+        Err(Error::PinAlreadySet),
+    );
+
+    let attributes = StorageAttributes::new().set_policy(Policy::new().set_pin(pin, None));
+    let private_key = block!(client.generate_key(Mechanism::Ed255, attributes).expect("no client error"))
+        .expect("no errors").key;
+    assert_eq!(
+        block!(client.sign_ed255(private_key, &message).expect("no client error")).map(drop),
+        Err(Error::PinNotVerified),
+    );
+
+    let reply = block!(client.verify_pin(pin, b"000000").expect("no client error")).expect("no errors");
+    assert!(!reply.success);
+    assert_eq!(reply.retries_left, 2);
+    let reply = block!(client.verify_pin(pin, b"123456").expect("no client error")).expect("no errors");
+    assert!(reply.success);
+    assert_eq!(reply.retries_left, 3);
+    block!(client.sign_ed255(private_key, &message).expect("no client error")).expect("no errors");
+
+    // a failed verification revokes the previous one, and enough of them block the PIN
+    for retries_left in (0..3).rev() {
+        let reply = block!(client.verify_pin(pin, b"000000").expect("no client error")).expect("no errors");
+        assert_eq!(reply.retries_left, retries_left);
+    }
+    assert_eq!(
+        block!(client.sign_ed255(private_key, &message).expect("no client error")).map(drop),
+        Err(Error::PinNotVerified),
+    );
+    assert_eq!(
+        block!(client.verify_pin(pin, b"123456").expect("no client error")).map(drop),
+        Err(Error::PinBlocked),
+    );
+
+    let reply = block!(client.reset_pin(pin, b"00000000", b"111111").expect("no client error")).expect("no errors");
+    assert!(!reply.success);
+    assert_eq!(reply.retries_left, 1);
+    let reply = block!(client.reset_pin(pin, b"12345678", b"111111").expect("no client error")).expect("no errors");
+    assert!(reply.success);
+    let retries = block!(client.pin_retries(pin).expect("no client error")).expect("no errors");
+    assert_eq!((retries.retries_left, retries.puk_retries_left), (3, Some(2)));
+
+    let reply = block!(client.change_pin(pin, b"123456", b"222222").expect("no client error")).expect("no errors");
+    assert!(!reply.success);
+    let reply = block!(client.change_pin(pin, b"111111", b"222222").expect("no client error")).expect("no errors");
+    assert!(reply.success);
+    assert!(block!(client.verify_pin(pin, b"222222").expect("no client error")).expect("no errors").success);
+    block!(client.sign_ed255(private_key, &message).expect("no client error")).expect("no errors");
+
+    // deleting the PIN takes the PIN, or a new PIN could be set for the keys bound to it
+    let reply = block!(client.delete_pin(pin, b"111111").expect("no client error")).expect("no errors");
+    assert!(!reply.success);
+    assert_eq!(reply.retries_left, 2);
+    assert!(block!(client.delete_pin(pin, b"222222").expect("no client error")).expect("no errors").success);
+    assert_eq!(
+        block!(client.verify_pin(pin, b"222222").expect("no client error")).map(drop),
+        Err(Error::NoSuchPin),
+    );
+    assert_eq!(
+        block!(client.sign_ed255(private_key, &message).expect("no client error")).map(drop),
+        Err(Error::PinNotVerified),
+    );
+}
+
+#[test]
+#[serial]
+fn pins_blocked_for_good() {
+    use crate::client::{PinClient as _, mechanisms::Ed255};
+    use crate::key::Policy;
+    setup!(client);
+    let (pin, other_pin) = (PinId::from_special(1), PinId::from_special(2));
+    let message = [1u8, 2u8, 3u8];
+
+    block!(client.set_pin(pin, b"123456", 1, None).expect("no client error")).expect("no errors");
+    block!(client.set_pin(other_pin, b"123456", 1, Some((b"12345678", 1))).expect("no client error"))
+        .expect("no errors");
+    let attributes = StorageAttributes::new().set_policy(Policy::new().set_pin(pin, None));
+    let bound_key = block!(client.generate_key(Mechanism::Ed255, attributes).expect("no client error"))
+        .expect("no errors").key;
+    let other_key = block!(client.generate_ed255_private_key(Location::Internal).expect("no client error"))
+        .expect("no errors").key;
+
+    // a blocked PIN with a PUK left has to be reset
+    block!(client.verify_pin(other_pin, b"000000").expect("no client error")).expect("no errors");
+    assert_eq!(
+        block!(client.delete_pin(other_pin, b"000000").expect("no client error")).map(drop),
+        Err(Error::PinBlocked),
+    );
+    // once the PUK is blocked too, it can be deleted
+    block!(client.reset_pin(other_pin, b"00000000", b"654321").expect("no client error")).expect("no errors");
+    assert!(block!(client.delete_pin(other_pin, b"000000").expect("no client error")).expect("no errors").success);
+
+    // without a PUK, it can be deleted once blocked, along with the keys bound to it
+    block!(client.verify_pin(pin, b"000000").expect("no client error")).expect("no errors");
+    assert!(block!(client.delete_pin(pin, b"000000").expect("no client error")).expect("no errors").success);
+    block!(client.set_pin(pin, b"654321", 3, None).expect("no client error")).expect("no errors");
+    assert!(block!(client.verify_pin(pin, b"654321").expect("no client error")).expect("no errors").success);
+    assert_eq!(
+        block!(client.sign_ed255(bound_key, &message).expect("no client error")).map(drop),
+        Err(Error::NoSuchKey),
+    );
+    block!(client.sign_ed255(other_key, &message).expect("no client error")).expect("no errors");
+}
+
+#[test]
+#[serial]
+fn backup_and_restore() {
+    use crate::client::{CertificateClient as _, CounterClient as _, FilesystemClient as _, ManagementClient as _, mechanisms::Ed255};
+    let message = [1u8, 2u8, 3u8];
+
+    let memory = create_memory!();
+    setup!(client, Store, Platform, memory, [0u8; 32], true, Some([0x42u8; 32]));
+
+    let wrapping_key = block!(client.generate_key(Mechanism::Chacha8Poly1305, StorageAttributes::new()).expect("no client error"))
+        .expect("no errors").key;
+    let other_wrapping_key = block!(client.generate_key(Mechanism::Chacha8Poly1305, StorageAttributes::new()).expect("no client error"))
+        .expect("no errors").key;
+    let attributes = StorageAttributes::new().set_extractable(true);
+    let private_key = block!(client.generate_key(Mechanism::Ed255, attributes).expect("no client error"))
+        .expect("no errors").key;
+    let unextractable_key = block!(client.generate_key(Mechanism::P256, StorageAttributes::new()).expect("no client error"))
+        .expect("no errors").key;
+    let signature = block!(client.sign_ed255(private_key, &message).expect("no client error"))
+        .expect("no errors").signature;
+
+    // too large for one chunk
+    let path = PathBuf::from("nested/large");
+    let data = Message::from_slice(&[0x5a; 1000]).unwrap();
+    block!(client.write_file(Location::Internal, path.clone(), data.clone(), None).expect("no client error"))
+        .expect("no errors");
+    let counter = block!(client.create_counter(Location::Internal).expect("no client error"))
+        .expect("no errors").id;
+    block!(client.increment_counter(counter).expect("no client error")).expect("no errors");
+    let certificate = block!(client.write_certificate(Location::Internal, &[0x30, 0x00]).expect("no client error"))
+        .expect("no errors").id;
+    let certificate_attributes = CertificateAttributes::default().set_label(b"backed up").set_key(private_key);
+    block!(client.set_certificate_attributes(certificate, certificate_attributes.clone()).expect("no client error"))
+        .expect("no errors");
+
+    let mut chunks = heapless::Vec::<Message, 8>::new();
+    let mut cursor = None;
+    loop {
+        let reply = block!(client.backup(wrapping_key, cursor).expect("no client error")).expect("no errors");
+        chunks.push(reply.chunk).unwrap();
+        cursor = reply.next;
+        if cursor.is_none() {
+            break;
+        }
+    }
+    assert!(chunks.len() > 1);
+    let cursor = block!(client.backup(wrapping_key, None).expect("no client error")).expect("no errors").next;
+    let other_chunk = block!(client.backup(wrapping_key, cursor).expect("no client error")).expect("no errors").chunk;
+
+    block!(client.delete(private_key).expect("no client error")).expect("no errors");
+    block!(client.delete(unextractable_key).expect("no client error")).expect("no errors");
+    block!(client.remove_file(Location::Internal, path.clone()).expect("no client error")).expect("no errors");
+    block!(client.delete_certificate(certificate).expect("no client error")).expect("no errors");
+    block!(client.increment_counter(counter).expect("no client error")).expect("no errors");
+
+    assert_eq!(
+        block!(client.restore(other_wrapping_key, &chunks[0]).expect("no client error")).map(drop),
+        Err(Error::AeadError),
+    );
+
+    // the header is authenticated
+    let mut tampered = chunks[0].clone();
+    // the version, the backup ID and the index come before the last chunk marker
+    tampered[1 + 16 + 4] ^= 1;
+    assert_eq!(
+        block!(client.restore(wrapping_key, &tampered).expect("no client error")).map(drop),
+        Err(Error::AeadError),
+    );
+
+    // chunks of another backup do not fit in
+    block!(client.restore(wrapping_key, &chunks[0]).expect("no client error")).expect("no errors");
+    assert_eq!(
+        block!(client.restore(wrapping_key, &other_chunk).expect("no client error")).map(drop),
+        Err(Error::BackupChunkOutOfSequence),
+    );
+    // nothing is restored without the last chunk
+    assert!(!block!(client.exists(Mechanism::Ed255, private_key).expect("no client error"))
+        .expect("no errors").exists);
+
+    for (index, chunk) in chunks.iter().enumerate() {
+        let reply = block!(client.restore(wrapping_key, chunk).expect("no client error")).expect("no errors");
+        assert_eq!(reply.index, index as u32);
+        assert_eq!(reply.last, index == chunks.len() - 1);
+    }
+    // a replayed chunk does not follow
+    assert_eq!(
+        block!(client.restore(wrapping_key, chunks.last().unwrap()).expect("no client error")).map(drop),
+        Err(Error::BackupChunkOutOfSequence),
+    );
+
+    assert!(block!(client.verify_ed255(private_key, &message, &signature).expect("no client error"))
+        .expect("no errors").valid);
+    block!(client.sign_ed255(private_key, &message).expect("no client error")).expect("no errors");
+    // the restored key was imported, not generated on this device
+    let flags = block!(client.get_key_info(private_key).expect("no client error")).expect("no errors").flags;
+    assert!(!flags.contains(key::Flags::LOCAL));
+    assert!(flags.contains(key::Flags::EXTRACTABLE | key::Flags::SENSITIVE));
+    assert!(!block!(client.exists(Mechanism::P256, unextractable_key).expect("no client error"))
+        .expect("no errors").exists);
+    assert_eq!(
+        block!(client.read_file(Location::Internal, path).expect("no client error")).expect("no errors").data,
+        data,
+    );
+    let restored_certificate = block!(client.read_certificate(certificate).expect("no client error")).expect("no errors");
+    assert_eq!(restored_certificate.der.as_slice(), &[0x30, 0x00]);
+    assert_eq!(restored_certificate.attributes, certificate_attributes);
+    // the counter is back to its value at the time of the backup
+    assert_eq!(
+        block!(client.increment_counter(counter).expect("no client error")).expect("no errors").counter,
+        1,
+    );
+}
+
+#[test]
+#[serial]
+fn split_and_combine_keys() {
+    use crate::client::mechanisms::Ed255;
+    setup!(client);
+    let message = [1u8, 2u8, 3u8];
+
+    let attributes = StorageAttributes::new().set_extractable(true);
+    let private_key = block!(client.generate_key(Mechanism::Ed255, attributes.clone()).expect("no client error"))
+        .expect("no errors").key;
+    let signature = block!(client.sign_ed255(private_key, &message).expect("no client error"))
+        .expect("no errors").signature;
+
+    for format in [ShareFormat::Raw, ShareFormat::Slip39] {
+        let shares = block!(client.split_key(private_key, 3, 5, format).expect("no client error"))
+            .expect("no errors").shares;
+        assert_eq!(shares.len(), 5);
+        if format == ShareFormat::Slip39 {
+            // 32 byte secrets take 33 words
+            assert!(shares.iter().all(|share| share.len() == 2 * 33));
+        }
+
+        // any three shares recombine to the key
+        let some_shares: [&[u8]; 3] = [&shares[4], &shares[1], &shares[2]];
+        let key = block!(client.combine_key(Mechanism::Ed255, &some_shares, format, StorageAttributes::new()).expect("no client error"))
+            .expect("no errors").key;
+        assert!(block!(client.verify_ed255(key, &message, &signature).expect("no client error"))
+            .expect("no errors").valid);
+
+        // too few shares, or a tampered share, are rejected
+        assert_eq!(
+            block!(client.combine_key(Mechanism::Ed255, &some_shares[..2], format, StorageAttributes::new()).expect("no client error")).map(drop),
+            Err(Error::InvalidShares),
+        );
+        let mut tampered = shares[0].clone();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        let tampered_shares: [&[u8]; 3] = [&tampered, &shares[1], &shares[2]];
+        assert_eq!(
+            block!(client.combine_key(Mechanism::Ed255, &tampered_shares, format, StorageAttributes::new()).expect("no client error")).map(drop),
+            Err(Error::InvalidShares),
+        );
+    }
+
+    assert_eq!(
+        block!(client.split_key(private_key, 4, 3, ShareFormat::Raw).expect("no client error")).map(drop),
+        Err(Error::InvalidShareThreshold),
+    );
+    let unextractable_key = block!(client.generate_key(Mechanism::Ed255, StorageAttributes::new()).expect("no client error"))
+        .expect("no errors").key;
+    assert_eq!(
+        block!(client.split_key(unextractable_key, 2, 3, ShareFormat::Raw).expect("no client error")).map(drop),
+        Err(Error::KeyUnextractable),
+    );
+}
+
+#[test]
+#[serial]
+fn key_format_upgrade() {
+    use crate::client::mechanisms::HmacSha256;
+    use crate::key::{SERIALIZATION_VERSION, serialized_version};
+    let message = [1u8, 2u8, 3u8];
+    let legacy_key = KeyId(Id(0x1111_1111));
+    let future_key = KeyId(Id(0x2222_2222));
+    let key_path = |id: KeyId| {
+        let mut path = PathBuf::new();
+        path.push(&PathBuf::from("TEST"));
+        path.push(&PathBuf::from("sec"));
+        path.push(&PathBuf::from(id.hex().as_slice()));
+        path
+    };
+
+    let mem = create_memory!();
+    let mem_service = create_memory!(mem);
+    let mem_check = create_memory!(mem);
+
+    // a key in the original format, without version, and one in a format from the future
+    store!(OldStore, Internal: InternalStorage, External: ExternalStorage, Volatile: VolatileStorage);
+    let store = OldStore::claim().unwrap();
+    store.mount(mem.0, mem.1, mem.2, mem.3, mem.4, mem.5, true).unwrap();
+    let mut legacy = Bytes::<64>::from_slice(&[0, 3, 0, 2]).unwrap();
+    legacy.extend_from_slice(&[0x42; 32]).unwrap();
+    store::store(store, Location::Internal, &key_path(legacy_key), &legacy).unwrap();
+    let mut future = Bytes::<64>::from_slice(&[0x80, 3, SERIALIZATION_VERSION + 1, 0, 2, 0, 0]).unwrap();
+    future.extend_from_slice(&[0x42; 32]).unwrap();
+    store::store(store, Location::Internal, &key_path(future_key), &future).unwrap();
+
+    setup!(client, Store, Platform, mem_service, [0u8; 32], false);
+    let signature = block!(client.sign_hmacsha256(legacy_key, &message).expect("no client error"))
+        .expect("no errors").signature;
+    let injected_key = block!(client.unsafe_inject_key(Mechanism::HmacSha256, &[0x42; 32], Location::Volatile).expect("no client error"))
+        .expect("no errors").key;
+    assert_eq!(
+        block!(client.sign_hmacsha256(injected_key, &message).expect("no client error")).expect("no errors").signature,
+        signature,
+    );
+    assert_eq!(
+        block!(client.sign_hmacsha256(future_key, &message).expect("no client error")).map(drop),
+        Err(Error::UnsupportedKeyVersion),
+    );
+
+    // using the legacy key upgraded its file
+    store!(CheckStore, Internal: InternalStorage, External: ExternalStorage, Volatile: VolatileStorage);
+    let store = CheckStore::claim().unwrap();
+    store.mount(mem_check.0, mem_check.1, mem_check.2, mem_check.3, mem_check.4, mem_check.5, false).unwrap();
+    let file: Bytes<64> = store::read(store, Location::Internal, &key_path(legacy_key)).unwrap();
+    assert_eq!(serialized_version(&file), Ok(SERIALIZATION_VERSION));
+    assert_eq!(crate::key::Key::try_deserialize(&file).unwrap().material.as_slice(), &[0x42; 32]);
+}
+
+#[test]
+#[serial]
+fn attest_with_template() {
+    use crate::client::{CertificateClient as _, mechanisms::{Ed255, P256}};
+    use crate::key::{Flags, Key, Kind, Limits, Material, Policy};
+    use core::convert::TryInto;
+    use crate::service::attest::{ED255_ATTN_KEY, P256_ATTN_KEY};
+    use hex_literal::hex;
+    let attn_seed = [0x42u8; 32];
+    let aaguid = hex!("04 10 00112233445566778899AABBCCDDEEFF");
+    let contains = |der: &[u8], part: &[u8]| der.windows(part.len()).any(|window| window == part);
+
+    let mem = create_memory!();
+    let mem_service = create_memory!(mem);
+
+    // the attestation keys, as provisioned during manufacturing
+    store!(AttnStore, Internal: InternalStorage, External: ExternalStorage, Volatile: VolatileStorage);
+    let store = AttnStore::claim().unwrap();
+    store.mount(mem.0, mem.1, mem.2, mem.3, mem.4, mem.5, true).unwrap();
+    for (id, kind) in [(ED255_ATTN_KEY, Kind::Ed255), (P256_ATTN_KEY, Kind::P256)] {
+        let key = Key {
+            flags: Flags::LOCAL,
+            kind,
+            policy: Policy::new(),
+            limits: Limits::new(),
+            metadata: Default::default(),
+            material: Material::from_slice(&attn_seed).unwrap(),
+        };
+        let mut path = PathBuf::from("attn");
+        path.push(&PathBuf::from("sec"));
+        path.push(&PathBuf::from(id.hex().as_slice()));
+        store::store(store, Location::Internal, &path, &key.serialize()).unwrap();
+    }
+
+    setup!(client, Store, Platform, mem_service, [0u8; 32], false);
+    let key = block!(client.generate_ed255_private_key(Location::Internal).expect("no client error"))
+        .expect("no errors").key;
+
+    let template = CertificateTemplate::default()
+        .set_subject(DistinguishedName::default()
+            .set_country(b"CH")
+            .set_organization("Trussed")
+            .set_organizational_unit("Authenticator Attestation")
+            .set_common_name("Trussed Test"))
+        .set_validity(DateTime::new(2022, 1, 1, 0, 0, 0), Some(DateTime::new(2052, 1, 1, 0, 0, 0)))
+        .set_key_usage(KeyUsage::DIGITAL_SIGNATURE)
+        .set_basic_constraints(BasicConstraints::default())
+        // FIDO AAGUID
+        .add_extension(&hex!("2B 06 01 04 01 82 E5 1C 01 01 04"), false, &aaguid);
+    let id = block!(client.attest_with_template(Mechanism::Ed255, key, template.clone()).expect("no client error"))
+        .expect("no errors").certificate;
+    let der = block!(client.read_certificate(id).expect("no client error")).expect("no errors").der;
+
+    assert!(contains(&der, b"\x0c\x19Authenticator Attestation"));
+    assert!(contains(&der, b"\x17\x0d220101000000Z\x18\x0f20520101000000Z"));
+    // critical key usage with only digitalSignature, critical basic constraints of an end entity
+    assert!(contains(&der, &hex!("06 03 55 1D 0F 01 01 FF 04 04 03 02 07 80")));
+    assert!(contains(&der, &hex!("06 03 55 1D 13 01 01 FF 04 02 30 00")));
+    assert!(contains(&der, &hex!("06 0B 2B 06 01 04 01 82 E5 1C 01 01 04 04 12")));
+    assert!(contains(&der, &aaguid));
+
+    // the signature over the TBS certificate is by the attestation key
+    assert_eq!(&der[..2], &hex!("30 82"));
+    let tbs_length = u16::from_be_bytes([der[6], der[7]]) as usize;
+    let tbs = &der[4..8 + tbs_length];
+    let signature: [u8; 64] = der[der.len() - 64..].try_into().unwrap();
+    salty::Keypair::from(&attn_seed).public
+        .verify(tbs, &salty::Signature::from(&signature))
+        .expect("valid signature");
+
+    // P256 keys are certified with their uncompressed point, and an empty subject is never left empty
+    let key = block!(client.generate_p256_private_key(Location::Internal).expect("no client error"))
+        .expect("no errors").key;
+    let id = block!(client.attest(Mechanism::P256, key).expect("no client error"))
+        .expect("no errors").certificate;
+    let der = block!(client.read_certificate(id).expect("no client error")).expect("no errors").der;
+    assert!(contains(&der, &hex!("03 42 00 04")));
+    let mut common_name = Bytes::<40>::from_slice(&hex!("06 03 55 04 03 0C")).unwrap();
+    common_name.push(key.hex().len() as u8).unwrap();
+    common_name.extend_from_slice(&key.hex()).unwrap();
+    assert!(contains(&der, &common_name));
+
+    let invalid_templates = [
+        // only CAs sign certificates
+        template.clone().set_key_usage(KeyUsage::KEY_CERT_SIGN),
+        template.clone().set_validity(DateTime::new(2022, 1, 1, 0, 0, 0), Some(DateTime::new(2021, 1, 1, 0, 0, 0))),
+        template.clone().set_validity(DateTime::new(2023, 2, 29, 0, 0, 0), None),
+        template.clone().set_subject(DistinguishedName::default().set_country(b"ch")),
+        // key usage is set through the template, not as a custom extension
+        template.clone().add_extension(&hex!("55 1D 0F"), true, &hex!("03 02 07 80")),
+        template.clone().add_extension(&hex!("2B 06 01 04 01 82 E5 1C 01 01 04"), false, &aaguid),
+    ];
+    for template in invalid_templates {
+        assert_eq!(
+            block!(client.attest_with_template(Mechanism::Ed255, key, template).expect("no client error")).map(drop),
+            Err(Error::InvalidCertificateTemplate),
+        );
+    }
+}
+
+#[test]
+#[serial]
+fn generate_csr() {
+    use core::convert::{TryFrom, TryInto};
+    use crate::client::{CertificateClient as _, mechanisms::{Ed255, P256}};
+    use hex_literal::hex;
+    let contains = |der: &[u8], part: &[u8]| der.windows(part.len()).any(|window| window == part);
+    let header_length = |der: &[u8]| match der[1] {
+        length if length < 0x80 => 2,
+        length => 2 + (length & 0x7F) as usize,
+    };
+
+    setup!(client);
+    let key = block!(client.generate_ed255_private_key(Location::Internal).expect("no client error"))
+        .expect("no errors").key;
+    let template = CertificateTemplate::default()
+        .set_subject(DistinguishedName::default().set_organization("Trussed").set_common_name("Device"))
+        .add_alternative_name(GeneralName::Dns("example.com".into()))
+        .set_key_usage(KeyUsage::DIGITAL_SIGNATURE);
+    let csr = block!(client.generate_csr(Mechanism::Ed255, key, template).expect("no client error"))
+        .expect("no errors").csr;
+
+    // version 0, the subject, and the extensions in an extension request
+    assert!(contains(&csr, &hex!("02 01 00 30 23 31 10 30 0E 06 03 55 04 0A 0C 07")));
+    assert!(contains(&csr, b"\x0c\x06Device"));
+    assert!(contains(&csr, &hex!("06 09 2A 86 48 86 F7 0D 01 09 0E")));
+    assert!(contains(&csr, &hex!("06 03 55 1D 0F 01 01 FF 04 04 03 02 07 80")));
+    assert!(contains(&csr, b"\x06\x03\x55\x1d\x11\x04\x0f\x30\x0d\x82\x0bexample.com"));
+
+    // signed by the key itself
+    let public_key = block!(client.derive_ed255_public_key(key, Location::Volatile).expect("no client error"))
+        .expect("no errors").key;
+    let public_key = block!(client.serialize_ed255_key(public_key, KeySerialization::Raw).expect("no client error"))
+        .expect("no errors").serialized_key;
+    assert!(contains(&csr, &public_key));
+    let info = &csr[header_length(&csr)..];
+    let info = &info[..header_length(info) + info[header_length(info) - 1] as usize];
+    let signature: [u8; 64] = csr[csr.len() - 64..].try_into().unwrap();
+    let public_key: [u8; 32] = public_key.as_slice().try_into().unwrap();
+    salty::PublicKey::try_from(&public_key).unwrap()
+        .verify(info, &salty::Signature::from(&signature))
+        .expect("valid signature");
+
+    // the subject may be left empty, then the alternative names are critical
+    let key = block!(client.generate_p256_private_key(Location::Internal).expect("no client error"))
+        .expect("no errors").key;
+    let template = CertificateTemplate::default()
+        .add_alternative_name(GeneralName::IpAddress(Bytes::from_slice(&[192, 0, 2, 1]).unwrap()));
+    let csr = block!(client.generate_csr(Mechanism::P256, key, template).expect("no client error"))
+        .expect("no errors").csr;
+    assert!(contains(&csr, &hex!("02 01 00 30 00 30 59")));
+    assert!(contains(&csr, &hex!("06 03 55 1D 11 01 01 FF 04 08 30 06 87 04 C0 00 02 01")));
+
+    // no extensions, no extension request
+    let template = CertificateTemplate::default().set_subject(DistinguishedName::default().set_common_name("Leaf"));
+    let csr = block!(client.generate_csr(Mechanism::P256, key, template).expect("no client error"))
+        .expect("no errors").csr;
+    assert!(contains(&csr, &hex!("A0 00 30 0A 06 08 2A 86 48 CE 3D 04 03 02")));
+
+    // the subject must be named somehow
+    assert_eq!(
+        block!(client.generate_csr(Mechanism::P256, key, CertificateTemplate::default()).expect("no client error")).map(drop),
+        Err(Error::InvalidCertificateTemplate),
+    );
+
+    let template = CertificateTemplate::default().add_alternative_name(GeneralName::Email("".into()));
+    assert_eq!(
+        block!(client.generate_csr(Mechanism::P256, key, template).expect("no client error")).map(drop),
+        Err(Error::InvalidCertificateTemplate),
+    );
+}
+
+#[test]
+#[serial]
+fn issue_certificate() {
+    use core::convert::{TryFrom, TryInto};
+    use crate::client::{CertificateClient as _, mechanisms::{Ed255, P256}};
+    use hex_literal::hex;
+    let find = |der: &[u8], part: &[u8]| der.windows(part.len()).position(|window| window == part);
+    let key_identifier = |der: &[u8], prefix: &[u8]| {
+        let start = find(der, prefix).expect("key identifier") + prefix.len();
+        der[start..start + 20].to_vec()
+    };
+    let subject_key_identifier = hex!("06 03 55 1D 0E 04 16 04 14");
+    let authority_key_identifier = hex!("06 03 55 1D 23 04 18 30 16 80 14");
+
+    setup!(client);
+    let root_key = block!(client.generate_ed255_private_key(Location::Internal).expect("no client error"))
+        .expect("no errors").key;
+    let public_key = block!(client.derive_ed255_public_key(root_key, Location::Volatile).expect("no client error"))
+        .expect("no errors").key;
+    let root_public_key: [u8; 32] = block!(client.serialize_ed255_key(public_key, KeySerialization::Raw).expect("no client error"))
+        .expect("no errors").serialized_key.as_slice().try_into().unwrap();
+    let root_public_key = salty::PublicKey::try_from(&root_public_key).unwrap();
+    let verify = |der: &[u8]| {
+        let tbs_length = u16::from_be_bytes([der[6], der[7]]) as usize;
+        let signature: [u8; 64] = der[der.len() - 64..].try_into().unwrap();
+        root_public_key.verify(&der[4..8 + tbs_length], &salty::Signature::from(&signature)).is_ok()
+    };
+
+    // a self-signed root
+    let root_name = DistinguishedName::default().set_organization("Trussed").set_common_name("Root");
+    let template = CertificateTemplate::default()
+        .set_subject(root_name.clone())
+        .set_key_usage(KeyUsage::KEY_CERT_SIGN | KeyUsage::CRL_SIGN)
+        .set_basic_constraints(BasicConstraints { ca: true, path_length: Some(0) });
+    let id = block!(client.issue_certificate(Mechanism::Ed255, root_key, root_name.clone(), root_key, template, Location::Internal)
+        .expect("no client error")).expect("no errors").certificate;
+    let root = block!(client.read_certificate(id).expect("no client error")).expect("no errors").der;
+    assert!(verify(&root));
+    // the same name as issuer and subject
+    let name = b"\x0c\x07Trussed1\r0\x0b\x06\x03U\x04\x03\x0c\x04Root";
+    let issuer = find(&root, name).unwrap();
+    assert!(find(&root[issuer + 1..], name).is_some());
+    assert!(find(&root, &hex!("06 03 55 1D 13 01 01 FF 04 08 30 06 01 01 FF 02 01 00")).is_some());
+    let root_identifier = key_identifier(&root, &subject_key_identifier);
+    assert_eq!(key_identifier(&root, &authority_key_identifier), root_identifier);
+
+    // a leaf, signed by the root
+    let leaf_key = block!(client.generate_p256_private_key(Location::Internal).expect("no client error"))
+        .expect("no errors").key;
+    let template = CertificateTemplate::default()
+        .set_subject(DistinguishedName::default().set_common_name("Leaf"))
+        .set_validity(DateTime::new(2022, 6, 1, 0, 0, 0), Some(DateTime::new(2032, 6, 1, 0, 0, 0)));
+    let id = block!(client.issue_certificate(Mechanism::Ed255, root_key, root_name.clone(), leaf_key, template.clone(), Location::Volatile)
+        .expect("no client error")).expect("no errors").certificate;
+    let leaf = block!(client.read_certificate(id).expect("no client error")).expect("no errors").der;
+    assert!(verify(&leaf));
+    assert_eq!(key_identifier(&leaf, &authority_key_identifier), root_identifier);
+    assert_ne!(key_identifier(&leaf, &subject_key_identifier), root_identifier);
+
+    // a public key on its own
+    let public_key = block!(client.derive_p256_public_key(leaf_key, Location::Volatile).expect("no client error"))
+        .expect("no errors").key;
+    let serialized_key = block!(client.serialize_p256_key(public_key, KeySerialization::Raw).expect("no client error"))
+        .expect("no errors").serialized_key;
+    let public_key = block!(client.deserialize_p256_key(&serialized_key, KeySerialization::Raw, StorageAttributes::new())
+        .expect("no client error")).expect("no errors").key;
+    let id = block!(client.issue_certificate(Mechanism::Ed255, root_key, root_name.clone(), public_key, template.clone(), Location::Volatile)
+        .expect("no client error")).expect("no errors").certificate;
+    let certificate = block!(client.read_certificate(id).expect("no client error")).expect("no errors").der;
+    assert!(verify(&certificate));
+    assert_eq!(key_identifier(&certificate, &subject_key_identifier), key_identifier(&leaf, &subject_key_identifier));
+
+    // the issuer must have a name, and its key must fit the mechanism
+    assert_eq!(
+        block!(client.issue_certificate(Mechanism::Ed255, root_key, DistinguishedName::default(), leaf_key, template.clone(), Location::Volatile)
+            .expect("no client error")).map(drop),
+        Err(Error::InvalidCertificateTemplate),
+    );
+    assert!(block!(client.issue_certificate(Mechanism::P256, root_key, root_name, leaf_key, template, Location::Volatile)
+        .expect("no client error")).is_err());
+}
+
+#[test]
+#[serial]
+fn verify_certificate_chain() {
+    use crate::client::{CertificateClient, mechanisms::{Ed255, P256}};
+    use crate::x509::{self, Certificate};
+    assert_eq!(DateTime::from_unix_time(1_600_000_000), DateTime::new(2020, 9, 13, 12, 26, 40));
+    assert_eq!(DateTime::from_unix_time(951_782_400), DateTime::new(2000, 2, 29, 0, 0, 0));
+
+    setup!(client);
+    let ca = |path_length| CertificateTemplate::default()
+        .set_key_usage(KeyUsage::KEY_CERT_SIGN)
+        .set_basic_constraints(BasicConstraints { ca: true, path_length });
+    let issue = |client: &mut _, mechanism, issuer_key, issuer: &DistinguishedName, subject_key, template| {
+        let id = block!(CertificateClient::issue_certificate(client, mechanism, issuer_key, issuer.clone(), subject_key, template, Location::Volatile)
+            .expect("no client error")).expect("no errors").certificate;
+        let der = block!(CertificateClient::read_certificate(client, id).expect("no client error")).expect("no errors").der;
+        (id, der)
+    };
+
+    // two roots with the same name and key, only one allowing intermediates
+    let root_key = block!(client.generate_ed255_private_key(Location::Internal).expect("no client error"))
+        .expect("no errors").key;
+    let root_name = DistinguishedName::default().set_common_name("Root");
+    let (root, root_der) = issue(&mut client, Mechanism::Ed255, root_key, &root_name, root_key, ca(None).set_subject(root_name.clone()));
+    let (strict_root, _) = issue(&mut client, Mechanism::Ed255, root_key, &root_name, root_key, ca(Some(0)).set_subject(root_name.clone()));
+
+    let intermediate_key = block!(client.generate_p256_private_key(Location::Internal).expect("no client error"))
+        .expect("no errors").key;
+    let intermediate_name = DistinguishedName::default().set_common_name("Intermediate");
+    let (intermediate_id, intermediate) = issue(&mut client, Mechanism::Ed255, root_key, &root_name, intermediate_key, ca(Some(0)).set_subject(intermediate_name.clone()));
+
+    let leaf_key = block!(client.generate_ed255_private_key(Location::Internal).expect("no client error"))
+        .expect("no errors").key;
+    let leaf_name = DistinguishedName::default().set_common_name("Leaf");
+    let template = CertificateTemplate::default()
+        .set_subject(leaf_name.clone())
+        .set_key_usage(KeyUsage::DIGITAL_SIGNATURE)
+        .set_validity(DateTime::new(2022, 6, 1, 0, 0, 0), Some(DateTime::new(2032, 6, 1, 0, 0, 0)));
+    let (leaf_id, leaf) = issue(&mut client, Mechanism::P256, intermediate_key, &intermediate_name, leaf_key, template);
+
+    let certificate = Certificate::parse(&leaf).unwrap();
+    assert_eq!(certificate.version, 3);
+    assert!(matches!(certificate.signature_algorithm, x509::SignatureAlgorithm::P256));
+    assert!(matches!(certificate.public_key, x509::PublicKey::Ed255(_)));
+    assert_eq!(certificate.not_after, DateTime::new(2032, 6, 1, 0, 0, 0));
+    assert_eq!(certificate.key_usage, Some(KeyUsage::DIGITAL_SIGNATURE));
+    assert_eq!(certificate.basic_constraints, None);
+    assert_eq!(certificate.subject_key_identifier.map(<[u8]>::len), Some(20));
+    assert!(!certificate.unhandled_critical_extension);
+    let certificate = Certificate::parse(&intermediate).unwrap();
+    assert!(matches!(certificate.public_key, x509::PublicKey::P256(key) if key.len() == 65));
+    assert_eq!(certificate.basic_constraints, Some(BasicConstraints { ca: true, path_length: Some(0) }));
+    assert!(Certificate::parse(&leaf[..leaf.len() - 1]).is_err());
+
+    let mut chain = leaf.clone();
+    chain.extend_from_slice(&intermediate).unwrap();
+    let verify = |client: &mut _, chain: &[u8], anchors: &[CertId], time| {
+        block!(CertificateClient::verify_certificate_chain(client, chain, anchors, time).expect("no client error"))
+            .map(|reply| reply.anchor)
+    };
+    let now = Some(DateTime::new(2025, 1, 1, 0, 0, 0));
+    assert_eq!(verify(&mut client, &chain, &[leaf_id, strict_root, root], now), Ok(root));
+    assert_eq!(verify(&mut client, &chain, &[strict_root], now), Err(Error::UntrustedCertificate));
+    // the chain may end with the anchor
+    let mut full_chain = intermediate.clone();
+    full_chain.extend_from_slice(&root_der).unwrap();
+    assert_eq!(verify(&mut client, &full_chain, &[root], now), Ok(root));
+    // missing the intermediate
+    assert_eq!(verify(&mut client, &leaf, &[root], now), Err(Error::UntrustedCertificate));
+    // before and after the leaf's validity, and before the roots' (platform clock)
+    assert_eq!(verify(&mut client, &chain, &[root], Some(DateTime::new(2022, 5, 31, 23, 59, 59))), Err(Error::CertificateExpired));
+    assert_eq!(verify(&mut client, &chain, &[root], Some(DateTime::new(2032, 6, 1, 0, 0, 1))), Err(Error::CertificateExpired));
+    assert_eq!(verify(&mut client, &chain, &[root], None), Err(Error::CertificateExpired));
+
+    // a tampered signature
+    let mut tampered = chain.clone();
+    let last = leaf.len() - 1;
+    tampered[last] ^= 1;
+    assert_eq!(verify(&mut client, &tampered, &[root], now), Err(Error::UntrustedCertificate));
+
+    // the leaf is no CA, and the intermediate may not have further intermediates below
+    let (_, below_leaf) = issue(&mut client, Mechanism::Ed255, leaf_key, &leaf_name, leaf_key, CertificateTemplate::default());
+    let mut long_chain = below_leaf.clone();
+    long_chain.extend_from_slice(&leaf).unwrap();
+    assert_eq!(verify(&mut client, &long_chain, &[intermediate_id], now), Err(Error::UntrustedCertificate));
+    let (second_intermediate_id, second_intermediate) = issue(&mut client, Mechanism::P256, intermediate_key, &intermediate_name, leaf_key, ca(None).set_subject(leaf_name));
+    let mut long_chain = below_leaf;
+    long_chain.extend_from_slice(&second_intermediate).unwrap();
+    assert_eq!(verify(&mut client, &long_chain, &[intermediate_id], now), Err(Error::UntrustedCertificate));
+    assert_eq!(verify(&mut client, &long_chain, &[second_intermediate_id], now), Ok(second_intermediate_id));
+
+    assert_eq!(verify(&mut client, &[], &[root], now), Err(Error::InvalidCertificate));
+}
+
+#[test]
+#[serial]
+fn list_certificates() {
+    use crate::client::{CertificateClient, mechanisms::{Ed255, P256}};
+    use sha2::digest::Digest as _;
+
+    setup!(client);
+    let root_key = block!(client.generate_ed255_private_key(Location::Internal).expect("no client error"))
+        .expect("no errors").key;
+    let leaf_key = block!(client.generate_p256_private_key(Location::Internal).expect("no client error"))
+        .expect("no errors").key;
+    let root_name = DistinguishedName::default().set_common_name("Root");
+    let template = CertificateTemplate::default().set_subject(root_name.clone());
+    let root = block!(client.issue_certificate(Mechanism::Ed255, root_key, root_name.clone(), root_key, template, Location::Internal)
+        .expect("no client error")).expect("no errors").certificate;
+    let template = CertificateTemplate::default().set_subject(DistinguishedName::default().set_common_name("Leaf"));
+    let leaf = block!(client.issue_certificate(Mechanism::Ed255, root_key, root_name, leaf_key, template, Location::Volatile)
+        .expect("no client error")).expect("no errors").certificate;
+    let unparsed = block!(client.write_certificate(Location::External, b"not DER").expect("no client error"))
+        .expect("no errors").id;
+    // numbered by counter zero
+    assert_eq!((root, leaf, unparsed), (CertId::from_special(1), CertId::from_special(2), CertId::from_special(3)));
+
+    let list = |client: &mut _, filter: CertificateFilter, after| {
+        let reply = block!(CertificateClient::list_certificates(client, filter, after).expect("no client error")).expect("no errors");
+        let ids: std::vec::Vec<CertId> = reply.certificates.iter().map(|(id, _, _)| *id).collect();
+        (ids, reply.num_certificates)
+    };
+    assert_eq!(list(&mut client, CertificateFilter::default(), None), (vec![root, leaf, unparsed], 3));
+    assert_eq!(list(&mut client, CertificateFilter::default(), Some(root)), (vec![leaf, unparsed], 3));
+    assert_eq!(list(&mut client, CertificateFilter::default().set_location(Location::Volatile), None), (vec![leaf], 1));
+    assert_eq!(list(&mut client, CertificateFilter::default().set_key(leaf_key), None), (vec![leaf], 1));
+
+    let der = block!(client.read_certificate(leaf).expect("no client error")).expect("no errors").der;
+    let certificate = x509::Certificate::parse(&der).unwrap();
+    let hash = |value: &[u8]| -> [u8; 32] { sha2::Sha256::digest(value).into() };
+    assert_eq!(list(&mut client, CertificateFilter::default().set_issuer(hash(certificate.issuer)), None), (vec![root, leaf], 2));
+    assert_eq!(list(&mut client, CertificateFilter::default().set_subject(hash(certificate.issuer)), None), (vec![root], 1));
+    let public_key_hash = hash(certificate.public_key.as_bytes());
+    assert_eq!(list(&mut client, CertificateFilter::default().set_public_key(public_key_hash), None), (vec![leaf], 1));
+
+    // attributes are replaced as a whole
+    let attributes = CertificateAttributes::default().set_label(b"vendor");
+    block!(client.set_certificate_attributes(unparsed, attributes.clone()).expect("no client error")).expect("no errors");
+    assert_eq!(list(&mut client, CertificateFilter::default().set_label(b"vendor"), None), (vec![unparsed], 1));
+    let reply = block!(client.read_certificate(unparsed).expect("no client error")).expect("no errors");
+    assert_eq!((&reply.der[..], reply.attributes), (&b"not DER"[..], attributes));
+    block!(client.set_certificate_attributes(leaf, CertificateAttributes::default()).expect("no client error")).expect("no errors");
+    assert_eq!(list(&mut client, CertificateFilter::default().set_key(leaf_key), None), (vec![], 0));
+
+    block!(client.delete_certificate(unparsed).expect("no client error")).expect("no errors");
+    assert_eq!(list(&mut client, CertificateFilter::default(), None), (vec![root, leaf], 2));
+    assert_eq!(
+        block!(client.set_certificate_attributes(unparsed, CertificateAttributes::default()).expect("no client error")).map(drop),
+        Err(Error::NoSuchCertificate),
+    );
+    let id = block!(client.write_certificate(Location::Volatile, b"again").expect("no client error"))
+        .expect("no errors").id;
+    assert_eq!(id, CertId::from_special(4));
+}
+
+#[test]
+#[serial]
+fn provision_attestation() {
+    use crate::client::{CertificateClient, CryptoClient, ManagementClient, mechanisms::{Ed255, P256}};
+    use crate::x509::Certificate;
+
+    // only provisioners may provision
+    {
+        setup!(client);
+        assert_eq!(
+            block!(client.provision_attestation_key(Mechanism::Ed255, None).expect("no client error")).map(drop),
+            Err(Error::RequestNotAvailable),
+        );
+        assert_eq!(
+            block!(client.lock_provisioning().expect("no client error")).map(drop),
+            Err(Error::RequestNotAvailable),
+        );
+    }
+
+    let memory = create_memory!();
+    setup!(client, ProvisionerStore, ProvisionerPlatform, memory, [0u8; 32], true, None, true);
+
+    // an injected P256 key, and a generated Ed255 key
+    let scalar = [0x42u8; 32];
+    let public_key = block!(client.provision_attestation_key(Mechanism::P256, Some(&scalar)).expect("no client error"))
+        .expect("no errors").public_key;
+    let expected = p256_cortex_m4::SecretKey::from_bytes(scalar).unwrap().public_key().to_uncompressed_sec1_bytes();
+    assert_eq!(&public_key[..], &expected[1..]);
+    assert_eq!(
+        block!(client.provision_attestation_key(Mechanism::P256, Some(&[0u8; 32])).expect("no client error")).map(drop),
+        Err(Error::InvalidSerializedKey),
+    );
+    let attn_public_key = block!(client.provision_attestation_key(Mechanism::Ed255, None).expect("no client error"))
+        .expect("no errors").public_key;
+    assert_eq!(attn_public_key.len(), 32);
+
+    // a root certifies the attestation key, as the CA of the attestations
+    let issue = |client: &mut _, issuer_key, issuer: &DistinguishedName, subject_key, template| {
+        let id = block!(CertificateClient::issue_certificate(client, Mechanism::Ed255, issuer_key, issuer.clone(), subject_key, template, Location::Volatile)
+            .expect("no client error")).expect("no errors").certificate;
+        let der = block!(CertificateClient::read_certificate(client, id).expect("no client error")).expect("no errors").der;
+        (id, der)
+    };
+    let ca = CertificateTemplate::default()
+        .set_key_usage(KeyUsage::KEY_CERT_SIGN)
+        .set_basic_constraints(BasicConstraints { ca: true, path_length: None });
+    let root_key = block!(client.generate_ed255_private_key(Location::Internal).expect("no client error"))
+        .expect("no errors").key;
+    let root_name = DistinguishedName::default().set_common_name("Root");
+    let (root, root_der) = issue(&mut client, root_key, &root_name, root_key, ca.clone().set_subject(root_name.clone()));
+    let attn_key = block!(client.deserialize_ed255_key(&attn_public_key, KeySerialization::Raw, StorageAttributes::new().set_persistence(Location::Volatile))
+        .expect("no client error")).expect("no errors").key;
+    let attn_name = DistinguishedName::default().set_organization("Trussed").set_common_name("Attestation");
+    let (_, attn_der) = issue(&mut client, root_key, &root_name, attn_key, ca.set_subject(attn_name));
+
+    // the first certificate is for the attestation key, and each one is issued by the next
+    let install = |client: &mut _, mechanism, chain: &[u8]| {
+        block!(ManagementClient::install_attestation_certificates(client, mechanism, chain).expect("no client error")).map(drop)
+    };
+    assert_eq!(install(&mut client, Mechanism::Ed255, &root_der), Err(Error::InvalidCertificate));
+    assert_eq!(install(&mut client, Mechanism::P256, &attn_der), Err(Error::InvalidCertificate));
+    let mut chain = attn_der.clone();
+    chain.extend_from_slice(&attn_der).unwrap();
+    assert_eq!(install(&mut client, Mechanism::Ed255, &chain), Err(Error::UntrustedCertificate));
+    let mut chain = attn_der.clone();
+    chain.extend_from_slice(&root_der).unwrap();
+    assert_eq!(install(&mut client, Mechanism::Ed255, &chain), Ok(()));
+
+    // attestations are now issued by the attestation certificate
+    let key = block!(client.generate_ed255_private_key(Location::Internal).expect("no client error"))
+        .expect("no errors").key;
+    let id = block!(client.attest(Mechanism::Ed255, key).expect("no client error"))
+        .expect("no errors").certificate;
+    let der = block!(client.read_certificate(id).expect("no client error")).expect("no errors").der;
+    let attestation = Certificate::parse(&der).unwrap();
+    let attn_certificate = Certificate::parse(&attn_der).unwrap();
+    assert_eq!(attestation.issuer, attn_certificate.subject);
+    assert_eq!(attestation.authority_key_identifier, attn_certificate.subject_key_identifier);
+    let mut chain = der.clone();
+    chain.extend_from_slice(&attn_der).unwrap();
+    let now = Some(DateTime::new(2025, 1, 1, 0, 0, 0));
+    assert_eq!(
+        block!(client.verify_certificate_chain(&chain, &[root], now).expect("no client error")).map(|reply| reply.anchor),
+        Ok(root),
+    );
+
+    // for good
+    block!(client.lock_provisioning().expect("no client error")).expect("no errors");
+    assert_eq!(
+        block!(client.provision_attestation_key(Mechanism::Ed255, None).expect("no client error")).map(drop),
+        Err(Error::ProvisioningLocked),
+    );
+    assert_eq!(install(&mut client, Mechanism::Ed255, &chain), Err(Error::ProvisioningLocked));
+
+    // without certificates, the injected key attests under the default name
+    let key = block!(client.generate_p256_private_key(Location::Internal).expect("no client error"))
+        .expect("no errors").key;
+    let id = block!(client.attest(Mechanism::P256, key).expect("no client error"))
+        .expect("no errors").certificate;
+    let der = block!(client.read_certificate(id).expect("no client error")).expect("no errors").der;
+    let attestation = Certificate::parse(&der).unwrap();
+    assert!(attestation.issuer.windows(7).any(|window| window == b"Trussed"));
+    assert!(attestation.is_signed_by(&crate::x509::PublicKey::P256(&expected)));
+}
+
+#[test]
+#[serial]
+fn attestation_statement() {
+    use crate::client::{CertificateClient, CryptoClient, ManagementClient, mechanisms::{Ed255, P256}};
+    use crate::x509;
+    use hex_literal::hex;
+
+    #[derive(serde::Deserialize)]
+    struct Statement {
+        alg: Option<i32>,
+        sig: Bytes<80>,
+        x5c: heapless::Vec<Bytes<512>, 4>,
+    }
+
+    let memory = create_memory!();
+    setup!(client, Store, Platform, memory, [0u8; 32], true, None, true);
+    let attn_public_key = block!(client.provision_attestation_key(Mechanism::P256, Some(&[0x42; 32])).expect("no client error"))
+        .expect("no errors").public_key;
+
+    // a credential, in authenticator data with its RP ID hash, flags, counter, AAGUID and ID
+    let credential = block!(client.generate_p256_private_key(Location::Internal).expect("no client error"))
+        .expect("no errors").key;
+    let cose_key = block!(client.serialize_p256_key(credential, KeySerialization::Cose).expect("no client error"))
+        .expect("no errors").serialized_key;
+    let raw_key = block!(client.serialize_p256_key(credential, KeySerialization::Raw).expect("no client error"))
+        .expect("no errors").serialized_key;
+    let mut authenticator_data = Message::new();
+    authenticator_data.extend_from_slice(&[0x11; 32]).unwrap();
+    authenticator_data.extend_from_slice(&hex!("41 00000007")).unwrap();
+    authenticator_data.extend_from_slice(&[0; 16]).unwrap();
+    authenticator_data.extend_from_slice(&hex!("0010")).unwrap();
+    authenticator_data.extend_from_slice(&[0x22; 16]).unwrap();
+    authenticator_data.extend_from_slice(&cose_key).unwrap();
+    let client_data_hash = [0x33; 32];
+    let statement = |client: &mut _, format, mechanism, authenticator_data: &[u8]| {
+        block!(CryptoClient::attestation_statement(client, format, mechanism, authenticator_data, &client_data_hash)
+            .expect("no client error")).map(|reply| reply.statement)
+    };
+
+    // there are no certificates yet
+    assert_eq!(
+        statement(&mut client, AttestationStatementFormat::Packed, Mechanism::P256, &authenticator_data),
+        Err(Error::NoSuchCertificate),
+    );
+
+    // a root certifies the attestation key
+    let root_key = block!(client.generate_ed255_private_key(Location::Internal).expect("no client error"))
+        .expect("no errors").key;
+    let root_name = DistinguishedName::default().set_common_name("Root");
+    let issue = |client: &mut _, subject_key, template| {
+        let id = block!(CertificateClient::issue_certificate(client, Mechanism::Ed255, root_key, root_name.clone(), subject_key, template, Location::Volatile)
+            .expect("no client error")).expect("no errors").certificate;
+        block!(CertificateClient::read_certificate(client, id).expect("no client error")).expect("no errors").der
+    };
+    let root_der = issue(&mut client, root_key, CertificateTemplate::default()
+        .set_subject(root_name.clone())
+        .set_key_usage(KeyUsage::KEY_CERT_SIGN)
+        .set_basic_constraints(BasicConstraints { ca: true, path_length: None }));
+    let attn_key = block!(client.deserialize_p256_key(&attn_public_key, KeySerialization::Raw, StorageAttributes::new().set_persistence(Location::Volatile))
+        .expect("no client error")).expect("no errors").key;
+    let attn_der = issue(&mut client, attn_key, CertificateTemplate::default()
+        .set_subject(DistinguishedName::default()
+            .set_country(b"CH")
+            .set_organization("Trussed")
+            .set_organizational_unit("Authenticator Attestation")
+            .set_common_name("Trussed Attestation")));
+    let mut chain = attn_der.clone();
+    chain.extend_from_slice(&root_der).unwrap();
+    block!(client.install_attestation_certificates(Mechanism::P256, &chain).expect("no client error")).expect("no errors");
+
+    let mut attn_point = Bytes::<65>::from_slice(&[0x04]).unwrap();
+    attn_point.extend_from_slice(&attn_public_key).unwrap();
+    let attn_public_key = x509::PublicKey::P256(&attn_point);
+
+    // packed signs the authenticator data and the client data hash, and has all certificates
+    let packed = statement(&mut client, AttestationStatementFormat::Packed, Mechanism::P256, &authenticator_data)
+        .expect("no errors");
+    assert_eq!(&packed[..6], &hex!("A3 63 616C67 26"));
+    let packed: Statement = crate::cbor_deserialize(&packed).unwrap();
+    assert_eq!(packed.alg, Some(-7));
+    assert_eq!(packed.x5c.len(), 2);
+    assert_eq!(&packed.x5c[0][..], &attn_der[..]);
+    assert_eq!(&packed.x5c[1][..], &root_der[..]);
+    let mut signed = authenticator_data.clone();
+    signed.extend_from_slice(&client_data_hash).unwrap();
+    assert!(attn_public_key.verify(x509::SignatureAlgorithm::P256, &signed, &packed.sig));
+
+    // fido-u2f signs the U2F registration data, and has only the attestation certificate
+    let u2f = statement(&mut client, AttestationStatementFormat::FidoU2f, Mechanism::P256, &authenticator_data)
+        .expect("no errors");
+    assert_eq!(u2f[0], 0xA2);
+    let u2f: Statement = crate::cbor_deserialize(&u2f).unwrap();
+    assert_eq!(u2f.alg, None);
+    assert_eq!(u2f.x5c.len(), 1);
+    assert_eq!(&u2f.x5c[0][..], &attn_der[..]);
+    let mut signed = Message::from_slice(&[0]).unwrap();
+    signed.extend_from_slice(&[0x11; 32]).unwrap();
+    signed.extend_from_slice(&client_data_hash).unwrap();
+    signed.extend_from_slice(&[0x22; 16]).unwrap();
+    signed.push(0x04).unwrap();
+    signed.extend_from_slice(&raw_key).unwrap();
+    assert!(attn_public_key.verify(x509::SignatureAlgorithm::P256, &signed, &u2f.sig));
+
+    // fido-u2f needs the attested credential data, and P256
+    let mut without_credential = authenticator_data.clone();
+    without_credential[32] = 0x01;
+    assert_eq!(
+        statement(&mut client, AttestationStatementFormat::FidoU2f, Mechanism::P256, &without_credential),
+        Err(Error::InvalidSerializationFormat),
+    );
+    assert_eq!(
+        statement(&mut client, AttestationStatementFormat::FidoU2f, Mechanism::Ed255, &authenticator_data),
+        Err(Error::MechanismNotAvailable),
+    );
+    // the Ed255 attestation key has no certificates
+    block!(client.provision_attestation_key(Mechanism::Ed255, None).expect("no client error")).expect("no errors");
+    assert_eq!(
+        statement(&mut client, AttestationStatementFormat::Packed, Mechanism::Ed255, &authenticator_data),
+        Err(Error::NoSuchCertificate),
+    );
+}
+
+#[test]
+#[serial]
+fn dice() {
+    use crate::client::CryptoClient;
+    use crate::platform::DiceInputs;
+    use crate::x509;
+
+    fn public_keys(client: &mut impl CryptoClient) -> (Vec<u8, 32>, Vec<u8, 32>) {
+        let chain = block!(client.dice_certificates().expect("no client error")).expect("no errors").chain;
+        let certificates = x509::parse_chain(&chain).unwrap();
+        (
+            Vec::from_slice(certificates[1].public_key.as_bytes()).unwrap(),
+            Vec::from_slice(certificates[0].public_key.as_bytes()).unwrap(),
+        )
+    }
+
+    let inputs = DiceInputs { compound_device_identifier: [1; 32], measurement: [2; 32] };
+    let (device_id, alias) = {
+        let memory = create_memory!();
+        setup!(client, DiceStore, DicePlatform, memory, [0u8; 32], true, None, false, Some(inputs));
+        public_keys(&mut client)
+    };
+
+    // the keys are stored under the key encryption key, and stay the same
+    {
+        let memory = create_memory!();
+        setup!(client, EncryptedDiceStore, EncryptedDicePlatform, memory, [0u8; 32], true, Some([0x42u8; 32]), false, Some(inputs));
+        assert_eq!(public_keys(&mut client), (device_id.clone(), alias.clone()));
+    }
+
+    // other firmware is measured into another CDI, and gets neither key
+    let updated = DiceInputs { compound_device_identifier: [3; 32], measurement: [4; 32] };
+    let memory = create_memory!();
+    setup!(client, UpdatedDiceStore, UpdatedDicePlatform, memory, [0u8; 32], true, None, false, Some(updated));
+    let (updated_device_id, updated_alias) = public_keys(&mut client);
+    assert_ne!(device_id, updated_device_id);
+    assert_ne!(alias, updated_alias);
+
+    // only DICE signers may sign with the Alias key
+    assert_eq!(
+        block!(client.dice_sign(b"message").expect("no client error")).map(drop),
+        Err(Error::RequestNotAvailable),
+    );
+}
+
+#[test]
+#[serial]
+fn dice_requires_inputs() {
+    use crate::client::CryptoClient;
+    setup!(client);
+
+    // the mock platform has no DICE inputs
+    assert_eq!(
+        block!(client.dice_certificates().expect("no client error")).map(drop),
+        Err(Error::RequestNotAvailable),
+    );
+    assert_eq!(
+        block!(client.dice_sign(b"message").expect("no client error")).map(drop),
+        Err(Error::RequestNotAvailable),
+    );
+}
+
+#[test]
+#[serial]
+fn counters() {
+    use crate::client::{CertificateClient as _, CounterClient as _};
+    use crate::store::counterstore::COUNTER_ZERO;
+    setup!(client);
+
+    let id = block!(client.create_counter(Location::Internal).expect("no client error"))
+        .expect("no errors").id;
+    block!(client.set_counter_at_least(id, u128::MAX - 1).expect("no client error")).expect("no errors");
+    assert_eq!(
+        block!(client.increment_counter_by(id, 2).expect("no client error")).map(drop),
+        Err(Error::CounterOverflow),
+    );
+    assert_eq!(
+        block!(client.increment_counter(id).expect("no client error")).expect("no errors").counter,
+        u128::MAX - 1,
+    );
+
+    // counter zero numbers the certificates, so it can be read but not deleted or listed
+    let certificate = block!(client.write_certificate(Location::Internal, &[0x30, 0x00]).expect("no client error"))
+        .expect("no errors").id;
+    assert_eq!(
+        block!(client.read_counter(COUNTER_ZERO).expect("no client error")).expect("no errors").counter,
+        certificate.0.0 + 1,
+    );
+    assert_eq!(
+        block!(client.delete_counter(COUNTER_ZERO).expect("no client error")).map(drop),
+        Err(Error::ObjectHandleInvalid),
+    );
+    let listed = block!(client.list_counters(None, None).expect("no client error")).expect("no errors");
+    assert_eq!(&listed.counters[..], &[(id, Location::Internal, u128::MAX)]);
+}
@@ -22,7 +22,7 @@ use rand_core::{CryptoRng, RngCore};
 use serde::{Deserialize, Serialize};
 
 use crate::config::*;
-use crate::key::Secrecy;
+use crate::key::{self, Secrecy};
 
 pub use crate::platform::Platform;
 pub use crate::client::FutureResult;
@@ -155,11 +155,11 @@ macro_rules! impl_id { ($Name:ident) => {
 impl_id!(CertId);
 impl_id!(CounterId);
 impl_id!(KeyId);
-// TODO: decide whether this is good idea.
-// It would allow using the same underlying u128 ID for the public key of the private
-// key in a keypair. However, DeleteKey and others would need to be adjusted.
-// impl_id!(PublicKeyId);
-// impl_id!(SecretKeyId);
+// chosen by the app, e.g. `PinId::from_special(1)`
+impl_id!(PinId);
+// The halves of a keypair share their `KeyId`: the private key is stored as secret key,
+// the public key as public key under the same ID. Deleting the `KeyId` deletes both.
+// `DeriveKey` replies with a copy of the public key under an ID of its own.
 
 
 pub mod ui {
@@ -441,18 +441,345 @@ pub enum Location {
     External,
 }
 
+/// A key as listed by `ListKeys`: its ID, kind, secrecy and location.
+pub type KeyListing = (KeyId, key::Kind, key::Secrecy, Location);
+
+pub type CounterListing = (CounterId, Location, u128);
+
+/// Where the next chunk of a backup starts.
+#[derive(Copy, Clone, Default, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct BackupCursor {
+    /// The random ID of the backup, bound to each of its chunks.
+    pub backup: [u8; 16],
+    pub chunk: u32,
+    pub file: u32,
+    pub offset: u32,
+}
+
+pub type NameAttribute = String<MAX_NAME_ATTRIBUTE_LENGTH>;
+
+/// The subject or issuer of a certificate.
+#[derive(Clone, Default, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct DistinguishedName {
+    /// Two letter ISO 3166 code, such as `b"CH"`.
+    pub country: Option<[u8; 2]>,
+    pub state: Option<NameAttribute>,
+    pub locality: Option<NameAttribute>,
+    pub organization: Option<NameAttribute>,
+    pub organizational_unit: Option<NameAttribute>,
+    pub common_name: Option<NameAttribute>,
+}
+
+impl DistinguishedName {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn set_country(mut self, country: &[u8; 2]) -> Self {
+        self.country = Some(*country);
+        self
+    }
+
+    /// Panics if the state is longer than `MAX_NAME_ATTRIBUTE_LENGTH`.
+    pub fn set_state(mut self, state: &str) -> Self {
+        self.state = Some(NameAttribute::from(state));
+        self
+    }
+
+    /// Panics if the locality is longer than `MAX_NAME_ATTRIBUTE_LENGTH`.
+    pub fn set_locality(mut self, locality: &str) -> Self {
+        self.locality = Some(NameAttribute::from(locality));
+        self
+    }
+
+    /// Panics if the organization is longer than `MAX_NAME_ATTRIBUTE_LENGTH`.
+    pub fn set_organization(mut self, organization: &str) -> Self {
+        self.organization = Some(NameAttribute::from(organization));
+        self
+    }
+
+    /// Panics if the unit is longer than `MAX_NAME_ATTRIBUTE_LENGTH`.
+    pub fn set_organizational_unit(mut self, organizational_unit: &str) -> Self {
+        self.organizational_unit = Some(NameAttribute::from(organizational_unit));
+        self
+    }
+
+    /// Panics if the common name is longer than `MAX_NAME_ATTRIBUTE_LENGTH`.
+    pub fn set_common_name(mut self, common_name: &str) -> Self {
+        self.common_name = Some(NameAttribute::from(common_name));
+        self
+    }
+}
+
+/// A point in time, in UTC.
+///
+/// The derived ordering is chronological.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Serialize, Deserialize)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl DateTime {
+    pub const fn new(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> Self {
+        Self { year, month, day, hour, minute, second }
+    }
+
+    /// The UTC date and time `seconds` after the UNIX epoch.
+    pub fn from_unix_time(seconds: u64) -> Self {
+        let (days, seconds) = (seconds / 86_400, seconds % 86_400);
+        // civil from days, see http://howardhinnant.github.io/date_algorithms.html
+        let z = days + 719_468;
+        let era = z / 146_097;
+        let day_of_era = z % 146_097;
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+        let year = year_of_era + era * 400 + (month <= 2) as u64;
+        Self::new(
+            year as u16, month as u8, day as u8,
+            (seconds / 3600) as u8, (seconds / 60 % 60) as u8, (seconds % 60) as u8,
+        )
+    }
+
+    /// Whether this is an actual point in time with a four digit year.
+    pub fn is_valid(&self) -> bool {
+        let leap_year = matches!((self.year % 4, self.year % 100, self.year % 400), (0, 1.., _) | (_, _, 0));
+        let days_in_month = match self.month {
+            2 if leap_year => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        };
+        [
+            self.year <= 9999,
+            self.month >= 1,
+            self.month <= 12,
+            self.day >= 1,
+            self.day <= days_in_month,
+            self.hour <= 23,
+            self.minute <= 59,
+            self.second <= 59,
+        ].iter().all(|b| *b)
+    }
+}
+
+bitflags::bitflags! {
+    #[derive(Serialize, Deserialize)]
+    /// The X.509 key usage extension, bit `n` is the named bit `n` of RFC 5280.
+    pub struct KeyUsage: u16 {
+        const DIGITAL_SIGNATURE = 1 << 0;
+        const NON_REPUDIATION = 1 << 1;
+        const KEY_ENCIPHERMENT = 1 << 2;
+        const DATA_ENCIPHERMENT = 1 << 3;
+        const KEY_AGREEMENT = 1 << 4;
+        const KEY_CERT_SIGN = 1 << 5;
+        const CRL_SIGN = 1 << 6;
+        const ENCIPHER_ONLY = 1 << 7;
+        const DECIPHER_ONLY = 1 << 8;
+    }
+}
+
+/// The X.509 basic constraints extension.
+#[derive(Copy, Clone, Default, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct BasicConstraints {
+    pub ca: bool,
+    /// Only allowed for CAs.
+    pub path_length: Option<u8>,
+}
+
+/// An entry of the X.509 subject alternative name extension.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub enum GeneralName {
+    /// An `rfc822Name`, in ASCII.
+    Email(NameAttribute),
+    /// A `dNSName`, in ASCII.
+    Dns(NameAttribute),
+    /// A `uniformResourceIdentifier`, in ASCII.
+    Uri(NameAttribute),
+    /// An `iPAddress`, of 4 bytes for IPv4 or 16 bytes for IPv6.
+    IpAddress(Bytes<16>),
+}
+
+/// An X.509 extension not otherwise covered by `CertificateTemplate`.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct CertificateExtension {
+    /// The contents of the DER encoded OID, without tag and length.
+    pub oid: Bytes<MAX_EXTENSION_OID_LENGTH>,
+    pub critical: bool,
+    /// The DER encoded value, which is wrapped in the `extnValue` OCTET STRING.
+    pub value: Bytes<MAX_EXTENSION_VALUE_LENGTH>,
+}
+
+/// What goes into a certificate, besides its key, serial number and issuer.
+///
+/// An empty subject is replaced by a common name derived from the key's ID, unless there are
+/// alternative names. The validity defaults to start at 2021-03-13 12:00:00, and to never end.
+/// Certificate signing requests have no validity, and leave an empty subject empty, so they need
+/// a subject or alternative names.
+#[derive(Clone, Default, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct CertificateTemplate {
+    pub subject: DistinguishedName,
+    pub alternative_names: Vec<GeneralName, MAX_ALTERNATIVE_NAMES>,
+    pub not_before: Option<DateTime>,
+    pub not_after: Option<DateTime>,
+    pub key_usage: Option<KeyUsage>,
+    pub basic_constraints: Option<BasicConstraints>,
+    pub extensions: Vec<CertificateExtension, MAX_CERTIFICATE_EXTENSIONS>,
+}
+
+impl CertificateTemplate {
+    pub fn set_subject(mut self, subject: DistinguishedName) -> Self {
+        self.subject = subject;
+        self
+    }
+
+    /// Panics if there are more than `MAX_ALTERNATIVE_NAMES` alternative names.
+    pub fn add_alternative_name(mut self, name: GeneralName) -> Self {
+        self.alternative_names.push(name).expect("too many alternative names");
+        self
+    }
+
+    pub fn set_validity(mut self, not_before: DateTime, not_after: Option<DateTime>) -> Self {
+        self.not_before = Some(not_before);
+        self.not_after = not_after;
+        self
+    }
+
+    pub fn set_key_usage(mut self, key_usage: KeyUsage) -> Self {
+        self.key_usage = Some(key_usage);
+        self
+    }
+
+    pub fn set_basic_constraints(mut self, basic_constraints: BasicConstraints) -> Self {
+        self.basic_constraints = Some(basic_constraints);
+        self
+    }
+
+    /// Panics if there are more than `MAX_CERTIFICATE_EXTENSIONS` extensions,
+    /// or the OID or value are too long.
+    pub fn add_extension(mut self, oid: &[u8], critical: bool, value: &[u8]) -> Self {
+        self.extensions.push(CertificateExtension {
+            oid: Bytes::from_slice(oid).expect("OID too long"),
+            critical,
+            value: Bytes::from_slice(value).expect("extension value too long"),
+        }).expect("too many extensions");
+        self
+    }
+}
+
+/// Attributes of a stored certificate, kept next to its DER encoding.
+#[derive(Clone, Default, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct CertificateAttributes {
+    /// Description of the certificate, to find it again
+    pub label: Option<key::Label>,
+
+    /// The key the certificate certifies
+    pub key: Option<KeyId>,
+}
+
+impl CertificateAttributes {
+    /// Panics if the label is longer than `MAX_KEY_LABEL_LENGTH`.
+    pub fn set_label(mut self, label: &[u8]) -> Self {
+        self.label = Some(key::Label::from_slice(label).expect("label too long"));
+        self
+    }
+
+    pub fn set_key(mut self, key: KeyId) -> Self {
+        self.key = Some(key);
+        self
+    }
+}
+
+/// A certificate as listed by `ListCertificates`: its ID, location and attributes.
+pub type CertificateListing = (CertId, Location, CertificateAttributes);
+
+/// Which certificates `ListCertificates` returns, where `None` matches any.
+///
+/// The hashes are SHA-256 of the DER encoded names, and of the subject public key
+/// (the contents of its BIT STRING).
+#[derive(Clone, Default, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct CertificateFilter {
+    pub location: Option<Location>,
+    pub label: Option<key::Label>,
+    pub key: Option<KeyId>,
+    pub subject: Option<[u8; 32]>,
+    pub issuer: Option<[u8; 32]>,
+    pub public_key: Option<[u8; 32]>,
+}
+
+impl CertificateFilter {
+    pub fn set_location(mut self, location: Location) -> Self {
+        self.location = Some(location);
+        self
+    }
+
+    /// Panics if the label is longer than `MAX_KEY_LABEL_LENGTH`.
+    pub fn set_label(mut self, label: &[u8]) -> Self {
+        self.label = Some(key::Label::from_slice(label).expect("label too long"));
+        self
+    }
+
+    pub fn set_key(mut self, key: KeyId) -> Self {
+        self.key = Some(key);
+        self
+    }
+
+    pub fn set_subject(mut self, subject_hash: [u8; 32]) -> Self {
+        self.subject = Some(subject_hash);
+        self
+    }
+
+    pub fn set_issuer(mut self, issuer_hash: [u8; 32]) -> Self {
+        self.issuer = Some(issuer_hash);
+        self
+    }
+
+    pub fn set_public_key(mut self, public_key_hash: [u8; 32]) -> Self {
+        self.public_key = Some(public_key_hash);
+        self
+    }
+}
+
+/// The WebAuthn attestation statement formats made with the attestation keys.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub enum AttestationStatementFormat {
+    /// `packed`, with the installed certificates.
+    Packed,
+    /// `fido-u2f`, with the attestation certificate only, and only for P256.
+    FidoU2f,
+}
+
 #[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub struct StorageAttributes {
     // each object must have a unique ID
     // unique_id: UniqueId,
 
-    // description of object
-    // label: String<MAX_LABEL_LENGTH>,
+    /// Description of the object, to find it again
+    pub label: Option<key::Label>,
+
+    /// Opaque app-defined data
+    pub tag: Option<key::Tag>,
 
     // // cryptoki: token (vs session) object
     // persistent: bool,
     pub persistence: Location,
 
+    /// Usage policy of the key, fixed at creation
+    pub policy: key::Policy,
+
+    /// Whether the key may be wrapped, fixed at creation; ignored for keys with limits
+    pub extractable: bool,
+
+    /// Number of uses and deadline after which the key is deleted
+    pub limits: key::Limits,
+
     // cryptoki: user must be logged in
     // private: bool,
 
@@ -467,6 +794,33 @@ impl StorageAttributes {
         self.persistence = persistence;
         self
     }
+
+    pub fn set_policy(mut self, policy: key::Policy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn set_extractable(mut self, extractable: bool) -> Self {
+        self.extractable = extractable;
+        self
+    }
+
+    pub fn set_limits(mut self, limits: key::Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Panics if the label is longer than `MAX_KEY_LABEL_LENGTH`.
+    pub fn set_label(mut self, label: &[u8]) -> Self {
+        self.label = Some(key::Label::from_slice(label).expect("label too long"));
+        self
+    }
+
+    /// Panics if the tag is longer than `MAX_KEY_TAG_LENGTH`.
+    pub fn set_tag(mut self, tag: &[u8]) -> Self {
+        self.tag = Some(key::Tag::from_slice(tag).expect("tag too long"));
+        self
+    }
 }
 
 impl StorageAttributes {
@@ -474,10 +828,14 @@ impl StorageAttributes {
     pub fn new() -> Self {
         Self {
             // unique_id,
-            // label: String::new(),
+            label: None,
+            tag: None,
             // persistent: false,
 
             persistence: Location::Volatile,
+            policy: key::Policy::new(),
+            extractable: false,
+            limits: key::Limits::new(),
 
             // modifiable: true,
             // copyable: true,
@@ -494,7 +852,15 @@ impl Default for StorageAttributes {
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub enum Mechanism {
+    Aes128Cbc,
+    Aes128Ctr,
+    Aes128Ecb,
+    Aes192Cbc,
+    Aes192Ctr,
+    Aes192Ecb,
     Aes256Cbc,
+    Aes256Ctr,
+    Aes256Ecb,
     Chacha8Poly1305,
     Ed255,
     HmacBlake2s,
@@ -507,6 +873,9 @@ pub enum Mechanism {
     // clients can also do hashing by themselves
     Sha256,
     Tdes,
+    TdesCbc,
+    TdesCtr,
+    TdesEcb,
     Totp,
     Trng,
     X255,
@@ -530,6 +899,15 @@ pub enum KeySerialization {
 
 pub type Signature = Bytes<MAX_SIGNATURE_LENGTH>;
 
+/// Encoding of the shares of a split key.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub enum ShareFormat {
+    /// The threshold, the share's index and its value.
+    Raw,
+    /// The indices of the words of a SLIP-0039 mnemonic, as big-endian `u16`s.
+    Slip39,
+}
+
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub enum SignatureSerialization {
     Asn1Der,
@@ -0,0 +1,558 @@
+//! A minimal X.509 certificate parser, and certification path validation.
+//!
+//! Signatures are understood as far as Trussed can verify them: Ed25519, and ECDSA over
+//! P-256 with SHA-256. Certificates with other algorithms parse, but never verify.
+//!
+//! Of the extensions, key usage and basic constraints are processed, the key identifiers
+//! are extracted, and any other extension is left to the application (see
+//! [`Certificate::extension`]).
+
+use core::convert::{TryFrom, TryInto};
+
+use heapless::Vec;
+use hex_literal::hex;
+
+use crate::{
+    config::MAX_CERTIFICATE_CHAIN_LENGTH,
+    error::{Error, Result},
+    types::{BasicConstraints, DateTime, KeyUsage},
+};
+
+const BOOLEAN: u8 = 0x01;
+const INTEGER: u8 = 0x02;
+const BIT_STRING: u8 = 0x03;
+const OCTET_STRING: u8 = 0x04;
+const OBJECT_IDENTIFIER: u8 = 0x06;
+const UTC_TIME: u8 = 0x17;
+const GENERALIZED_TIME: u8 = 0x18;
+const SEQUENCE: u8 = 0x30;
+
+// context specific tags of the TBSCertificate, and of AuthorityKeyIdentifier
+const VERSION: u8 = 0xA0;
+const ISSUER_UNIQUE_ID: u8 = 0x81;
+const SUBJECT_UNIQUE_ID: u8 = 0x82;
+const EXTENSIONS: u8 = 0xA3;
+const KEY_IDENTIFIER: u8 = 0x80;
+
+// AlgorithmIdentifier contents, parameters are absent for both
+const ED255_SIGNATURE_ALGORITHM: &[u8] = &hex!("06 03 2B 65 70");
+const P256_SIGNATURE_ALGORITHM: &[u8] = &hex!("06 08 2A 86 48 CE 3D 04 03 02");
+
+const ED255_OID: &[u8] = &hex!("2B 65 70");
+const EC_PUBLIC_KEY_OID: &[u8] = &hex!("2A 86 48 CE 3D 02 01");
+const P256_OID: &[u8] = &hex!("2A 86 48 CE 3D 03 01 07");
+
+const SUBJECT_KEY_IDENTIFIER_OID: &[u8] = &hex!("55 1D 0E");
+const KEY_USAGE_OID: &[u8] = &hex!("55 1D 0F");
+const SUBJECT_ALTERNATIVE_NAME_OID: &[u8] = &hex!("55 1D 11");
+const BASIC_CONSTRAINTS_OID: &[u8] = &hex!("55 1D 13");
+const AUTHORITY_KEY_IDENTIFIER_OID: &[u8] = &hex!("55 1D 23");
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SignatureAlgorithm<'a> {
+    Ed255,
+    P256,
+    /// The contents of the DER encoded AlgorithmIdentifier.
+    Other(&'a [u8]),
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PublicKey<'a> {
+    Ed255(&'a [u8; 32]),
+    /// A compressed or uncompressed SEC1 encoded point.
+    P256(&'a [u8]),
+    /// The contents of the DER encoded AlgorithmIdentifier, and the public key bits.
+    Other { algorithm: &'a [u8], key: &'a [u8] },
+}
+
+impl<'a> PublicKey<'a> {
+    /// The subject public key, as in the certificate.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        match self {
+            PublicKey::Ed255(key) => &key[..],
+            PublicKey::P256(key) => key,
+            PublicKey::Other { key, .. } => key,
+        }
+    }
+
+    /// Whether `signature` is a valid signature of `message` by this key.
+    pub fn verify(&self, algorithm: SignatureAlgorithm<'_>, message: &[u8], signature: &[u8]) -> bool {
+        match (self, algorithm) {
+            (PublicKey::Ed255(key), SignatureAlgorithm::Ed255) => {
+                let key = match salty::signature::PublicKey::try_from(*key) {
+                    Ok(key) => key,
+                    Err(_) => return false,
+                };
+                let signature: &[u8; 64] = match signature.try_into() {
+                    Ok(signature) => signature,
+                    Err(_) => return false,
+                };
+                key.verify(message, &salty::signature::Signature::from(signature)).is_ok()
+            }
+            (PublicKey::P256(key), SignatureAlgorithm::P256) => {
+                let key = match p256_cortex_m4::PublicKey::from_sec1_bytes(key) {
+                    Ok(key) => key,
+                    Err(_) => return false,
+                };
+                // the Cortex-M4 implementation does not decode DER signatures
+                let signature = match parse_ecdsa_signature(signature)
+                    .ok()
+                    .and_then(|signature| p256_cortex_m4::Signature::from_untagged_bytes(&signature).ok())
+                {
+                    Some(signature) => signature,
+                    None => return false,
+                };
+                key.verify(message, &signature)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// A parsed X.509 certificate, borrowing from its DER encoding.
+///
+/// Names are kept DER encoded, and compared byte for byte during path validation.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Certificate<'a> {
+    pub der: &'a [u8],
+    /// The DER encoding of the signed part of the certificate.
+    pub tbs_certificate: &'a [u8],
+    pub version: u8,
+    pub serial_number: &'a [u8],
+    pub signature_algorithm: SignatureAlgorithm<'a>,
+    pub issuer: &'a [u8],
+    pub not_before: DateTime,
+    pub not_after: DateTime,
+    pub subject: &'a [u8],
+    pub public_key: PublicKey<'a>,
+    pub subject_key_identifier: Option<&'a [u8]>,
+    pub authority_key_identifier: Option<&'a [u8]>,
+    pub key_usage: Option<KeyUsage>,
+    pub basic_constraints: Option<BasicConstraints>,
+    /// Whether there is a critical extension other than the ones above, or subject alternative names.
+    pub unhandled_critical_extension: bool,
+    /// The contents of the DER encoded extensions.
+    pub extensions: &'a [u8],
+    pub signature: &'a [u8],
+}
+
+impl<'a> Certificate<'a> {
+    pub fn parse(der: &'a [u8]) -> Result<Self> {
+        let mut reader = Reader(der);
+        let mut certificate = Reader(reader.read(SEQUENCE)?);
+        reader.finish()?;
+
+        let tbs_certificate = certificate.read_encoding(SEQUENCE)?;
+        let signature_algorithm = parse_signature_algorithm(certificate.read(SEQUENCE)?);
+        let signature = parse_bit_string(certificate.read(BIT_STRING)?)?;
+        certificate.finish()?;
+
+        let mut tbs = Reader(Reader(tbs_certificate).read(SEQUENCE)?);
+        let version = match tbs.read_optional(VERSION)? {
+            Some(version) => {
+                let mut version = Reader(version);
+                // version 1 is the default, so DER omits it
+                let number = match version.read(INTEGER)? {
+                    [1] => 2,
+                    [2] => 3,
+                    _ => return Err(Error::InvalidCertificate),
+                };
+                version.finish()?;
+                number
+            }
+            None => 1,
+        };
+        let serial_number = tbs.read(INTEGER)?;
+        if parse_signature_algorithm(tbs.read(SEQUENCE)?) != signature_algorithm {
+            return Err(Error::InvalidCertificate);
+        }
+        let issuer = tbs.read_encoding(SEQUENCE)?;
+
+        let mut validity = Reader(tbs.read(SEQUENCE)?);
+        let not_before = parse_time(&mut validity)?;
+        let not_after = parse_time(&mut validity)?;
+        validity.finish()?;
+
+        let subject = tbs.read_encoding(SEQUENCE)?;
+
+        let mut subject_public_key_info = Reader(tbs.read(SEQUENCE)?);
+        let algorithm = subject_public_key_info.read(SEQUENCE)?;
+        let key = parse_bit_string(subject_public_key_info.read(BIT_STRING)?)?;
+        subject_public_key_info.finish()?;
+        let public_key = parse_public_key(algorithm, key)?;
+
+        if version >= 2 {
+            tbs.read_optional(ISSUER_UNIQUE_ID)?;
+            tbs.read_optional(SUBJECT_UNIQUE_ID)?;
+        }
+
+        let mut certificate = Self {
+            der,
+            tbs_certificate,
+            version,
+            serial_number,
+            signature_algorithm,
+            issuer,
+            not_before,
+            not_after,
+            subject,
+            public_key,
+            subject_key_identifier: None,
+            authority_key_identifier: None,
+            key_usage: None,
+            basic_constraints: None,
+            unhandled_critical_extension: false,
+            extensions: &[],
+            signature,
+        };
+
+        if version == 3 {
+            if let Some(extensions) = tbs.read_optional(EXTENSIONS)? {
+                let mut extensions = Reader(extensions);
+                certificate.extensions = extensions.read(SEQUENCE)?;
+                extensions.finish()?;
+                certificate.parse_extensions()?;
+            }
+        }
+        tbs.finish()?;
+
+        Ok(certificate)
+    }
+
+    fn parse_extensions(&mut self) -> Result<()> {
+        let mut extensions = Reader(self.extensions);
+        if extensions.is_empty() {
+            return Err(Error::InvalidCertificate);
+        }
+        while !extensions.is_empty() {
+            let (oid, critical, value) = parse_extension(extensions.read(SEQUENCE)?)?;
+            let duplicate = match oid {
+                SUBJECT_KEY_IDENTIFIER_OID => {
+                    let mut value = Reader(value);
+                    let duplicate = self.subject_key_identifier.replace(value.read(OCTET_STRING)?).is_some();
+                    value.finish()?;
+                    duplicate
+                }
+                AUTHORITY_KEY_IDENTIFIER_OID => {
+                    let mut value = Reader(value);
+                    let mut identifier = Reader(value.read(SEQUENCE)?);
+                    value.finish()?;
+                    // the issuer and serial number alternative is not used
+                    match identifier.read_optional(KEY_IDENTIFIER)? {
+                        Some(key_identifier) => self.authority_key_identifier.replace(key_identifier).is_some(),
+                        None => false,
+                    }
+                }
+                KEY_USAGE_OID => self.key_usage.replace(parse_key_usage(value)?).is_some(),
+                BASIC_CONSTRAINTS_OID => self.basic_constraints.replace(parse_basic_constraints(value)?).is_some(),
+                SUBJECT_ALTERNATIVE_NAME_OID => false,
+                _ => {
+                    self.unhandled_critical_extension |= critical;
+                    false
+                }
+            };
+            if duplicate {
+                return Err(Error::InvalidCertificate);
+            }
+        }
+        Ok(())
+    }
+
+    /// The criticality and the (DER encoded) value of the extension with the given DER encoded OID.
+    pub fn extension(&self, oid: &[u8]) -> Option<(bool, &'a [u8])> {
+        let mut extensions = Reader(self.extensions);
+        while !extensions.is_empty() {
+            let (id, critical, value) = parse_extension(extensions.read(SEQUENCE).ok()?).ok()?;
+            if id == oid {
+                return Some((critical, value));
+            }
+        }
+        None
+    }
+
+    pub fn is_self_issued(&self) -> bool {
+        self.issuer == self.subject
+    }
+
+    pub fn is_signed_by(&self, issuer_key: &PublicKey<'_>) -> bool {
+        issuer_key.verify(self.signature_algorithm, self.tbs_certificate, self.signature)
+    }
+
+    pub fn is_valid_at(&self, time: &DateTime) -> bool {
+        self.not_before <= *time && *time <= self.not_after
+    }
+}
+
+/// Parses concatenated DER encoded certificates.
+pub fn parse_chain(der: &[u8]) -> Result<Vec<Certificate<'_>, MAX_CERTIFICATE_CHAIN_LENGTH>> {
+    let mut reader = Reader(der);
+    let mut chain = Vec::new();
+    while !reader.is_empty() {
+        let certificate = Certificate::parse(reader.read_encoding(SEQUENCE)?)?;
+        chain.push(certificate).map_err(|_| Error::InvalidCertificate)?;
+    }
+    if chain.is_empty() {
+        return Err(Error::InvalidCertificate);
+    }
+    Ok(chain)
+}
+
+/// Validates a certification path, following RFC 5280, section 6.1, without policies and name constraints.
+///
+/// The chain starts with the end entity certificate, each certificate is issued by the next one,
+/// and the last one by `anchor`. The chain may end with the trust anchor itself. The validity
+/// periods are checked if `time` is given, and the anchor is only used for its subject, public key
+/// and path length constraint.
+///
+/// Returns `CertificateExpired` if a certificate is not valid at `time`, and `UntrustedCertificate`
+/// for any other failure.
+pub fn verify_chain(chain: &[Certificate<'_>], anchor: &Certificate<'_>, time: Option<&DateTime>) -> Result<()> {
+    let chain = match chain.split_last() {
+        Some((last, rest)) if last.der == anchor.der => rest,
+        _ => chain,
+    };
+
+    let mut issuer = anchor;
+    let mut max_path_length = anchor.basic_constraints.and_then(|constraints| constraints.path_length);
+    for (i, certificate) in chain.iter().enumerate().rev() {
+        let trusted = [
+            !certificate.unhandled_critical_extension,
+            certificate.issuer == issuer.subject,
+            certificate.is_signed_by(&issuer.public_key),
+        ].iter().all(|b| *b);
+        if !trusted {
+            return Err(Error::UntrustedCertificate);
+        }
+        if let Some(time) = time {
+            if !certificate.is_valid_at(time) {
+                return Err(Error::CertificateExpired);
+            }
+        }
+
+        if i > 0 {
+            // issues the next certificate, so must be a CA
+            let constraints = match certificate.basic_constraints {
+                Some(constraints) if constraints.ca => constraints,
+                _ => return Err(Error::UntrustedCertificate),
+            };
+            if let Some(key_usage) = certificate.key_usage {
+                if !key_usage.contains(KeyUsage::KEY_CERT_SIGN) {
+                    return Err(Error::UntrustedCertificate);
+                }
+            }
+            if !certificate.is_self_issued() {
+                max_path_length = match max_path_length {
+                    Some(0) => return Err(Error::UntrustedCertificate),
+                    Some(length) => Some(length - 1),
+                    None => None,
+                };
+            }
+            max_path_length = match (max_path_length, constraints.path_length) {
+                (Some(length), Some(constraint)) => Some(length.min(constraint)),
+                (length, constraint) => length.or(constraint),
+            };
+        }
+
+        issuer = certificate;
+    }
+    Ok(())
+}
+
+/// The relative distinguished names of a DER encoded name, without the SEQUENCE around them.
+pub fn name_contents(name: &[u8]) -> Result<&[u8]> {
+    let mut reader = Reader(name);
+    let contents = reader.read(SEQUENCE)?;
+    reader.finish()?;
+    Ok(contents)
+}
+
+/// Reads DER encoded values one after the other.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns the tag, the contents and the entire encoding of the next value.
+    fn read_any(&mut self) -> Result<(u8, &'a [u8], &'a [u8])> {
+        let encoding = self.0;
+        let (tag, rest) = match encoding {
+            // multi-byte tags are not used by X.509
+            [tag, rest @ ..] if tag & 0x1F != 0x1F => (*tag, rest),
+            _ => return Err(Error::InvalidCertificate),
+        };
+        // DER lengths are definite and minimally encoded, certificates are below 64 KiB
+        let (length, rest) = match rest {
+            [length, rest @ ..] if *length < 0x80 => (*length as usize, rest),
+            [0x81, length, rest @ ..] if *length >= 0x80 => (*length as usize, rest),
+            [0x82, high, low, rest @ ..] if *high != 0 => (u16::from_be_bytes([*high, *low]) as usize, rest),
+            _ => return Err(Error::InvalidCertificate),
+        };
+        if rest.len() < length {
+            return Err(Error::InvalidCertificate);
+        }
+        let header_length = encoding.len() - rest.len();
+        self.0 = &rest[length..];
+        Ok((tag, &rest[..length], &encoding[..header_length + length]))
+    }
+
+    fn read(&mut self, tag: u8) -> Result<&'a [u8]> {
+        match self.read_any()? {
+            (actual, contents, _) if actual == tag => Ok(contents),
+            _ => Err(Error::InvalidCertificate),
+        }
+    }
+
+    fn read_encoding(&mut self, tag: u8) -> Result<&'a [u8]> {
+        match self.read_any()? {
+            (actual, _, encoding) if actual == tag => Ok(encoding),
+            _ => Err(Error::InvalidCertificate),
+        }
+    }
+
+    fn read_optional(&mut self, tag: u8) -> Result<Option<&'a [u8]>> {
+        if self.0.first() == Some(&tag) {
+            self.read(tag).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn finish(self) -> Result<()> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::InvalidCertificate)
+        }
+    }
+}
+
+fn parse_signature_algorithm(algorithm: &[u8]) -> SignatureAlgorithm<'_> {
+    match algorithm {
+        ED255_SIGNATURE_ALGORITHM => SignatureAlgorithm::Ed255,
+        P256_SIGNATURE_ALGORITHM => SignatureAlgorithm::P256,
+        _ => SignatureAlgorithm::Other(algorithm),
+    }
+}
+
+fn parse_public_key<'a>(algorithm: &'a [u8], key: &'a [u8]) -> Result<PublicKey<'a>> {
+    let mut parameters = Reader(algorithm);
+    let oid = parameters.read(OBJECT_IDENTIFIER)?;
+    if oid == ED255_OID && parameters.is_empty() {
+        key.try_into().map(PublicKey::Ed255).map_err(|_| Error::InvalidCertificate)
+    } else if oid == EC_PUBLIC_KEY_OID && parameters.read_optional(OBJECT_IDENTIFIER)? == Some(P256_OID)
+        && parameters.is_empty()
+    {
+        Ok(PublicKey::P256(key))
+    } else {
+        Ok(PublicKey::Other { algorithm, key })
+    }
+}
+
+/// The contents of a BIT STRING holding whole bytes.
+fn parse_bit_string(bit_string: &[u8]) -> Result<&[u8]> {
+    match bit_string {
+        [0, bytes @ ..] => Ok(bytes),
+        _ => Err(Error::InvalidCertificate),
+    }
+}
+
+/// Accepts UTCTime and GeneralizedTime in the forms required by RFC 5280, section 4.1.2.5.
+fn parse_time(reader: &mut Reader<'_>) -> Result<DateTime> {
+    let (tag, time, _) = reader.read_any()?;
+    let (digits, year_digits) = match (tag, time.split_last()) {
+        (UTC_TIME, Some((b'Z', digits))) if digits.len() == 12 => (digits, 2),
+        (GENERALIZED_TIME, Some((b'Z', digits))) if digits.len() == 14 => (digits, 4),
+        _ => return Err(Error::InvalidCertificate),
+    };
+    if !digits.iter().all(u8::is_ascii_digit) {
+        return Err(Error::InvalidCertificate);
+    }
+    let number = |i: usize| (digits[i] - b'0') * 10 + (digits[i + 1] - b'0');
+    let year = match year_digits {
+        // two digit years are 1950 to 2049
+        2 if number(0) >= 50 => 1900 + number(0) as u16,
+        2 => 2000 + number(0) as u16,
+        _ => number(0) as u16 * 100 + number(2) as u16,
+    };
+    let i = year_digits;
+    let time = DateTime::new(year, number(i), number(i + 2), number(i + 4), number(i + 6), number(i + 8));
+    if time.is_valid() {
+        Ok(time)
+    } else {
+        Err(Error::InvalidCertificate)
+    }
+}
+
+/// Returns the OID, criticality and value of an extension.
+fn parse_extension(extension: &[u8]) -> Result<(&[u8], bool, &[u8])> {
+    let mut extension = Reader(extension);
+    let oid = extension.read(OBJECT_IDENTIFIER)?;
+    let critical = match extension.read_optional(BOOLEAN)? {
+        Some([0xFF]) => true,
+        // DER would omit the default, but it is commonly found
+        Some([0x00]) | None => false,
+        Some(_) => return Err(Error::InvalidCertificate),
+    };
+    let value = extension.read(OCTET_STRING)?;
+    extension.finish()?;
+    Ok((oid, critical, value))
+}
+
+fn parse_key_usage(value: &[u8]) -> Result<KeyUsage> {
+    let mut value = Reader(value);
+    let bits = match value.read(BIT_STRING)? {
+        [unused, bytes @ ..] if *unused < 8 && !bytes.is_empty() => bytes,
+        _ => return Err(Error::InvalidCertificate),
+    };
+    value.finish()?;
+    // named bit `n` is the `n`-th most significant bit, the ones after decipherOnly are unknown
+    let named_bits = bits.iter().take(2).enumerate()
+        .fold(0u16, |named_bits, (i, byte)| named_bits | (byte.reverse_bits() as u16) << (8 * i));
+    Ok(KeyUsage::from_bits_truncate(named_bits))
+}
+
+fn parse_basic_constraints(value: &[u8]) -> Result<BasicConstraints> {
+    let mut value = Reader(value);
+    let mut constraints = Reader(value.read(SEQUENCE)?);
+    value.finish()?;
+    let ca = match constraints.read_optional(BOOLEAN)? {
+        Some([0xFF]) => true,
+        Some([0x00]) | None => false,
+        Some(_) => return Err(Error::InvalidCertificate),
+    };
+    let path_length = match constraints.read_optional(INTEGER)? {
+        None => None,
+        Some([length]) if *length < 0x80 => Some(*length),
+        Some([0, length]) if *length >= 0x80 => Some(*length),
+        // longer than any chain we could verify
+        Some([first, _, ..]) if *first != 0 && *first < 0x80 => Some(u8::MAX),
+        Some(_) => return Err(Error::InvalidCertificate),
+    };
+    constraints.finish()?;
+    Ok(BasicConstraints { ca, path_length })
+}
+
+/// Decodes a DER encoded ECDSA-Sig-Value into the big endian r and s.
+fn parse_ecdsa_signature(signature: &[u8]) -> Result<[u8; 64]> {
+    let mut reader = Reader(signature);
+    let mut values = Reader(reader.read(SEQUENCE)?);
+    reader.finish()?;
+    let mut untagged = [0u8; 64];
+    for half in untagged.chunks_mut(32) {
+        let integer = values.read(INTEGER)?;
+        // positive and minimally encoded
+        let integer = match integer {
+            [0, rest @ ..] if matches!(rest.first(), Some(byte) if byte & 0x80 != 0) => rest,
+            [first, ..] if *first != 0 && first & 0x80 == 0 => integer,
+            _ => return Err(Error::InvalidCertificate),
+        };
+        if integer.len() > 32 {
+            return Err(Error::InvalidCertificate);
+        }
+        half[32 - integer.len()..].copy_from_slice(integer);
+    }
+    values.finish()?;
+    Ok(untagged)
+}
@@ -0,0 +1,95 @@
+use core::convert::TryInto;
+
+use trussed::client::mechanisms::{Aes128, Aes192, Aes256};
+use trussed::{client::CryptoClient as _, syscall, try_syscall};
+use trussed::error::Error;
+use trussed::types::Mechanism;
+use hex_literal::hex;
+
+mod client;
+
+use trussed::types::Location::*;
+
+#[test]
+fn aes_modes() {
+    client::get(|client| {
+        let key = syscall!(client.generate_aes128_key(Volatile)).key;
+        let message = [0x42u8; 48];
+
+        let ciphertext = syscall!(client.encrypt_aes128ecb(key, &message)).ciphertext;
+        assert_ne!(&ciphertext[..], &message[..]);
+        // ECB: equal plaintext blocks give equal ciphertext blocks
+        assert_eq!(&ciphertext[..16], &ciphertext[16..32]);
+        let plaintext = syscall!(client.decrypt_aes128ecb(key, &ciphertext)).plaintext.unwrap();
+        assert_eq!(&plaintext[..], &message[..]);
+
+        // CBC with zero IV agrees with ECB on the first block
+        let zero_iv = [0u8; 16];
+        let reply = syscall!(client.encrypt_aes128cbc(key, &message, Some(&zero_iv)));
+        assert_eq!(&reply.nonce[..], &zero_iv[..]);
+        assert_eq!(&reply.ciphertext[..16], &ciphertext[..16]);
+        assert_ne!(&reply.ciphertext[16..32], &ciphertext[16..32]);
+
+        let iv = [0x17u8; 16];
+        let ciphertext = syscall!(client.encrypt_aes128cbc(key, &message, Some(&iv))).ciphertext;
+        let plaintext = syscall!(client.decrypt_aes128cbc(key, &ciphertext, &iv)).plaintext.unwrap();
+        assert_eq!(&plaintext[..], &message[..]);
+
+        // without an IV, a random one is used and returned
+        let reply = syscall!(client.encrypt_aes128cbc(key, &message, None));
+        let random_iv: [u8; 16] = reply.nonce.as_slice().try_into().unwrap();
+        assert_ne!(random_iv, zero_iv);
+        assert_ne!(syscall!(client.encrypt_aes128cbc(key, &message, None)).nonce, reply.nonce);
+        let plaintext = syscall!(client.decrypt_aes128cbc(key, &reply.ciphertext, &random_iv)).plaintext.unwrap();
+        assert_eq!(&plaintext[..], &message[..]);
+
+        // no padding
+        assert_eq!(try_syscall!(client.encrypt_aes128ecb(key, &message[..20])).map(drop), Err(Error::WrongMessageLength));
+        assert_eq!(try_syscall!(client.encrypt_aes128cbc(key, &message[..20], None)).map(drop), Err(Error::WrongMessageLength));
+        assert_eq!(try_syscall!(client.decrypt_aes128cbc(key, &message[..20], &iv)).map(drop), Err(Error::WrongMessageLength));
+
+        // CTR: keystream is the encrypted counter block, messages need not be aligned
+        let counter_block = [0xFFu8; 16];
+        let keystream = syscall!(client.encrypt_aes128ecb(key, &counter_block)).ciphertext;
+        let ciphertext = syscall!(client.encrypt_aes128ctr(key, &[0u8; 20], Some(&counter_block))).ciphertext;
+        assert_eq!(ciphertext.len(), 20);
+        assert_eq!(&ciphertext[..16], &keystream[..]);
+        // ...and the counter wraps around to zero
+        let keystream = syscall!(client.encrypt_aes128ecb(key, &[0u8; 16])).ciphertext;
+        assert_eq!(&ciphertext[16..], &keystream[..4]);
+
+        let reply = syscall!(client.encrypt_aes128ctr(key, &message[..20], None));
+        let counter_block: [u8; 16] = reply.nonce.as_slice().try_into().unwrap();
+        let plaintext = syscall!(client.decrypt_aes128ctr(key, &reply.ciphertext, &counter_block)).plaintext.unwrap();
+        assert_eq!(&plaintext[..], &message[..20]);
+
+        let key192 = syscall!(client.generate_aes192_key(Volatile)).key;
+        let key256 = syscall!(client.generate_aes256_key(Internal)).key;
+        let message = [0x42u8; 32];
+
+        let ciphertext = syscall!(client.encrypt_aes192ecb(key192, &message)).ciphertext;
+        let plaintext = syscall!(client.decrypt_aes192ecb(key192, &ciphertext)).plaintext.unwrap();
+        assert_eq!(&plaintext[..], &message[..]);
+
+        let ciphertext = syscall!(client.encrypt_aes256ecb(key256, &message)).ciphertext;
+        let plaintext = syscall!(client.decrypt_aes256ecb(key256, &ciphertext)).plaintext.unwrap();
+        assert_eq!(&plaintext[..], &message[..]);
+
+        // keys are bound to their size
+        assert!(try_syscall!(client.encrypt_aes128ecb(key192, &message)).is_err());
+        assert!(try_syscall!(client.encrypt_aes192ecb(key256, &message)).is_err());
+
+        // NIST SP 800-38A, F.1.1, F.2.1 and F.5.1 (first block)
+        let key = syscall!(client.unsafe_inject_key(Mechanism::Aes128Ecb,
+            &hex!("2b7e151628aed2a6abf7158809cf4f3c"), Volatile)).key;
+        let plaintext = hex!("6bc1bee22e409f96e93d7e117393172a");
+        let ciphertext = syscall!(client.encrypt_aes128ecb(key, &plaintext)).ciphertext;
+        assert_eq!(&ciphertext[..], &hex!("3ad77bb40d7a3660a89ecaf32466ef97")[..]);
+        let iv = hex!("000102030405060708090a0b0c0d0e0f");
+        let ciphertext = syscall!(client.encrypt_aes128cbc(key, &plaintext, Some(&iv))).ciphertext;
+        assert_eq!(&ciphertext[..], &hex!("7649abac8119b246cee98e9b12e9197d")[..]);
+        let counter_block = hex!("f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff");
+        let ciphertext = syscall!(client.encrypt_aes128ctr(key, &plaintext, Some(&counter_block))).ciphertext;
+        assert_eq!(&ciphertext[..], &hex!("874d6191b620e3261bef6864990db6ce")[..]);
+    })
+}
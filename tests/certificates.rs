@@ -0,0 +1,29 @@
+use trussed::client::{CertificateClient as _, CryptoClient as _};
+use trussed::error::Error;
+use trussed::types::{DateTime, Location};
+use trussed::{syscall, try_syscall};
+use trussed::x509;
+
+mod client;
+
+// the simulated platform has no clock
+#[test]
+fn verify_without_clock() {
+    client::get(|client| {
+        let chain = syscall!(client.dice_certificates()).chain;
+        let device_id = x509::parse_chain(&chain).unwrap()[1].der;
+        let anchor = syscall!(client.write_certificate(Location::Volatile, device_id)).id;
+
+        // the validity periods can not be checked without a time
+        assert_eq!(
+            try_syscall!(client.verify_certificate_chain(&chain, &[anchor], None)).map(drop),
+            Err(Error::FunctionNotSupported),
+        );
+
+        let now = Some(DateTime::new(2025, 1, 1, 0, 0, 0));
+        assert_eq!(
+            try_syscall!(client.verify_certificate_chain(&chain, &[anchor], now)).map(|reply| reply.anchor),
+            Ok(anchor),
+        );
+    })
+}
@@ -4,46 +4,69 @@ trussed::platform!(Platform,
     UI: ui::UserInterface,
 );
 
-pub fn get<R>(
-        test: impl FnOnce(&mut trussed::ClientImplementation<&mut trussed::service::Service<Platform>>) -> R
+pub type Service = trussed::service::Service<Platform>;
+pub type Client<'a> = trussed::ClientImplementation<&'a mut Service>;
+
+#[allow(dead_code)] // not every test uses it
+pub fn get<R>(test: impl FnOnce(&mut Client<'_>) -> R) -> R {
+    get_with(Service::try_as_new_client, test)
+}
+
+/// Like `get`, with the test client added by `new_client`, e.g. to grant it permissions.
+pub fn get_with<R>(
+        new_client: for<'a> fn(&'a mut Service, &str) -> Result<Client<'a>, ()>,
+        test: impl FnOnce(&mut Client<'_>) -> R,
     )
         -> R
 {
     use trussed::Interchange as _;
+    use trussed::platform::Platform as _;
     unsafe { trussed::pipe::TrussedInterchange::reset_claims(); }
     let trussed_platform = init_platform();
-    let mut trussed_service = trussed::service::Service::new(trussed_platform);
+    let store = trussed_platform.store();
 
-    // preparations for attestation
-    let client_id = "attn";
-    let mut attn_client = trussed_service.try_as_new_client(client_id).unwrap();
+    // provision the attestation keys, as during manufacturing
+    {
+        let mut trussed_service = trussed::service::Service::new(trussed_platform);
+        let mut provisioner = trussed_service.try_as_new_provisioner("provisioner").unwrap();
 
-    use trussed::client::mechanisms::Ed255;
-    let _attn_ed255_key = trussed::syscall!(attn_client.generate_ed255_private_key(trussed::types::Location::Internal)).key;
-    use trussed::client::mechanisms::P256;
-    let _attn_p256_key = trussed::syscall!(attn_client.generate_p256_private_key(trussed::types::Location::Internal)).key;
+        use trussed::client::ManagementClient as _;
+        use trussed::types::Mechanism;
+        trussed::syscall!(provisioner.provision_attestation_key(Mechanism::Ed255, None));
+        trussed::syscall!(provisioner.provision_attestation_key(Mechanism::P256, None));
+    }
 
-    // destroy this attestation client
+    // restart without the provisioner, whose endpoint would otherwise answer the test client
     unsafe { trussed::pipe::TrussedInterchange::reset_claims(); }
+    let mut trussed_service = trussed::service::Service::new(platform(store));
 
     let client_id = "test";
-    let mut trussed_client = trussed_service.try_as_new_client(client_id).unwrap();
+    let mut trussed_client = new_client(&mut trussed_service, client_id).unwrap();
     test(&mut trussed_client)
 }
 
 pub fn init_platform() -> Platform {
-    use rand_core::SeedableRng as _;
-    // causing a regression again
-    // let rng = chacha20::ChaCha8Rng::from_rng(rand_core::OsRng).unwrap();
-    let rng = chacha20::ChaCha8Rng::from_seed([42u8; 32]);
     let store = store::Store::format(
         store::InternalStorage::new(),
         store::ExternalStorage::new(),
         store::VolatileStorage::new(),
         );
+    platform(store)
+}
+
+fn platform(store: store::Store) -> Platform {
+    use rand_core::SeedableRng as _;
+    // causing a regression again
+    // let rng = chacha20::ChaCha8Rng::from_rng(rand_core::OsRng).unwrap();
+    let rng = chacha20::ChaCha8Rng::from_seed([42u8; 32]);
     let ui = ui::UserInterface::new();
 
-    let platform = Platform::new(rng, store, ui);
+    // fixed DICE inputs, as a boot ROM would pass them
+    let dice_inputs = trussed::platform::DiceInputs {
+        compound_device_identifier: [0x5A; 32],
+        measurement: [0xA5; 32],
+    };
+    let platform = Platform::new(rng, store, ui).with_dice_inputs(dice_inputs);
 
     platform
 }
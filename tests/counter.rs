@@ -3,7 +3,7 @@ mod store;
 
 use trussed::{
     client::CounterClient as _,
-    syscall,
+    syscall, try_syscall,
     types::Location::*,
 };
 
@@ -53,5 +53,35 @@ fn counter_client() {
             assert_eq!(syscall!(client.increment_counter(jd)).counter, j);
         }
 
+        // reading does not increment
+        assert_eq!(syscall!(client.read_counter(id)).counter, 1_000);
+        assert_eq!(syscall!(client.read_counter(id)).counter, 1_000);
+
+        assert_eq!(syscall!(client.increment_counter_by(id, 10)).counter, 1_000);
+        assert_eq!(syscall!(client.read_counter(id)).counter, 1_010);
+        assert!(try_syscall!(client.increment_counter_by(id, u128::MAX)).is_err());
+        assert_eq!(syscall!(client.read_counter(id)).counter, 1_010);
+
+        // counters never decrease
+        assert_eq!(syscall!(client.set_counter_at_least(id, 5)).counter, 1_010);
+        assert_eq!(syscall!(client.set_counter_at_least(id, 2_000)).counter, 2_000);
+        assert_eq!(syscall!(client.increment_counter(id)).counter, 2_000);
+
+        let listed = syscall!(client.list_counters(None, None));
+        assert_eq!(listed.num_counters, 2);
+        assert!(listed.counters.contains(&(id, Volatile, 2_001)));
+        assert!(listed.counters.contains(&(jd, External, 1_000)));
+        let external = syscall!(client.list_counters(Some(External), None));
+        assert_eq!(&external.counters[..], &[(jd, External, 1_000)]);
+        let (first, _, _) = listed.counters[0];
+        let rest = syscall!(client.list_counters(None, Some(first)));
+        assert_eq!(rest.num_counters, 2);
+        assert_eq!(&rest.counters[..], &listed.counters[1..]);
+
+        syscall!(client.delete_counter(id));
+        assert!(try_syscall!(client.read_counter(id)).is_err());
+        assert!(try_syscall!(client.delete_counter(id)).is_err());
+        assert_eq!(syscall!(client.list_counters(None, None)).num_counters, 1);
+
     });
 }
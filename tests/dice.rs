@@ -0,0 +1,37 @@
+use trussed::client::CryptoClient as _;
+use trussed::syscall;
+use trussed::x509::{self, SignatureAlgorithm};
+
+mod client;
+
+// 2.23.133.5.4.1 tcg-dice-TcbInfo
+const TCB_INFO_OID: &[u8] = &[0x67, 0x81, 0x05, 0x05, 0x04, 0x01];
+
+#[test]
+fn dice() {
+    client::get_with(client::Service::try_as_new_dice_signer, |client| {
+        let chain = syscall!(client.dice_certificates()).chain;
+        let certificates = x509::parse_chain(&chain).expect("valid chain");
+        assert_eq!(certificates.len(), 2);
+        let (alias, device_id) = (&certificates[0], &certificates[1]);
+
+        // the DeviceID certificate is self-signed, and issues the Alias certificate
+        x509::verify_chain(&certificates, device_id, None).unwrap();
+        assert!(device_id.is_signed_by(&device_id.public_key));
+
+        let (critical, tcb_info) = alias.extension(TCB_INFO_OID).expect("TcbInfo extension");
+        assert!(!critical);
+        assert!(tcb_info.ends_with(&[0xA5; 32]));
+
+        let message = b"measured boot";
+        let signature = syscall!(client.dice_sign(message)).signature;
+        assert!(alias.public_key.verify(SignatureAlgorithm::Ed255, message, &signature));
+        assert!(!device_id.public_key.verify(SignatureAlgorithm::Ed255, message, &signature));
+
+        // the keys are derived, not generated
+        let again = syscall!(client.dice_certificates()).chain;
+        let again = x509::parse_chain(&again).unwrap();
+        assert_eq!(again[0].public_key.as_bytes(), alias.public_key.as_bytes());
+        assert_eq!(again[1].public_key.as_bytes(), device_id.public_key.as_bytes());
+    })
+}
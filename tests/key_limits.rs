@@ -0,0 +1,23 @@
+use trussed::client::CryptoClient as _;
+use trussed::error::Error;
+use trussed::key::{Deadline, Limits};
+use trussed::try_syscall;
+
+mod client;
+
+use trussed::types::{Mechanism, StorageAttributes};
+
+// the simulated platform has no clock
+#[test]
+fn deadlines_without_clock() {
+    client::get(|client| {
+        let attributes = StorageAttributes::new().set_limits(Limits::new().set_deadline(Deadline::Time(1_600_000_000)));
+        assert_eq!(
+            try_syscall!(client.generate_key(Mechanism::P256, attributes)).map(drop),
+            Err(Error::FunctionNotSupported),
+        );
+
+        let attributes = StorageAttributes::new().set_limits(Limits::new().set_deadline(Deadline::Uptime(60)));
+        try_syscall!(client.generate_key(Mechanism::P256, attributes)).expect("no errors");
+    })
+}
@@ -0,0 +1,30 @@
+use trussed::client::mechanisms::Tdes;
+use trussed::syscall;
+
+mod client;
+
+use trussed::types::Location::*;
+
+#[test]
+fn tdes_modes() {
+    client::get(|client| {
+        let key = syscall!(client.generate_tdes_key(Volatile)).key;
+        let message = [0x42u8; 16];
+
+        let block = syscall!(client.encrypt_tdes(key, &message[..8])).ciphertext;
+        let ciphertext = syscall!(client.encrypt_tdesecb(key, &message)).ciphertext;
+        assert_eq!(&ciphertext[..8], &block[..]);
+        assert_eq!(&ciphertext[8..], &block[..]);
+        let plaintext = syscall!(client.decrypt_tdesecb(key, &ciphertext)).plaintext.unwrap();
+        assert_eq!(&plaintext[..], &message[..]);
+
+        let iv = [0x17u8; 8];
+        let ciphertext = syscall!(client.encrypt_tdescbc(key, &message, Some(&iv))).ciphertext;
+        let plaintext = syscall!(client.decrypt_tdescbc(key, &ciphertext, &iv)).plaintext.unwrap();
+        assert_eq!(&plaintext[..], &message[..]);
+
+        let ciphertext = syscall!(client.encrypt_tdesctr(key, &message[..11], Some(&iv))).ciphertext;
+        let plaintext = syscall!(client.decrypt_tdesctr(key, &ciphertext, &iv)).plaintext.unwrap();
+        assert_eq!(&plaintext[..], &message[..11]);
+    })
+}